@@ -0,0 +1,30 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.24;
+
+/// @notice Reverts with `message` when a condition does not hold.
+/// @dev Weiroll's VM wraps the `Error(string)` revert into `ExecutionFailed`, so the message
+/// reaches the caller along with the failing command's index. The functions keep `message`
+/// last so its `Error(string)` encoding can be copied straight from calldata.
+contract Assertions {
+    function assertTrue(bool condition, string calldata message) external pure {
+        if (!condition) {
+            _fail(message);
+        }
+    }
+
+    function assertEq(uint256 a, uint256 b, string calldata message) external pure {
+        if (a != b) {
+            _fail(message);
+        }
+    }
+
+    function assertGte(uint256 a, uint256 b, string calldata message) external pure {
+        if (a < b) {
+            _fail(message);
+        }
+    }
+
+    function _fail(string calldata message) private pure {
+        revert(message);
+    }
+}
@@ -0,0 +1,41 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.24;
+
+/// @notice Re-encodes the arguments of a call as a single array or tuple, so a weiroll command
+/// can combine several state slots into one argument.
+/// @dev The elements follow the declared parameters, which Solidity cannot express, so calls are
+/// dispatched in `fallback`. The VM strips the leading offset of a dynamic return value, so each
+/// function returns `0x20` followed by the encoding of the value.
+contract Builder {
+    /// `buildTuple()`: the elements, encoded as a tuple.
+    bytes4 private constant BUILD_TUPLE = 0xfb477586;
+    /// `buildArray(uint256 length)`: `length` static elements.
+    bytes4 private constant BUILD_ARRAY = 0x95d4ad11;
+    /// `buildDynamicArray(uint256 length)`: `length` dynamic elements.
+    bytes4 private constant BUILD_DYNAMIC_ARRAY = 0x55273660;
+
+    fallback() external {
+        bytes4 selector = msg.sig;
+        if (selector != BUILD_TUPLE && selector != BUILD_ARRAY && selector != BUILD_DYNAMIC_ARRAY) {
+            revert();
+        }
+
+        assembly {
+            // An array's length is its first argument, so the arguments are already laid out
+            // as the array's encoding, apart from the element offsets.
+            mstore(0, 0x20)
+            let size := sub(calldatasize(), 4)
+            calldatacopy(0x20, 4, size)
+
+            if eq(selector, BUILD_DYNAMIC_ARRAY) {
+                // Offsets were relative to the length word rather than the first element
+                let end := add(0x40, shl(5, mload(0x20)))
+                for { let p := 0x40 } lt(p, end) { p := add(p, 0x20) } {
+                    mstore(p, sub(mload(p), 0x20))
+                }
+            }
+
+            return(0, add(size, 0x20))
+        }
+    }
+}
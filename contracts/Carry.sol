@@ -0,0 +1,57 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.24;
+
+/// @notice Carries values between the transactions of a split plan.
+/// @dev Must be CALLed by the VM, which stores a value after the command producing it and loads
+/// it back in a later transaction. Values are kept per caller, so VMs cannot read or overwrite
+/// each other's values. Each value is stored as the return data `load` produces, which the VM
+/// writes back into a state slot exactly as the producing command did.
+contract Carry {
+    /// The return data of `load(index)`, for `msg.sender`: its length at `slot`, and its words
+    /// from `slot + 1`.
+    function slotOf(uint256 index) private view returns (uint256 slot) {
+        assembly {
+            mstore(0, caller())
+            mstore(0x20, index)
+            slot := keccak256(0, 0x40)
+        }
+    }
+
+    /// @notice Stores a static value.
+    function store(uint256 index, bytes32 value) external {
+        uint256 slot = slotOf(index);
+        assembly {
+            sstore(slot, 0x20)
+            sstore(add(slot, 1), value)
+        }
+    }
+
+    /// @notice Stores a dynamic value, which the VM passes as the raw contents of its state slot
+    /// rather than as ABI-encoded `bytes`.
+    function storeDynamic(uint256 index, bytes calldata) external {
+        uint256 slot = slotOf(index);
+        assembly {
+            let offset := add(4, calldataload(0x24))
+            let length := sub(calldatasize(), offset)
+            mstore(0, 0x20)
+            calldatacopy(0x20, offset, length)
+            let total := add(length, 0x20)
+            sstore(slot, total)
+            for { let i := 0 } lt(i, total) { i := add(i, 0x20) } {
+                sstore(add(add(slot, shr(5, i)), 1), mload(i))
+            }
+        }
+    }
+
+    /// @notice Returns the value stored at `index`, encoded as the producing command returned it.
+    function load(uint256 index) external view {
+        uint256 slot = slotOf(index);
+        assembly {
+            let length := sload(slot)
+            for { let i := 0 } lt(i, length) { i := add(i, 0x20) } {
+                mstore(i, sload(add(add(slot, shr(5, i)), 1)))
+            }
+            return(0, length)
+        }
+    }
+}
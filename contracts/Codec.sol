@@ -0,0 +1,75 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.24;
+
+/// @notice Encodes any number of values into a single `bytes` value, so a weiroll plan can build
+/// calldata or signature payloads from earlier outputs.
+/// @dev The values follow the declared parameters, which Solidity cannot express, so calls are
+/// dispatched in `fallback`. The output is returned as `bytes`.
+contract Codec {
+    /// `encode()`: `abi.encode` of the values.
+    bytes4 private constant ENCODE = 0xbd14598f;
+    /// `encodePacked(bytes layout)`: `abi.encodePacked` of the values. Each byte of `layout`
+    /// describes one value: 0 for `bytes` or `string`, the width `n` of a right-aligned static
+    /// value such as `uintN` or `address`, or `0x80 | n` for a left-aligned `bytesN`.
+    bytes4 private constant ENCODE_PACKED = 0xf178d5ac;
+    /// `concat()`: the contents of the `bytes` values, one after another.
+    bytes4 private constant CONCAT = 0x6bdbf8e6;
+
+    fallback(bytes calldata input) external returns (bytes memory) {
+        bytes4 selector = msg.sig;
+        bytes calldata args = input[4:];
+
+        if (selector == ENCODE) {
+            return _wrap(args);
+        }
+
+        if (selector == CONCAT) {
+            bytes memory out;
+            uint256 count = args.length == 0 ? 0 : uint256(bytes32(args[:32])) / 32;
+            for (uint256 i = 0; i < count; i++) {
+                out = bytes.concat(out, _bytesAt(args, i));
+            }
+            return _wrap(out);
+        }
+
+        if (selector == ENCODE_PACKED) {
+            bytes memory layout = abi.decode(args, (bytes));
+            bytes memory out;
+            for (uint256 i = 0; i < layout.length; i++) {
+                uint8 kind = uint8(layout[i]);
+                if (kind == 0) {
+                    out = bytes.concat(out, _bytesAt(args, i + 1));
+                } else {
+                    bytes32 word = bytes32(args[32 * (i + 1):32 * (i + 2)]);
+                    uint256 width = kind & 0x7f;
+                    if (kind & 0x80 == 0) {
+                        word <<= 8 * (32 - width);
+                    }
+                    out = bytes.concat(out, _prefix(word, width));
+                }
+            }
+            return _wrap(out);
+        }
+
+        revert();
+    }
+
+    /// The contents of the `bytes` value whose offset is the `index`th word of `args`.
+    function _bytesAt(bytes calldata args, uint256 index) private pure returns (bytes calldata) {
+        uint256 offset = uint256(bytes32(args[32 * index:32 * (index + 1)]));
+        uint256 length = uint256(bytes32(args[offset:offset + 32]));
+        return args[offset + 32:offset + 32 + length];
+    }
+
+    function _prefix(bytes32 word, uint256 width) private pure returns (bytes memory out) {
+        out = new bytes(width);
+        for (uint256 i = 0; i < width; i++) {
+            out[i] = word[i];
+        }
+    }
+
+    /// Fallback outputs are returned as they are, so `bytes` outputs are encoded explicitly.
+    function _wrap(bytes memory out) private pure returns (bytes memory) {
+        return abi.encode(out);
+    }
+}
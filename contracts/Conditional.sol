@@ -0,0 +1,37 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.24;
+
+interface IVM {
+    function execute(bytes32[] calldata commands, bytes[] memory state)
+        external
+        payable
+        returns (bytes[] memory);
+}
+
+/// @notice Selects whether a subplan runs, so a plan can branch on an earlier result.
+/// @dev Must be DELEGATECALLed by the VM: a selected subplan runs by re-entering the VM's
+/// `execute` through `address(this)`, and its reverts are passed on.
+contract Conditional {
+    /// @return The state after `commands` if `condition` holds, or `state` unchanged.
+    function executeIf(bool condition, bytes32[] calldata commands, bytes[] memory state)
+        external
+        returns (bytes[] memory)
+    {
+        return condition ? _execute(commands, state) : state;
+    }
+
+    /// @return The state after `commands` unless `condition` holds, or `state` unchanged.
+    function executeUnless(bool condition, bytes32[] calldata commands, bytes[] memory state)
+        external
+        returns (bytes[] memory)
+    {
+        return condition ? state : _execute(commands, state);
+    }
+
+    function _execute(bytes32[] calldata commands, bytes[] memory state)
+        private
+        returns (bytes[] memory)
+    {
+        return IVM(address(this)).execute(commands, state);
+    }
+}
@@ -0,0 +1,45 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.24;
+
+interface IVM {
+    function execute(bytes32[] calldata commands, bytes[] memory state)
+        external
+        payable
+        returns (bytes[] memory);
+}
+
+/// @notice Runs a subplan once per element of an array.
+/// @dev Both functions must be DELEGATECALLed by the VM: `forEach` re-enters the VM's `execute`
+/// through `address(this)` for each element, passing on the state each iteration returns, and
+/// keeps the current element in transient storage for `element`.
+contract Loop {
+    /// `keccak256("weiroll.Loop.element")`
+    bytes32 private constant ELEMENT =
+        0x2d529f7d98aea30400ee3328d8ad8d1841da39d942c20567cb91c4148c805fc2;
+
+    /// @return The state after running `commands` for every element of `items`.
+    function forEach(
+        bytes32[] calldata items,
+        uint256 maxIterations,
+        bytes32[] calldata commands,
+        bytes[] memory state
+    ) external returns (bytes[] memory) {
+        require(items.length <= maxIterations);
+
+        for (uint256 i; i < items.length; ++i) {
+            bytes32 item = items[i];
+            assembly {
+                tstore(ELEMENT, item)
+            }
+            state = IVM(address(this)).execute(commands, state);
+        }
+        return state;
+    }
+
+    /// @return item The element the current iteration of `forEach` runs for.
+    function element() external view returns (bytes32 item) {
+        assembly {
+            item := tload(ELEMENT)
+        }
+    }
+}
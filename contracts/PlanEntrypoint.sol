@@ -0,0 +1,84 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.24;
+
+/// @notice Executes plans signed off-chain as EIP-712 `SignedPlan`s.
+/// @dev Plans are forwarded to `vm.execute` with the signer appended to the calldata, as an
+/// ERC-2771 trusted forwarder does, so the VM (typically a smart account which embeds one) can
+/// authorize the signer. Each signer has a sequential nonce, and a plan is rejected once its
+/// deadline has passed.
+contract PlanEntrypoint {
+    bytes32 private constant DOMAIN_TYPEHASH = keccak256(
+        "EIP712Domain(string name,string version,uint256 chainId,address verifyingContract)"
+    );
+    bytes32 private constant PLAN_TYPEHASH = keccak256("Plan(bytes32[] commands,bytes[] state)");
+    bytes32 private constant SIGNED_PLAN_TYPEHASH = keccak256(
+        "SignedPlan(address vm,Plan plan,uint256 nonce,uint256 deadline)"
+        "Plan(bytes32[] commands,bytes[] state)"
+    );
+    /// Half the order of secp256k1; larger `s` values are malleable.
+    uint256 private constant MAX_S =
+        0x7fffffffffffffffffffffffffffffff5d576e7357a4501ddfe92f46681b20a0;
+
+    mapping(address => uint256) public nonces;
+
+    function DOMAIN_SEPARATOR() public view returns (bytes32) {
+        return keccak256(
+            abi.encode(
+                DOMAIN_TYPEHASH,
+                keccak256("WeirollEntrypoint"),
+                keccak256("1"),
+                block.chainid,
+                address(this)
+            )
+        );
+    }
+
+    /// @return The state returned by `vm.execute(commands, state)`.
+    function executeSigned(
+        address vm,
+        bytes32[] calldata commands,
+        bytes[] calldata state,
+        uint256 nonce,
+        uint256 deadline,
+        bytes calldata signature
+    ) external payable returns (bytes[] memory) {
+        require(block.timestamp <= deadline);
+
+        bytes32[] memory stateHashes = new bytes32[](state.length);
+        for (uint256 i; i < state.length; ++i) {
+            stateHashes[i] = keccak256(state[i]);
+        }
+        bytes32 planHash = keccak256(
+            abi.encode(
+                PLAN_TYPEHASH,
+                keccak256(abi.encodePacked(commands)),
+                keccak256(abi.encodePacked(stateHashes))
+            )
+        );
+        bytes32 structHash =
+            keccak256(abi.encode(SIGNED_PLAN_TYPEHASH, vm, planHash, nonce, deadline));
+        bytes32 digest = keccak256(abi.encodePacked("\x19\x01", DOMAIN_SEPARATOR(), structHash));
+
+        require(signature.length == 65);
+        bytes32 r = bytes32(signature[0:32]);
+        bytes32 s = bytes32(signature[32:64]);
+        uint8 v = uint8(signature[64]);
+        require(uint256(s) <= MAX_S);
+        address signer = ecrecover(digest, v, r, s);
+        require(signer != address(0));
+
+        require(nonces[signer]++ == nonce);
+
+        (bool ok, bytes memory result) = vm.call{value: msg.value}(
+            abi.encodePacked(
+                abi.encodeWithSignature("execute(bytes32[],bytes[])", commands, state), signer
+            )
+        );
+        if (!ok) {
+            assembly {
+                revert(add(result, 0x20), mload(result))
+            }
+        }
+        return abi.decode(result, (bytes[]));
+    }
+}
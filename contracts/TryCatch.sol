@@ -0,0 +1,44 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.24;
+
+interface IVM {
+    function execute(bytes32[] calldata commands, bytes[] memory state)
+        external
+        payable
+        returns (bytes[] memory);
+}
+
+/// @notice Runs a subplan which is allowed to revert.
+/// @dev Both functions must be DELEGATECALLed by the VM: `tryExecute` re-enters the VM's
+/// `execute` through `address(this)`, and records the outcome in transient storage for
+/// `succeeded`, which must be planned directly after it.
+contract TryCatch {
+    /// `keccak256("weiroll.TryCatch.succeeded")`
+    bytes32 private constant SUCCEEDED =
+        0x0b1b108ab2ef536084eda67e5860cccb12883b63e468186ae9ee36d8d77c3948;
+
+    /// @return The state after `commands`, or `state` unchanged if they reverted.
+    function tryExecute(bytes32[] calldata commands, bytes[] memory state)
+        external
+        returns (bytes[] memory)
+    {
+        try IVM(address(this)).execute(commands, state) returns (bytes[] memory next) {
+            assembly {
+                tstore(SUCCEEDED, 1)
+            }
+            return next;
+        } catch {
+            assembly {
+                tstore(SUCCEEDED, 0)
+            }
+            return state;
+        }
+    }
+
+    /// @return ok Whether the last `tryExecute` succeeded.
+    function succeeded() external view returns (bool ok) {
+        assembly {
+            ok := tload(SUCCEEDED)
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use alloy::node_bindings::Anvil;
+use alloy::primitives::U256;
+use alloy::providers::{Provider, ProviderBuilder};
+use alloy::signers::local::PrivateKeySigner;
+use weiroll::{
+    Planner,
+    bindings::{math::Math, testable_vm::TestableVM},
+    helpers::entrypoint::PlanEntrypoint,
+    signing::SignedPlan,
+};
+
+#[tokio::main]
+pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("Spawning anvil..");
+    let anvil = Anvil::new().spawn();
+    let relayer = anvil
+        .keys()
+        .first()
+        .cloned()
+        .ok_or_else(|| std::io::Error::other("anvil returned no keys"))?;
+    let relayer: PrivateKeySigner = relayer.into();
+
+    let provider = ProviderBuilder::new()
+        .wallet(relayer)
+        .connect(&anvil.endpoint())
+        .await?;
+    let chain_id = provider.get_chain_id().await?;
+
+    println!("Deploying contracts..");
+    let math = Math::deploy(&provider).await?;
+    let vm = TestableVM::deploy(&provider).await?;
+    let entrypoint = PlanEntrypoint::deploy(&provider).await?;
+
+    let mut planner = Planner::default();
+    let sum =
+        weiroll::call_contract!(&mut planner, &math, Math::addCall[U256::from(1), U256::from(2)])?;
+    weiroll::call_contract!(&mut planner, &math, Math::mulCall[sum, U256::from(7)])?;
+    println!("Plan hash: {}", planner.plan_hash()?);
+
+    println!("Signing..");
+    let signer = PrivateKeySigner::random();
+    let nonce = entrypoint.nonces(signer.address()).call().await?;
+    let signed = SignedPlan::new(*vm.address(), &planner, nonce, U256::MAX)?;
+    let signature = signed
+        .sign(&signer, *entrypoint.address(), chain_id)
+        .await?;
+    let recovered = signed.recover(&signature, *entrypoint.address(), chain_id)?;
+    assert_eq!(recovered, signer.address());
+    println!("Signed by {recovered}");
+
+    let separator = entrypoint.DOMAIN_SEPARATOR().call().await?;
+    assert_eq!(
+        separator,
+        SignedPlan::domain(*entrypoint.address(), chain_id).separator()
+    );
+
+    println!("Relaying..");
+    let receipt = entrypoint
+        .executeSigned(
+            signed.vm,
+            signed.plan.commands.clone(),
+            signed.plan.state.clone(),
+            signed.nonce,
+            signed.deadline,
+            signature.as_bytes().to_vec().into(),
+        )
+        .send()
+        .await?
+        .get_receipt()
+        .await?;
+    assert!(receipt.status());
+    println!("Executed in {}", receipt.transaction_hash);
+
+    let nonce = entrypoint.nonces(signer.address()).call().await?;
+    assert_eq!(nonce, U256::from(1));
+    println!("Next nonce: {nonce}");
+
+    println!("Replaying..");
+    let replay = entrypoint
+        .executeSigned(
+            signed.vm,
+            signed.plan.commands.clone(),
+            signed.plan.state.clone(),
+            signed.nonce,
+            signed.deadline,
+            signature.as_bytes().to_vec().into(),
+        )
+        .call()
+        .await;
+    assert!(replay.is_err());
+    println!("Replay rejected");
+
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use alloy::node_bindings::Anvil;
+use alloy::primitives::{U256, address};
+use alloy::providers::ProviderBuilder;
+use alloy::signers::local::PrivateKeySigner;
+use weiroll::{
+    Planner, ProviderError, WeirollProviderExt,
+    bindings::{events::Events, math::Math, revert::Revert, testable_vm::TestableVM},
+    helpers::assertions::Assertions,
+    testable_vm_override,
+};
+
+#[tokio::main]
+pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("Spawning anvil..");
+    let anvil = Anvil::new().spawn();
+    let wallet = anvil
+        .keys()
+        .first()
+        .cloned()
+        .ok_or_else(|| std::io::Error::other("anvil returned no keys"))?;
+    let wallet: PrivateKeySigner = wallet.into();
+
+    let provider = ProviderBuilder::new()
+        .wallet(wallet)
+        .connect(&anvil.endpoint())
+        .await?;
+
+    println!("Deploying contracts..");
+    let math = Math::deploy(&provider).await?;
+    let events = Events::deploy(&provider).await?;
+    let revert = Revert::deploy(&provider).await?;
+    let assertions = Assertions::deploy(&provider).await?;
+
+    let mut planner = Planner::default();
+    let sum =
+        weiroll::call_contract!(&mut planner, &math, Math::addCall[U256::from(1), U256::from(2)])?;
+    let product =
+        weiroll::call_contract!(&mut planner, &math, Math::mulCall[sum.clone(), U256::from(7)])?;
+    weiroll::call_contract!(&mut planner, &events, Events::logUintCall[product.clone()])?;
+
+    println!("Simulating against an injected VM..");
+    let injected_vm = address!("0x00000000000000000000000000000000000fee11");
+    let simulation = provider
+        .simulate_plan(
+            injected_vm,
+            &planner,
+            Some(testable_vm_override(injected_vm)),
+        )
+        .await?;
+    println!("sum = {:?}", simulation.get(&sum)?);
+    println!("product = {:?}", simulation.get(&product)?);
+
+    println!("Executing..");
+    let vm = TestableVM::deploy(&provider).await?;
+    let receipt = provider
+        .send_plan(*vm.address(), &planner)
+        .await?
+        .get_receipt()
+        .await?;
+    println!("Executed in {}", receipt.transaction_hash);
+
+    println!("Executing a failing plan..");
+    let mut failing = Planner::default();
+    weiroll::call_contract!(&mut failing, &events, Events::logUintCall[U256::from(1)])?;
+    weiroll::call_contract!(&mut failing, &revert, Revert::failCall[])?;
+    match provider.send_plan(*vm.address(), &failing).await {
+        Err(ProviderError::ExecutionFailed {
+            command, message, ..
+        }) => println!("command {command:?} failed: {message}"),
+        other => println!("unexpected result: {other:?}"),
+    }
+
+    println!("Executing a plan with a failing assertion..");
+    let mut asserting = Planner::default();
+    let sum = weiroll::call_contract!(&mut asserting, &math, Math::addCall[U256::from(1), U256::from(2)])?;
+    asserting.assertions(*assertions.address()).assert_gte(
+        sum,
+        U256::from(4),
+        String::from("sum below minimum"),
+    )?;
+    match provider.send_plan(*vm.address(), &asserting).await {
+        Err(ProviderError::ExecutionFailed {
+            command, message, ..
+        }) => println!("command {command:?} failed: {message}"),
+        other => println!("unexpected result: {other:?}"),
+    }
+
+    Ok(())
+}
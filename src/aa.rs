@@ -0,0 +1,303 @@
+//! ERC-4337 user operations for smart accounts which embed a weiroll VM.
+//!
+//! The account calls `execute(bytes32[],bytes[])` on itself, so the user operation's `callData`
+//! is the planned `execute` call. Hashes follow the EntryPoint v0.7 `PackedUserOperation` format.
+//!
+//! Only `call_gas_limit` is estimated here, see [`UserOperation::estimate_call_gas`].
+//! `verification_gas_limit` depends on the account's validation logic and `pre_verification_gas`
+//! on the bundler's overhead model, so both are out of scope and must be set by the caller,
+//! typically from the bundler's `eth_estimateUserOperationGas`.
+
+use crate::Planner;
+use crate::error::{ProviderError, WeirollError};
+
+use alloy::network::{Network, TransactionBuilder};
+use alloy::primitives::{Address, B256, Bytes, U256, keccak256};
+use alloy::providers::Provider;
+use alloy::signers::Signer;
+use alloy::sol;
+use alloy::sol_types::SolValue;
+
+sol! {
+    /// A user operation as consumed by EntryPoint v0.7 `handleOps`.
+    #[derive(Debug, Default, PartialEq)]
+    struct PackedUserOperation {
+        address sender;
+        uint256 nonce;
+        bytes initCode;
+        bytes callData;
+        bytes32 accountGasLimits;
+        uint256 preVerificationGas;
+        bytes32 gasFees;
+        bytes paymasterAndData;
+        bytes signature;
+    }
+}
+
+/// An unpacked user operation.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UserOperation {
+    pub sender: Address,
+    pub nonce: U256,
+    pub init_code: Bytes,
+    pub call_data: Bytes,
+    /// Not estimated by this crate, see the [module docs](self)
+    pub verification_gas_limit: u128,
+    /// See [`UserOperation::estimate_call_gas`]
+    pub call_gas_limit: u128,
+    /// Not estimated by this crate, see the [module docs](self)
+    pub pre_verification_gas: U256,
+    pub max_priority_fee_per_gas: u128,
+    pub max_fee_per_gas: u128,
+    pub paymaster_and_data: Bytes,
+    pub signature: Bytes,
+}
+
+/// Packs two 128-bit values into a single word, `high` first.
+fn pack_u128(high: u128, low: u128) -> B256 {
+    let mut word = [0u8; 32];
+    word[..16].copy_from_slice(&high.to_be_bytes());
+    word[16..].copy_from_slice(&low.to_be_bytes());
+    word.into()
+}
+
+impl UserOperation {
+    /// A user operation in which `account` executes `planner` on its own VM.
+    pub fn execute_plan(
+        account: Address,
+        nonce: U256,
+        planner: &Planner<'_>,
+    ) -> Result<Self, WeirollError> {
+        Ok(Self {
+            sender: account,
+            nonce,
+            call_data: planner.execute_calldata()?,
+            ..Default::default()
+        })
+    }
+
+    /// Packs gas limits and fees into the EntryPoint v0.7 layout.
+    pub fn pack(&self) -> PackedUserOperation {
+        PackedUserOperation {
+            sender: self.sender,
+            nonce: self.nonce,
+            initCode: self.init_code.clone(),
+            callData: self.call_data.clone(),
+            accountGasLimits: pack_u128(self.verification_gas_limit, self.call_gas_limit),
+            preVerificationGas: self.pre_verification_gas,
+            gasFees: pack_u128(self.max_priority_fee_per_gas, self.max_fee_per_gas),
+            paymasterAndData: self.paymaster_and_data.clone(),
+            signature: self.signature.clone(),
+        }
+    }
+
+    /// The `userOpHash` the EntryPoint at `entry_point` computes on `chain_id`.
+    pub fn hash(&self, entry_point: Address, chain_id: u64) -> B256 {
+        let op = self.pack();
+        let inner = keccak256(
+            (
+                op.sender,
+                op.nonce,
+                keccak256(&op.initCode),
+                keccak256(&op.callData),
+                op.accountGasLimits,
+                op.preVerificationGas,
+                op.gasFees,
+                keccak256(&op.paymasterAndData),
+            )
+                .abi_encode(),
+        );
+
+        keccak256((inner, entry_point, U256::from(chain_id)).abi_encode())
+    }
+
+    /// Signs the `userOpHash` as an EIP-191 message and stores the signature.
+    ///
+    /// This is the scheme used by the reference `SimpleAccount`; accounts which validate a
+    /// different scheme should set [`UserOperation::signature`] themselves.
+    pub async fn sign<S: Signer + Sync + ?Sized>(
+        &mut self,
+        signer: &S,
+        entry_point: Address,
+        chain_id: u64,
+    ) -> alloy::signers::Result<()> {
+        let hash = self.hash(entry_point, chain_id);
+        let signature = signer.sign_message(hash.as_slice()).await?;
+        self.signature = signature.as_bytes().to_vec().into();
+        Ok(())
+    }
+
+    /// Sets `call_gas_limit` by estimating the account's self-call as sent by `entry_point`.
+    ///
+    /// `verification_gas_limit` and `pre_verification_gas` are left unchanged.
+    pub async fn estimate_call_gas<N, P>(
+        &mut self,
+        provider: &P,
+        entry_point: Address,
+    ) -> Result<u64, ProviderError>
+    where
+        N: Network,
+        P: Provider<N>,
+    {
+        let tx = N::TransactionRequest::default()
+            .with_from(entry_point)
+            .with_to(self.sender)
+            .with_input(self.call_data.clone());
+
+        let gas = provider.estimate_gas(tx).await?;
+        self.call_gas_limit = gas.into();
+        Ok(gas)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::math::Math;
+    use alloy::primitives::{Signature, address, b256, bytes};
+    use alloy::providers::ProviderBuilder;
+    use alloy::signers::local::PrivateKeySigner;
+
+    fn account() -> Address {
+        address!("0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee")
+    }
+
+    fn user_operation() -> UserOperation {
+        let mut planner = Planner::default();
+        planner
+            .call_address::<Math::addCall>(
+                account(),
+                vec![U256::from(1).into(), U256::from(2).into()],
+            )
+            .unwrap();
+        UserOperation::execute_plan(account(), U256::from(3), &planner).unwrap()
+    }
+
+    #[test]
+    fn pack_orders_gas_fields() {
+        let mut op = user_operation();
+        op.verification_gas_limit = 1;
+        op.call_gas_limit = 2;
+        op.max_priority_fee_per_gas = 3;
+        op.max_fee_per_gas = 4;
+
+        let packed = op.pack();
+        assert_eq!(packed.accountGasLimits[15], 1);
+        assert_eq!(packed.accountGasLimits[31], 2);
+        assert_eq!(packed.gasFees[15], 3);
+        assert_eq!(packed.gasFees[31], 4);
+    }
+
+    #[test]
+    fn hash_ignores_signature_and_binds_chain() {
+        let entry_point = address!("0x0000000071727De22E5E9d8BAf0edAc6f37da032");
+        let mut op = user_operation();
+        let hash = op.hash(entry_point, 1);
+
+        op.signature = vec![1u8; 65].into();
+        assert_eq!(op.hash(entry_point, 1), hash);
+        assert_ne!(op.hash(entry_point, 10), hash);
+    }
+
+    /// A fully populated user operation, whose hashes were computed independently of this crate
+    /// from the EntryPoint v0.7 `UserOperationLib.hash` layout.
+    fn known_operation() -> UserOperation {
+        UserOperation {
+            sender: address!("0x1234567890123456789012345678901234567890"),
+            nonce: U256::from(7),
+            init_code: bytes!("deadbeef"),
+            call_data: bytes!(
+                "b61d27f60000000000000000000000000000000000000000000000000000000000000001"
+            ),
+            verification_gas_limit: 100_000,
+            call_gas_limit: 200_000,
+            pre_verification_gas: U256::from(50_000),
+            max_priority_fee_per_gas: 1_000_000_000,
+            max_fee_per_gas: 2_000_000_000,
+            paymaster_and_data: bytes!("abcdef"),
+            signature: Bytes::new(),
+        }
+    }
+
+    #[test]
+    fn hash_matches_known_answers() {
+        let entry_point = address!("0x0000000071727De22E5E9d8BAf0edAc6f37da032");
+        let op = known_operation();
+
+        assert_eq!(
+            op.hash(entry_point, 1),
+            b256!("b3004fd381711c9df9bfe54f5d0fe142771d57c77be61431827fcb3b24a86269")
+        );
+        assert_eq!(
+            op.hash(entry_point, 11155111),
+            b256!("ba6291d6fdc9e089fb701675cc083149ea14745eb25390e81cae64e83c0c6d76")
+        );
+    }
+
+    /// Checks the known answer against `getUserOpHash` of the canonical v0.7 EntryPoint on the
+    /// chain at `ETH_RPC_URL`.
+    #[tokio::test]
+    #[ignore = "requires ETH_RPC_URL"]
+    async fn hash_matches_entry_point() {
+        sol! {
+            #[sol(rpc)]
+            interface IEntryPoint {
+                struct PackedUserOperation {
+                    address sender;
+                    uint256 nonce;
+                    bytes initCode;
+                    bytes callData;
+                    bytes32 accountGasLimits;
+                    uint256 preVerificationGas;
+                    bytes32 gasFees;
+                    bytes paymasterAndData;
+                    bytes signature;
+                }
+
+                function getUserOpHash(PackedUserOperation calldata userOp)
+                    external
+                    view
+                    returns (bytes32);
+            }
+        }
+
+        let url = std::env::var("ETH_RPC_URL").expect("ETH_RPC_URL");
+        let provider = ProviderBuilder::new().connect_http(url.parse().unwrap());
+        let chain_id = provider.get_chain_id().await.unwrap();
+        let entry_point = address!("0x0000000071727De22E5E9d8BAf0edAc6f37da032");
+
+        let op = known_operation();
+        let packed = op.pack();
+        let hash = IEntryPoint::new(entry_point, &provider)
+            .getUserOpHash(IEntryPoint::PackedUserOperation {
+                sender: packed.sender,
+                nonce: packed.nonce,
+                initCode: packed.initCode,
+                callData: packed.callData,
+                accountGasLimits: packed.accountGasLimits,
+                preVerificationGas: packed.preVerificationGas,
+                gasFees: packed.gasFees,
+                paymasterAndData: packed.paymasterAndData,
+                signature: packed.signature,
+            })
+            .call()
+            .await
+            .unwrap();
+
+        assert_eq!(hash, op.hash(entry_point, chain_id));
+    }
+
+    #[tokio::test]
+    async fn sign_recovers_to_signer() {
+        let entry_point = address!("0x0000000071727De22E5E9d8BAf0edAc6f37da032");
+        let signer = PrivateKeySigner::random();
+        let mut op = user_operation();
+        op.sign(&signer, entry_point, 1).await.unwrap();
+
+        let signature = Signature::from_raw(&op.signature).unwrap();
+        let recovered = signature
+            .recover_address_from_msg(op.hash(entry_point, 1))
+            .unwrap();
+        assert_eq!(recovered, signer.address());
+    }
+}
@@ -6,7 +6,7 @@ use alloy::{
 
 use crate::cmds::{CommandFlags, Value};
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct FunctionCall<'a> {
     pub(crate) address: Address,
     pub(crate) selector: [u8; 4],
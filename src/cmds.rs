@@ -1,11 +1,16 @@
 use crate::Planner;
 use crate::calls::FunctionCall;
-use alloy::dyn_abi::DynSolValue;
+use crate::composite::Composite;
+use crate::planner::PlannerId;
+use alloy::dyn_abi::{DynSolType, DynSolValue};
 use alloy::primitives::Bytes;
 use bitflags::bitflags;
 use slotmap::DefaultKey;
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
 use std::fmt::Debug;
 use std::hash::Hash;
+use std::sync::Arc;
 
 bitflags! {
     #[derive(Debug, PartialEq, Clone, Copy)]
@@ -28,17 +33,39 @@ bitflags! {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum CommandType {
     Call,
     RawCall,
     SubPlan,
 }
 
-#[derive(Clone, Debug, PartialOrd, Ord, PartialEq, Eq)]
+#[derive(Clone, Debug)]
 pub struct Literal {
-    dynamic: bool,
-    bytes: Vec<u8>,
+    pub(crate) dynamic: bool,
+    pub(crate) bytes: Vec<u8>,
+    // Literals sharing an encoding share a state slot, so the type is left out of comparisons
+    pub(crate) ty: Option<DynSolType>,
+}
+
+impl PartialEq for Literal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Literal {}
+
+impl PartialOrd for Literal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Literal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.dynamic, &self.bytes).cmp(&(other.dynamic, &other.bytes))
+    }
 }
 
 impl<T> From<T> for Literal
@@ -54,6 +81,7 @@ where
         Literal {
             dynamic: v.is_dynamic(),
             bytes,
+            ty: v.as_type(),
         }
     }
 }
@@ -73,12 +101,16 @@ impl Literal {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum Value<'a> {
     Literal(Literal),
     Return(ReturnValue),
     State(Vec<Bytes>),
     Subplan(&'a Planner<'a>),
+    /// A subplan built by the planner itself, such as the body of [`Planner::try_call`].
+    OwnedSubplan(Arc<Planner<'a>>),
+    /// An array or tuple with non-literal elements, see [`Value::array`] and [`Value::tuple`].
+    Composite(Composite<'a>),
 }
 
 impl From<ReturnValue> for Value<'_> {
@@ -93,19 +125,76 @@ impl Value<'_> {
             Value::Literal(l) => l.dynamic,
             Value::Return(r) => r.dynamic,
             Value::State(_) => true,
-            Value::Subplan(_) => true,
+            Value::Subplan(_) | Value::OwnedSubplan(_) => true,
+            Value::Composite(composite) => composite.is_dynamic(),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Command<'a> {
     pub(crate) call: FunctionCall<'a>,
     pub(crate) kind: CommandType,
+    pub(crate) label: Option<String>,
+}
+
+impl Command<'_> {
+    /// Collects the commands of `planner` whose outputs this command consumes, including
+    /// through subplans. Outputs of other planners are skipped, as their keys may collide.
+    pub(crate) fn consumed(&self, planner: PlannerId, used: &mut BTreeSet<DefaultKey>) {
+        let mut outputs = BTreeSet::new();
+        self.consumed_outputs(&mut outputs);
+        used.extend(
+            outputs
+                .into_iter()
+                .filter(|(owner, _)| *owner == planner)
+                .map(|(_, key)| key),
+        );
+    }
+
+    /// Collects the outputs of any planner this command consumes, including through subplans,
+    /// by planner and key.
+    pub(crate) fn consumed_outputs(&self, used: &mut BTreeSet<(PlannerId, DefaultKey)>) {
+        for arg in &self.call.args {
+            arg.consumed(used);
+        }
+    }
+}
+
+impl<'a> Value<'a> {
+    /// The nested plan, if this is a subplan.
+    pub(crate) fn subplan(&self) -> Option<&Planner<'a>> {
+        match self {
+            Value::Subplan(planner) => Some(planner),
+            Value::OwnedSubplan(planner) => Some(planner),
+            _ => None,
+        }
+    }
+
+    fn consumed(&self, used: &mut BTreeSet<(PlannerId, DefaultKey)>) {
+        if let Some(subplan) = self.subplan() {
+            for (_, command) in subplan.command_entries() {
+                command.consumed_outputs(used);
+            }
+        }
+
+        match self {
+            Value::Return(ret) => {
+                used.insert((ret.planner, ret.command));
+            }
+            Value::Composite(composite) => {
+                for element in composite.elements() {
+                    element.consumed(used);
+                }
+            }
+            _ => {}
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct ReturnValue {
     pub(crate) dynamic: bool,
     pub(crate) command: DefaultKey,
+    pub(crate) planner: PlannerId,
 }
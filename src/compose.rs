@@ -0,0 +1,261 @@
+//! Splicing the commands of one planner into another.
+//!
+//! Unlike a subplan, which the VM runs with a nested `execute`, inlined commands run as part of
+//! the enclosing plan. This lets reusable fragments be built once and dropped into any plan:
+//!
+//! ```ignore
+//! let unwrap_and_swap = build_unwrap_and_swap(amount)?;
+//!
+//! let mut planner = Planner::default();
+//! let remap = planner.inline(&unwrap_and_swap);
+//! let out = remap.get(&swapped).unwrap();
+//! ```
+
+use crate::Planner;
+use crate::cmds::{Command, ReturnValue, Value};
+use crate::planner::{CommandKey, PlannerId};
+
+use std::collections::BTreeMap;
+
+/// Maps commands of a spliced planner to their copies in the destination planner.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RemapTable {
+    from: PlannerId,
+    to: PlannerId,
+    keys: BTreeMap<CommandKey, CommandKey>,
+}
+
+impl RemapTable {
+    /// Returns the destination planner's equivalent of `ret`, a value of the spliced planner.
+    pub fn get(&self, ret: &ReturnValue) -> Option<ReturnValue> {
+        if ret.planner != self.from {
+            return None;
+        }
+
+        self.keys.get(&ret.command).map(|command| ReturnValue {
+            dynamic: ret.dynamic,
+            command: *command,
+            planner: self.to,
+        })
+    }
+
+    /// Number of commands spliced.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Remaps `value`, including the elements of composite values, in place.
+    fn remap_value(&self, value: &mut Value<'_>) {
+        match value {
+            Value::Return(ret) => {
+                if let Some(new) = self.get(ret) {
+                    *ret = new;
+                }
+            }
+            Value::Composite(composite) => {
+                for element in composite.elements_mut() {
+                    self.remap_value(element);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<'a> Planner<'a> {
+    /// Appends the commands of `other` after the commands of this planner.
+    ///
+    /// Arguments consuming outputs of `other`'s commands are remapped. Other [`ReturnValue`]
+    /// arguments are kept as they are, so they must be outputs of this planner planned before
+    /// the splice. Subplans of `other` are shared rather than copied,
+    /// so they must not consume outputs of `other`.
+    pub fn extend(&mut self, mut other: Planner<'a>) -> RemapTable {
+        let commands: Vec<_> = other
+            .order
+            .iter()
+            .filter_map(|key| Some((*key, other.commands.remove(*key)?)))
+            .collect();
+
+        self.splice(other.id, commands)
+    }
+
+    /// Appends copies of the commands of `other`, leaving `other` untouched so it can be
+    /// inlined again. See [`extend`](Self::extend) for how arguments are remapped.
+    pub fn inline(&mut self, other: &Planner<'a>) -> RemapTable {
+        let commands: Vec<_> = other
+            .command_entries()
+            .map(|(key, command)| (key, command.clone()))
+            .collect();
+
+        self.splice(other.id, commands)
+    }
+
+    fn splice(&mut self, from: PlannerId, commands: Vec<(CommandKey, Command<'a>)>) -> RemapTable {
+        let mut remap = RemapTable {
+            from,
+            to: self.id,
+            keys: BTreeMap::new(),
+        };
+
+        for (key, mut command) in commands {
+            for arg in command.call.args.iter_mut() {
+                remap.remap_value(arg);
+            }
+
+            let new = self.push_command(command);
+            remap.keys.insert(key, new);
+        }
+
+        remap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::{events::Events, math::Math};
+    use crate::cmds::{CommandFlags, CommandType};
+    use crate::{FunctionCall, WeirollError};
+    use alloy::dyn_abi::DynSolType;
+    use alloy::primitives::{Address, U256, address};
+    use alloy::sol_types::SolCall;
+
+    fn addr() -> Address {
+        address!("0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee")
+    }
+
+    /// Adds `a` and `b`, logs the sum and returns it.
+    fn add_and_log<'a>(
+        planner: &mut Planner<'a>,
+        a: impl Into<Value<'a>>,
+        b: impl Into<Value<'a>>,
+    ) -> Result<ReturnValue, WeirollError> {
+        let sum = planner.call_address::<Math::addCall>(addr(), vec![a.into(), b.into()])?;
+        planner.call_address::<Events::logUintCall>(addr(), vec![sum.clone().into()])?;
+        Ok(sum)
+    }
+
+    #[test]
+    fn inline_remaps_fragment_outputs() -> Result<(), WeirollError> {
+        let mut fragment = Planner::default();
+        let sum = add_and_log(&mut fragment, U256::from(1), U256::from(2))?;
+
+        let mut planner = Planner::default();
+        let first = planner.inline(&fragment);
+        let second = planner.inline(&fragment);
+        assert_eq!(first.len(), 2);
+        planner.call_address::<Math::mulCall>(
+            addr(),
+            vec![
+                first.get(&sum).unwrap().into(),
+                second.get(&sum).unwrap().into(),
+            ],
+        )?;
+
+        let mut expected = Planner::default();
+        let a = add_and_log(&mut expected, U256::from(1), U256::from(2))?;
+        let b = add_and_log(&mut expected, U256::from(1), U256::from(2))?;
+        expected.call_address::<Math::mulCall>(addr(), vec![a.into(), b.into()])?;
+
+        assert_eq!(planner.plan()?, expected.plan()?);
+        Ok(())
+    }
+
+    #[test]
+    fn extend_appends_after_existing_commands() -> Result<(), WeirollError> {
+        let mut planner = Planner::default();
+        let start = add_and_log(&mut planner, U256::from(1), U256::from(2))?;
+
+        let mut fragment = Planner::default();
+        let sum = add_and_log(&mut fragment, U256::from(3), U256::from(4))?;
+        let remap = planner.extend(fragment);
+        planner.call_address::<Math::mulCall>(
+            addr(),
+            vec![start.into(), remap.get(&sum).unwrap().into()],
+        )?;
+
+        let mut expected = Planner::default();
+        let a = add_and_log(&mut expected, U256::from(1), U256::from(2))?;
+        let b = add_and_log(&mut expected, U256::from(3), U256::from(4))?;
+        expected.call_address::<Math::mulCall>(addr(), vec![a.into(), b.into()])?;
+
+        assert_eq!(planner.plan()?, expected.plan()?);
+        Ok(())
+    }
+
+    #[test]
+    fn inline_keeps_outputs_of_the_destination() -> Result<(), WeirollError> {
+        let mut planner = Planner::default();
+        let x = planner.call_address::<Math::addCall>(
+            addr(),
+            vec![U256::from(1).into(), U256::from(2).into()],
+        )?;
+
+        // The fragment's first command has the same key as `x`
+        let mut fragment = Planner::default();
+        let own = fragment.call_address::<Math::addCall>(
+            addr(),
+            vec![U256::from(10).into(), U256::from(20).into()],
+        )?;
+        fragment
+            .call_address::<Math::mulCall>(addr(), vec![x.clone().into(), U256::from(5).into()])?;
+        assert_eq!(own.command, x.command);
+
+        let remap = planner.inline(&fragment);
+        assert!(remap.get(&x).is_none());
+        assert_ne!(remap.get(&own), Some(x.clone()));
+
+        let mut expected = Planner::default();
+        let x = expected.call_address::<Math::addCall>(
+            addr(),
+            vec![U256::from(1).into(), U256::from(2).into()],
+        )?;
+        expected.call_address::<Math::addCall>(
+            addr(),
+            vec![U256::from(10).into(), U256::from(20).into()],
+        )?;
+        expected.call_address::<Math::mulCall>(addr(), vec![x.into(), U256::from(5).into()])?;
+
+        assert_eq!(planner.plan()?, expected.plan()?);
+        Ok(())
+    }
+
+    #[test]
+    fn inline_remaps_composite_elements() -> Result<(), WeirollError> {
+        let mut fragment = Planner::default();
+        let sum = add_and_log(&mut fragment, U256::from(1), U256::from(2))?;
+        // Composites are normally expanded when planned, so push one as it is
+        fragment.push_command(Command {
+            call: FunctionCall {
+                address: addr(),
+                selector: Math::sumCall::SELECTOR,
+                flags: CommandFlags::CALL,
+                value: None,
+                args: vec![Value::array([sum.clone().into(), U256::from(4).into()])],
+                return_type: DynSolType::Uint(256),
+            },
+            kind: CommandType::Call,
+            label: None,
+        });
+
+        let mut planner = Planner::default();
+        add_and_log(&mut planner, U256::from(5), U256::from(6))?;
+        let remap = planner.inline(&fragment);
+        let inlined = remap.get(&sum).unwrap();
+        assert_ne!(inlined.command, sum.command);
+
+        let (_, command) = planner.command_entries().last().unwrap();
+        let [Value::Composite(composite)] = command.call.args.as_slice() else {
+            panic!("expected a composite argument");
+        };
+        assert!(matches!(
+            composite.elements(),
+            [Value::Return(ret), Value::Literal(_)] if *ret == inlined
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,415 @@
+//! Array and tuple arguments built from a mix of literals and return values.
+//!
+//! [`Value::array`] and [`Value::tuple`] encode their elements directly when they are all
+//! literals. Otherwise the planner assembles the value on-chain with the bundled
+//! [`Builder`] contract, which must first be configured with [`Planner::set_builder`]:
+//!
+//! ```ignore
+//! planner.set_builder(builder);
+//! let a = planner.math(math).add(U256::from(1), U256::from(2))?;
+//! let b = planner.math(math).mul(a.clone(), U256::from(3))?;
+//! let total = planner
+//!     .math(math)
+//!     .sum(Value::array([a.into(), b.into(), U256::from(4).into()]))
+//!     .delegatecall()?;
+//! ```
+//!
+//! A static tuple is encoded in place as its elements one after another, so it never needs
+//! the builder.
+
+use crate::Planner;
+use crate::calls::FunctionCall;
+use crate::cmds::{Command, CommandFlags, CommandType, Literal, ReturnValue, Value};
+use crate::error::WeirollError;
+use crate::helpers::builder::Builder;
+
+use alloy::dyn_abi::DynSolType;
+use alloy::primitives::{Address, U256};
+use alloy::sol_types::SolCall;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CompositeKind {
+    Array,
+    Tuple,
+}
+
+/// An array or tuple argument whose elements are not all literals.
+#[derive(Clone, Debug)]
+pub struct Composite<'a> {
+    kind: CompositeKind,
+    elements: Vec<Value<'a>>,
+}
+
+impl<'a> Composite<'a> {
+    pub(crate) fn is_dynamic(&self) -> bool {
+        self.kind == CompositeKind::Array || self.elements.iter().any(Value::is_dynamic_type)
+    }
+
+    pub(crate) fn elements(&self) -> &[Value<'a>] {
+        &self.elements
+    }
+
+    pub(crate) fn elements_mut(&mut self) -> &mut [Value<'a>] {
+        &mut self.elements
+    }
+
+    /// Encodes the composite as it is stored in the state, if every element is a literal.
+    fn literal(&self) -> Option<Literal> {
+        let elements = self
+            .elements
+            .iter()
+            .map(|element| match element {
+                Value::Literal(literal) => Some(literal.clone()),
+                Value::Composite(composite) => composite.literal(),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let mut bytes = match self.kind {
+            CompositeKind::Array => U256::from(elements.len()).to_be_bytes::<32>().to_vec(),
+            CompositeKind::Tuple => vec![],
+        };
+        bytes.extend(encode_sequence(&elements));
+
+        let ty = elements
+            .iter()
+            .map(|literal| literal.ty.clone())
+            .collect::<Option<Vec<_>>>()
+            .and_then(|types| composite_type(self.kind, types));
+
+        Some(Literal {
+            dynamic: self.is_dynamic(),
+            bytes,
+            ty,
+        })
+    }
+}
+
+/// ABI-encodes a sequence of values, with dynamic values in the tail.
+fn encode_sequence(elements: &[Literal]) -> Vec<u8> {
+    let head_len: usize = elements
+        .iter()
+        .map(|e| if e.dynamic { 32 } else { e.bytes.len() })
+        .sum();
+
+    let mut head = Vec::with_capacity(head_len);
+    let mut tail: Vec<u8> = vec![];
+    for element in elements {
+        if element.dynamic {
+            head.extend(U256::from(head_len + tail.len()).to_be_bytes::<32>());
+            tail.extend(&element.bytes);
+        } else {
+            head.extend(&element.bytes);
+        }
+    }
+
+    head.extend(tail);
+    head
+}
+
+fn composite_type(kind: CompositeKind, types: Vec<DynSolType>) -> Option<DynSolType> {
+    match kind {
+        CompositeKind::Array => types
+            .into_iter()
+            .next()
+            .map(|ty| DynSolType::Array(Box::new(ty))),
+        CompositeKind::Tuple => Some(DynSolType::Tuple(types)),
+    }
+}
+
+impl<'a> Value<'a> {
+    /// A `T[]` argument made of `elements`, which must all have the same type.
+    pub fn array(elements: impl IntoIterator<Item = Value<'a>>) -> Self {
+        Self::composite(CompositeKind::Array, elements)
+    }
+
+    /// A struct or tuple argument made of `elements`.
+    pub fn tuple(elements: impl IntoIterator<Item = Value<'a>>) -> Self {
+        Self::composite(CompositeKind::Tuple, elements)
+    }
+
+    fn composite(kind: CompositeKind, elements: impl IntoIterator<Item = Value<'a>>) -> Self {
+        let composite = Composite {
+            kind,
+            elements: elements.into_iter().collect(),
+        };
+
+        // Static tuples are left to be encoded in place, as state slots hold a single word
+        match composite.literal() {
+            Some(literal) if composite.is_dynamic() => Value::Literal(literal),
+            _ => Value::Composite(composite),
+        }
+    }
+}
+
+impl<'a> Planner<'a> {
+    /// Sets the address of the [`Builder`] contract, which assembles [`Value::array`] and
+    /// [`Value::tuple`] arguments whose elements are not all literals.
+    pub fn set_builder(&mut self, address: Address) {
+        self.builder = Some(address);
+    }
+
+    /// Replaces composite arguments with the arguments they expand to, planning [`Builder`]
+    /// calls as needed. Nothing is planned if expansion fails.
+    pub(crate) fn expand_args(
+        &mut self,
+        args: Vec<Value<'a>>,
+    ) -> Result<Vec<Value<'a>>, WeirollError> {
+        if !args.iter().any(|arg| matches!(arg, Value::Composite(_))) {
+            return Ok(args);
+        }
+
+        let planned = self.order.len();
+        let mut expanded = vec![];
+        for arg in args {
+            match self.expand(arg) {
+                Ok(values) => expanded.extend(values),
+                Err(err) => {
+                    for key in self.order.split_off(planned) {
+                        self.commands.remove(key);
+                    }
+                    return Err(err);
+                }
+            }
+        }
+
+        Ok(expanded)
+    }
+
+    fn expand(&mut self, value: Value<'a>) -> Result<Vec<Value<'a>>, WeirollError> {
+        let Value::Composite(composite) = value else {
+            return Ok(vec![value]);
+        };
+
+        if let Some(literal) = composite.literal().filter(|_| composite.is_dynamic()) {
+            return Ok(vec![Value::Literal(literal)]);
+        }
+
+        let types: Option<Vec<DynSolType>> = composite
+            .elements
+            .iter()
+            .map(|element| self.value_type(element))
+            .collect();
+
+        let (selector, mut args) = match composite.kind {
+            CompositeKind::Tuple if !composite.is_dynamic() => {
+                let mut args = vec![];
+                for element in composite.elements {
+                    args.extend(self.expand(element)?);
+                }
+                return Ok(args);
+            }
+            CompositeKind::Tuple => (Builder::buildTupleCall::SELECTOR, vec![]),
+            CompositeKind::Array => {
+                let dynamic = composite
+                    .elements
+                    .first()
+                    .is_some_and(Value::is_dynamic_type);
+                let selector = if dynamic {
+                    Builder::buildDynamicArrayCall::SELECTOR
+                } else {
+                    Builder::buildArrayCall::SELECTOR
+                };
+                let length = U256::from(composite.elements.len());
+                (selector, vec![length.into()])
+            }
+        };
+
+        let builder = self.builder.ok_or(WeirollError::MissingBuilder)?;
+
+        if composite.kind == CompositeKind::Array {
+            let expected = self.value_type(&composite.elements[0]);
+            for (argument, element) in composite.elements.iter().enumerate() {
+                if let (Some(expected), Some(actual)) = (&expected, self.value_type(element))
+                    && *expected != actual
+                {
+                    return Err(WeirollError::ArgumentTypeMismatch {
+                        command: self.next_context(builder, selector),
+                        argument: argument + 1,
+                        expected: expected.clone(),
+                        actual,
+                    });
+                }
+            }
+        }
+
+        for element in composite.elements {
+            args.extend(self.expand(element)?);
+        }
+
+        let return_type = types
+            .and_then(|types| composite_type(composite.kind, types))
+            .unwrap_or(DynSolType::Bytes);
+
+        let command = self.push_command(Command {
+            call: FunctionCall {
+                address: builder,
+                selector,
+                flags: CommandFlags::STATICCALL,
+                value: None,
+                args,
+                return_type,
+            },
+            kind: CommandType::Call,
+            label: None,
+        });
+
+        Ok(vec![Value::Return(ReturnValue {
+            dynamic: true,
+            command,
+            planner: self.id,
+        })])
+    }
+
+    /// The Solidity type of `value`, where it is known.
+    pub(crate) fn value_type(&self, value: &Value<'a>) -> Option<DynSolType> {
+        match value {
+            Value::Literal(literal) => literal.ty.clone(),
+            Value::Return(ret) => self.return_type(ret.command).cloned(),
+            Value::State(_) => Some(DynSolType::Array(Box::new(DynSolType::Bytes))),
+            Value::Subplan(_) | Value::OwnedSubplan(_) => None,
+            Value::Composite(composite) => composite
+                .elements
+                .iter()
+                .map(|element| self.value_type(element))
+                .collect::<Option<Vec<_>>>()
+                .and_then(|types| composite_type(composite.kind, types)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::math::Math;
+    use crate::view::{ArgView, CallType};
+    use alloy::dyn_abi::DynSolValue;
+    use alloy::primitives::{Bytes, address};
+
+    fn math() -> Address {
+        address!("0x1111111111111111111111111111111111111111")
+    }
+
+    fn builder() -> Address {
+        address!("0x2222222222222222222222222222222222222222")
+    }
+
+    #[test]
+    fn literal_composites_encode_directly() {
+        let values = [U256::from(1), U256::from(2)];
+        let Value::Literal(literal) = Value::array(values.map(Into::into)) else {
+            panic!("expected a literal");
+        };
+        let expected = Literal::from(DynSolValue::Array(values.map(Into::into).to_vec()));
+        assert_eq!(literal.bytes(), expected.bytes());
+        assert_eq!(literal.ty, expected.ty);
+
+        let Value::Literal(literal) =
+            Value::tuple([Value::from(U256::from(7)), String::from("seven").into()])
+        else {
+            panic!("expected a literal");
+        };
+        let expected = Literal::from(DynSolValue::Tuple(vec![
+            U256::from(7).into(),
+            String::from("seven").into(),
+        ]));
+        assert_eq!(literal.bytes(), expected.bytes());
+        assert!(literal.dynamic);
+
+        let words = Value::array([Value::tuple([U256::from(1).into(), U256::from(2).into()])]);
+        let expected = Literal::from(DynSolValue::Array(vec![DynSolValue::Tuple(vec![
+            U256::from(1).into(),
+            U256::from(2).into(),
+        ])]));
+        assert!(matches!(words, Value::Literal(literal) if literal.bytes() == expected.bytes()));
+    }
+
+    #[test]
+    fn array_of_return_values_uses_builder() -> Result<(), WeirollError> {
+        let mut planner = Planner::default();
+        let a = planner.math(math()).add(U256::from(1), U256::from(2))?;
+        assert_eq!(
+            planner
+                .math(math())
+                .sum(Value::array([a.clone().into(), U256::from(4).into()])),
+            Err(WeirollError::MissingBuilder)
+        );
+        assert_eq!(planner.commands().len(), 1);
+
+        planner.set_builder(builder());
+        let total = planner
+            .math(math())
+            .sum(Value::array([a.clone().into(), U256::from(4).into()]))?;
+
+        let commands = planner.commands();
+        assert_eq!(commands.len(), 3);
+        assert_eq!(commands[1].target, builder());
+        assert_eq!(commands[1].selector, Builder::buildArrayCall::SELECTOR);
+        assert_eq!(commands[1].call_type, CallType::StaticCall);
+        assert_eq!(
+            *commands[1].return_type,
+            DynSolType::Array(Box::new(DynSolType::Uint(256)))
+        );
+        assert!(matches!(&commands[1].args[..], [
+            ArgView::Literal(length),
+            ArgView::Return(ret),
+            ArgView::Literal(_),
+        ] if *length == Bytes::from(U256::from(2).to_be_bytes::<32>()) && *ret == a));
+        assert!(
+            matches!(&commands[2].args[..], [ArgView::Return(ret)] if *ret == commands[1].output)
+        );
+        assert_eq!(commands[2].output, total);
+        assert_eq!(commands[2].selector, Math::sumCall::SELECTOR);
+        Ok(())
+    }
+
+    #[test]
+    fn replace_state_reports_unexpandable_composites() -> Result<(), WeirollError> {
+        let mut planner = Planner::default();
+        let a = planner.math(math()).add(U256::from(1), U256::from(2))?;
+        assert_eq!(
+            planner.replace_state::<Math::sumCall>(
+                math(),
+                vec![Value::array([a.into(), U256::from(4).into()])]
+            ),
+            Err(WeirollError::MissingBuilder)
+        );
+        assert_eq!(planner.commands().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn tuples_expand_in_place_or_through_builder() -> Result<(), WeirollError> {
+        let mut planner = Planner::default();
+        planner.set_builder(builder());
+        let a = planner.math(math()).add(U256::from(1), U256::from(2))?;
+
+        // A static tuple becomes its elements
+        let args =
+            planner.expand_args(vec![Value::tuple([a.clone().into(), U256::from(3).into()])])?;
+        assert!(matches!(&args[..], [Value::Return(ret), Value::Literal(_)] if *ret == a));
+
+        let args = planner.expand_args(vec![Value::tuple([
+            Value::from(a.clone()),
+            String::from("label").into(),
+        ])])?;
+        let commands = planner.commands();
+        assert_eq!(commands[1].selector, Builder::buildTupleCall::SELECTOR);
+        assert!(matches!(&args[..], [Value::Return(ret)] if *ret == commands[1].output));
+        assert_eq!(
+            *commands[1].return_type,
+            DynSolType::Tuple(vec![DynSolType::Uint(256), DynSolType::String])
+        );
+
+        let mismatch = planner.expand_args(vec![Value::array([
+            Value::from(a),
+            Value::from(String::from("x")),
+        ])]);
+        assert!(matches!(
+            mismatch,
+            Err(WeirollError::ArgumentTypeMismatch { argument: 2, .. })
+        ));
+        assert_eq!(planner.commands().len(), 2);
+        Ok(())
+    }
+}
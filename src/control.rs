@@ -0,0 +1,646 @@
+//! Control flow built from subplans.
+//!
+//! [`Planner::try_call`] plans a command in a subplan run by the bundled [`TryCatch`] helper, so
+//! a revert skips the command rather than aborting the whole plan:
+//!
+//! ```ignore
+//! planner.set_try_catch(try_catch);
+//! let claim = planner.try_call(|p| p.call_address::<Rewards::claimCall>(rewards, vec![]))?;
+//! planner.call_address::<Events::logUintCall>(events, vec![claim.success.into()])?;
+//! ```
+//!
+//! [`Planner::if_then_else`] runs one of two branches through the bundled [`Conditional`]
+//! helper. Outputs which both branches label alike are merged into one [`ReturnValue`]:
+//!
+//! ```ignore
+//! planner.set_conditional(conditional);
+//! let mut then = planner.branch();
+//! let swapped = then.call_address::<Router::swapCall>(router, vec![amount.into()])?;
+//! then.set_label(&swapped, "out")?;
+//! let mut otherwise = planner.branch();
+//! let held = otherwise.staticcall_address::<ERC20::balanceOfCall>(token, vec![vm.into()])?;
+//! otherwise.set_label(&held, "out")?;
+//!
+//! let outputs = planner.if_then_else(should_swap, then, otherwise)?;
+//! planner.call_address::<Events::logUintCall>(events, vec![outputs["out"].clone().into()])?;
+//! ```
+//!
+//! [`Planner::for_each`] and [`Planner::fold`] run a subplan once per element of an array
+//! through the bundled [`Loop`] helper:
+//!
+//! ```ignore
+//! planner.set_loop(looping);
+//! let tokens = planner.staticcall_address::<Gauge::rewardTokensCall>(gauge, vec![])?;
+//! planner.for_each(tokens, |p, token| {
+//!     p.call_address::<Gauge::claimCall>(gauge, vec![token.into()])?;
+//!     Ok(())
+//! })?;
+//! ```
+
+use crate::Planner;
+use crate::cmds::CommandFlags;
+use crate::cmds::{ReturnValue, Value};
+use crate::error::WeirollError;
+use crate::helpers::conditional::Conditional;
+use crate::helpers::looping::Loop;
+use crate::helpers::try_catch::TryCatch;
+use crate::planner::{CommandKey, PlannerId};
+
+use alloy::dyn_abi::DynSolType;
+use alloy::primitives::{Address, U256};
+use alloy::sol_types::SolCall;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// The outputs of a command planned with [`Planner::try_call`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Attempt {
+    /// A `bool` which is true if the command succeeded
+    pub success: ReturnValue,
+    /// The command's output, if it has one. If the command reverted, the state slot is left as
+    /// it was, so this should only be consumed once `success` has been checked.
+    pub result: Option<ReturnValue>,
+}
+
+/// Whether values of `ty` are encoded as a single word, as the elements [`Loop`] iterates over
+/// must be.
+fn is_word(ty: &DynSolType) -> bool {
+    matches!(
+        ty,
+        DynSolType::Address
+            | DynSolType::Bool
+            | DynSolType::Int(_)
+            | DynSolType::Uint(_)
+            | DynSolType::FixedBytes(_)
+    )
+}
+
+/// The new planner and key of each command copied by [`Planner::import`].
+type ImportedKeys = BTreeMap<(PlannerId, CommandKey), (PlannerId, CommandKey)>;
+
+/// The most elements [`Planner::for_each`] iterates over unless
+/// [`Planner::set_max_iterations`] is called.
+pub const DEFAULT_MAX_ITERATIONS: usize = 256;
+
+impl<'a> Planner<'a> {
+    /// Sets the address of the [`TryCatch`] contract, which runs the subplans of
+    /// [`try_call`](Self::try_call).
+    pub fn set_try_catch(&mut self, address: Address) {
+        self.try_catch = Some(address);
+    }
+
+    /// Plans the command added by `body` so that a revert is caught rather than aborting the
+    /// plan.
+    ///
+    /// `body` plans into a subplan which the [`TryCatch`] helper runs by calling back into the
+    /// VM's `execute`, so the VM must accept calls to `execute` from itself and the chain must
+    /// support transient storage. A revert undoes everything `body` planned, and later commands
+    /// can branch on [`Attempt::success`].
+    pub fn try_call<F>(&mut self, body: F) -> Result<Attempt, WeirollError>
+    where
+        F: FnOnce(&mut Planner<'a>) -> Result<ReturnValue, WeirollError>,
+    {
+        let helper = self.try_catch.ok_or(WeirollError::MissingTryCatch)?;
+
+        let mut subplan = self.nested();
+        let ret = body(&mut subplan)?;
+        if subplan.command_position(ret.command).is_none() {
+            return Err(WeirollError::UnknownReturnValue);
+        }
+        let result = subplan
+            .return_type(ret.command)
+            .filter(|ty| **ty != DynSolType::Tuple(vec![]))
+            .map(|_| ret);
+        self.adopt(&subplan);
+
+        self.add_subplan::<TryCatch::tryExecuteCall>(
+            helper,
+            vec![Value::OwnedSubplan(Arc::new(subplan)), Value::State(vec![])],
+            DynSolType::Array(Box::new(DynSolType::Bytes)),
+        )?;
+        let success = self.delegatecall_address::<TryCatch::succeededCall>(helper, vec![])?;
+
+        Ok(Attempt { success, result })
+    }
+
+    /// Sets the address of the [`Conditional`] contract, which runs the branches of
+    /// [`if_then_else`](Self::if_then_else).
+    pub fn set_conditional(&mut self, address: Address) {
+        self.conditional = Some(address);
+    }
+
+    /// Returns an empty planner for a branch of [`if_then_else`](Self::if_then_else), whose
+    /// commands may consume outputs planned so far.
+    pub fn branch(&self) -> Planner<'a> {
+        self.nested()
+    }
+
+    /// Plans `then` to run if `condition` is true and `otherwise` if it is false.
+    ///
+    /// `condition` must be a `bool`. The branches run by calling back into the VM's `execute`,
+    /// so the VM must accept calls to `execute` from itself; a revert in either branch reverts
+    /// the plan. An empty branch is left out.
+    ///
+    /// Outputs of the branches can only be consumed through the returned map, which holds the
+    /// outputs both branches label alike. Branches which consume earlier outputs must come from
+    /// [`branch`](Self::branch).
+    pub fn if_then_else(
+        &mut self,
+        condition: ReturnValue,
+        then: Planner<'a>,
+        otherwise: Planner<'a>,
+    ) -> Result<BTreeMap<String, ReturnValue>, WeirollError> {
+        let helper = self.conditional.ok_or(WeirollError::MissingConditional)?;
+
+        let then = self.import(&then, &mut BTreeMap::new());
+        let mut otherwise = self.import(&otherwise, &mut BTreeMap::new());
+
+        let mut outputs = BTreeMap::new();
+        let labels = otherwise.labelled_outputs();
+        for (label, key) in then.labelled_outputs() {
+            let Some(other) = labels.get(&label) else {
+                continue;
+            };
+            let return_type = &then.commands[key].call.return_type;
+            if *return_type != otherwise.commands[*other].call.return_type {
+                return Err(WeirollError::BranchTypeMismatch { label });
+            }
+
+            let dynamic = return_type.is_dynamic();
+            otherwise.merged.insert(*other, (then.id, key));
+            outputs.insert(
+                label,
+                ReturnValue {
+                    dynamic,
+                    command: key,
+                    planner: then.id,
+                },
+            );
+        }
+
+        let state = DynSolType::Array(Box::new(DynSolType::Bytes));
+        if !then.order.is_empty() {
+            self.add_subplan::<Conditional::executeIfCall>(
+                helper,
+                vec![
+                    condition.clone().into(),
+                    Value::OwnedSubplan(Arc::new(then)),
+                    Value::State(vec![]),
+                ],
+                state.clone(),
+            )?;
+        }
+        if !otherwise.order.is_empty() {
+            self.add_subplan::<Conditional::executeUnlessCall>(
+                helper,
+                vec![
+                    condition.into(),
+                    Value::OwnedSubplan(Arc::new(otherwise)),
+                    Value::State(vec![]),
+                ],
+                state,
+            )?;
+        }
+
+        Ok(outputs)
+    }
+
+    /// The last command with each label which has an output.
+    fn labelled_outputs(&self) -> BTreeMap<String, CommandKey> {
+        self.command_entries()
+            .filter(|(_, command)| command.call.return_type != DynSolType::Tuple(vec![]))
+            .filter_map(|(key, command)| Some((command.label.clone()?, key)))
+            .collect()
+    }
+
+    /// Copies the commands of `plan` into a subplan of this planner.
+    ///
+    /// `plan` may have been planned separately, so its commands are given new keys, recorded in
+    /// `keys` by planner and key. Arguments consuming outputs of `plan` or of its subplans are
+    /// remapped; arguments consuming any other output, such as one of this planner, are left as
+    /// they are.
+    fn import(&mut self, plan: &Planner<'a>, keys: &mut ImportedKeys) -> Planner<'a> {
+        let mut subplan = self.nested();
+        for (key, command) in plan.command_entries() {
+            let mut command = command.clone();
+            for arg in command.call.args.iter_mut() {
+                subplan.import_value(arg, keys);
+            }
+            let imported = subplan.push_command(command);
+            keys.insert((plan.id, key), (subplan.id, imported));
+        }
+        for (from, to) in &plan.merged {
+            if let Some((_, from)) = keys.get(&(plan.id, *from)) {
+                subplan
+                    .merged
+                    .insert(*from, keys.get(to).copied().unwrap_or(*to));
+            }
+        }
+
+        self.adopt(&subplan);
+        subplan
+    }
+
+    fn import_value(&mut self, value: &mut Value<'a>, keys: &mut ImportedKeys) {
+        match value {
+            Value::Return(ret) => {
+                if let Some((planner, command)) = keys.get(&(ret.planner, ret.command)) {
+                    ret.planner = *planner;
+                    ret.command = *command;
+                }
+            }
+            Value::OwnedSubplan(plan) => {
+                *plan = Arc::new(self.import(plan, keys));
+            }
+            Value::Composite(composite) => {
+                for element in composite.elements_mut() {
+                    self.import_value(element, keys);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Sets the address of the [`Loop`] contract, which runs the subplans of
+    /// [`for_each`](Self::for_each) and [`fold`](Self::fold).
+    pub fn set_loop(&mut self, address: Address) {
+        self.loop_helper = Some(address);
+    }
+
+    /// Sets the most elements a loop iterates over; longer arrays revert the plan.
+    pub fn set_max_iterations(&mut self, max: usize) {
+        self.max_iterations = Some(max);
+    }
+
+    /// Plans `body` to run once per element of `array`, which must be an array of single-word values
+    /// such as `address[]`.
+    ///
+    /// `body` plans into a subplan and is given the current element. The subplan runs by calling
+    /// back into the VM's `execute`, so the VM must accept calls to `execute` from itself and
+    /// the chain must support transient storage. Outputs of `body` cannot be consumed after the
+    /// loop; use [`fold`](Self::fold) to carry a value across iterations.
+    pub fn for_each<F>(&mut self, array: ReturnValue, body: F) -> Result<(), WeirollError>
+    where
+        F: FnOnce(&mut Planner<'a>, ReturnValue) -> Result<(), WeirollError>,
+    {
+        self.plan_loop(array, None, |p, element| body(p, element).map(|()| None))
+    }
+
+    /// Like [`for_each`](Self::for_each), but carries an accumulator across iterations.
+    ///
+    /// `body` is given the current element and the accumulator, which starts as `initial`, and
+    /// returns its next value. The returned output holds the final value, and is written to the
+    /// slot of `initial`, which must not be consumed after the loop for its original value.
+    pub fn fold<F>(
+        &mut self,
+        array: ReturnValue,
+        initial: ReturnValue,
+        body: F,
+    ) -> Result<ReturnValue, WeirollError>
+    where
+        F: FnOnce(&mut Planner<'a>, ReturnValue, ReturnValue) -> Result<ReturnValue, WeirollError>,
+    {
+        let accumulator = initial.clone();
+        self.plan_loop(array, Some(initial.clone()), |p, element| {
+            body(p, element, accumulator).map(Some)
+        })?;
+
+        Ok(initial)
+    }
+
+    fn plan_loop<F>(
+        &mut self,
+        array: ReturnValue,
+        accumulator: Option<ReturnValue>,
+        body: F,
+    ) -> Result<(), WeirollError>
+    where
+        F: FnOnce(&mut Planner<'a>, ReturnValue) -> Result<Option<ReturnValue>, WeirollError>,
+    {
+        let helper = self.loop_helper.ok_or(WeirollError::MissingLoop)?;
+        let command = self.next_context(helper, Loop::forEachCall::SELECTOR);
+
+        let element_type = match self.return_type(array.command) {
+            Some(DynSolType::Array(ty)) if is_word(ty) => (**ty).clone(),
+            Some(actual) => {
+                return Err(WeirollError::ArgumentTypeMismatch {
+                    command,
+                    argument: 0,
+                    expected: DynSolType::Array(Box::new(DynSolType::FixedBytes(32))),
+                    actual: actual.clone(),
+                });
+            }
+            None => return Err(WeirollError::UnknownReturnValue),
+        };
+
+        let mut subplan = self.nested();
+        let element = subplan.insert_raw_call(
+            helper,
+            Loop::elementCall::SELECTOR,
+            CommandFlags::DELEGATECALL,
+            None,
+            vec![],
+            element_type,
+        )?;
+        let next = body(&mut subplan, element)?;
+
+        // Each iteration writes the next value to the accumulator's slot
+        if let (Some(accumulator), Some(next)) = (accumulator, next)
+            && next != accumulator
+        {
+            if subplan.command_position(next.command).is_none() {
+                return Err(WeirollError::UnknownReturnValue);
+            }
+            let expected = self.return_type(accumulator.command);
+            let actual = subplan.return_type(next.command);
+            if let (Some(expected), Some(actual)) = (expected, actual)
+                && expected != actual
+            {
+                return Err(WeirollError::AccumulatorTypeMismatch {
+                    expected: expected.clone(),
+                    actual: actual.clone(),
+                });
+            }
+            subplan
+                .merged
+                .insert(next.command, (accumulator.planner, accumulator.command));
+        }
+        self.adopt(&subplan);
+
+        let max = self.max_iterations.unwrap_or(DEFAULT_MAX_ITERATIONS);
+        self.add_subplan::<Loop::forEachCall>(
+            helper,
+            vec![
+                array.into(),
+                U256::from(max).into(),
+                Value::OwnedSubplan(Arc::new(subplan)),
+                Value::State(vec![]),
+            ],
+            DynSolType::Array(Box::new(DynSolType::Bytes)),
+        )?;
+
+        Ok(())
+    }
+
+    /// Returns an empty planner for a subplan of this one.
+    ///
+    /// Command keys are only unique within a planner, so the subplan starts from a copy of this
+    /// planner's commands; once [`adopt`](Self::adopt)ed, outputs of either planner can be
+    /// consumed by the other.
+    pub(crate) fn nested(&self) -> Planner<'a> {
+        Planner {
+            commands: self.commands.clone(),
+            order: vec![],
+            builder: self.builder,
+            dialect: self.dialect.clone(),
+            try_catch: self.try_catch,
+            conditional: self.conditional,
+            loop_helper: self.loop_helper,
+            max_iterations: self.max_iterations,
+            carry: self.carry,
+            merged: BTreeMap::new(),
+            id: PlannerId::default(),
+        }
+    }
+
+    /// Reserves the keys of commands planned in `subplan`, which came from [`nested`](Self::nested).
+    pub(crate) fn adopt(&mut self, subplan: &Planner<'a>) {
+        self.commands = subplan.commands.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::erc20::ERC20;
+    use crate::bindings::math::Math;
+    use crate::bindings::revert::Revert;
+    use crate::view::{CallType, CommandKind};
+    use alloy::primitives::{U256, address};
+    use alloy::sol_types::SolCall;
+
+    fn try_catch() -> Address {
+        address!("0x1111111111111111111111111111111111111111")
+    }
+
+    fn math() -> Address {
+        address!("0x2222222222222222222222222222222222222222")
+    }
+
+    #[test]
+    fn try_call_runs_command_in_subplan() -> Result<(), WeirollError> {
+        let mut planner = Planner::default();
+        assert!(matches!(
+            planner.try_call(|p| p.call_address::<Revert::failCall>(math(), vec![])),
+            Err(WeirollError::MissingTryCatch)
+        ));
+
+        planner.set_try_catch(try_catch());
+        let failed = planner.try_call(|p| p.call_address::<Revert::failCall>(math(), vec![]))?;
+        assert_eq!(failed.result, None);
+
+        let sum = planner.try_call(|p| {
+            p.call_address::<Math::addCall>(
+                math(),
+                vec![U256::from(1).into(), U256::from(2).into()],
+            )
+        })?;
+        let result = sum.result.clone().expect("add has an output");
+        planner.call_address::<Math::addCall>(math(), vec![result.into(), U256::from(3).into()])?;
+
+        let commands = planner.commands();
+        assert_eq!(commands.len(), 5);
+        assert_eq!(commands[0].kind, CommandKind::Subplan);
+        assert_eq!(commands[0].selector, TryCatch::tryExecuteCall::SELECTOR);
+        assert_eq!(commands[1].output, failed.success);
+        assert_eq!(commands[1].call_type, CallType::DelegateCall);
+
+        let (words, state) = planner.plan()?;
+        assert_eq!(words.len(), 5);
+        // The subplan writes the output of `add` to the slot the parent reads it from
+        let subplan = &state[usize::from(words[2][5] & 0x7f)];
+        assert_eq!(subplan[..32], U256::from(1).to_be_bytes::<32>());
+        assert_eq!(subplan[32 + 11], words[4][5]);
+        Ok(())
+    }
+
+    #[test]
+    fn if_then_else_merges_labelled_outputs() -> Result<(), WeirollError> {
+        let mut planner = Planner::default();
+        let condition = planner.staticcall_address::<ERC20::approveCall>(
+            math(),
+            vec![math().into(), U256::from(1).into()],
+        )?;
+
+        let mut then = planner.branch();
+        let sum = then.call_address::<Math::addCall>(
+            math(),
+            vec![U256::from(1).into(), U256::from(2).into()],
+        )?;
+        then.set_label(&sum, "out")?;
+        let mut otherwise = planner.branch();
+        let product = otherwise.call_address::<Math::mulCall>(
+            math(),
+            vec![U256::from(4).into(), U256::from(5).into()],
+        )?;
+        otherwise.set_label(&product, "out")?;
+        assert_eq!(
+            otherwise.set_label(&condition, "condition"),
+            Err(WeirollError::UnknownReturnValue)
+        );
+        otherwise.call_address::<Math::addCall>(
+            math(),
+            vec![product.into(), condition.clone().into()],
+        )?;
+
+        assert!(matches!(
+            planner.if_then_else(condition.clone(), then.branch(), otherwise.branch()),
+            Err(WeirollError::MissingConditional)
+        ));
+        planner.set_conditional(math());
+        let outputs = planner.if_then_else(condition, then, otherwise)?;
+        let out = outputs["out"].clone();
+        planner.call_address::<Math::addCall>(math(), vec![out.into(), U256::from(3).into()])?;
+
+        let (words, state) = planner.plan()?;
+        assert_eq!(words.len(), 4);
+        assert_eq!(words[1][..4], Conditional::executeIfCall::SELECTOR);
+        assert_eq!(words[2][..4], Conditional::executeUnlessCall::SELECTOR);
+
+        // Both branches write the merged output to the slot the parent reads it from
+        let then = &state[usize::from(words[1][6] & 0x7f)];
+        let otherwise = &state[usize::from(words[2][6] & 0x7f)];
+        assert_eq!(then[32 + 11], words[3][5]);
+        assert_eq!(otherwise[32 + 11], words[3][5]);
+        // and the branch reads its output from there too
+        assert_eq!(otherwise[64 + 5], words[3][5]);
+        Ok(())
+    }
+
+    #[test]
+    fn branches_keep_outputs_planned_after_them() -> Result<(), WeirollError> {
+        let mut planner = Planner::default();
+        planner.set_conditional(math());
+        let mut then = planner.branch();
+        let condition = planner.staticcall_address::<ERC20::approveCall>(
+            math(),
+            vec![math().into(), U256::from(1).into()],
+        )?;
+        let x = planner.call_address::<Math::addCall>(
+            math(),
+            vec![U256::from(1).into(), U256::from(2).into()],
+        )?;
+
+        // The branch hands out the keys the parent used for `condition` and `x`
+        then.call_address::<ERC20::approveCall>(math(), vec![math().into(), U256::from(1).into()])?;
+        let own = then.call_address::<Math::addCall>(
+            math(),
+            vec![U256::from(10).into(), U256::from(20).into()],
+        )?;
+        assert_eq!(own.command, x.command);
+        then.call_address::<Math::addCall>(math(), vec![own.into(), x.into()])?;
+        planner.if_then_else(condition, then, Planner::default())?;
+
+        let (words, state) = planner.plan()?;
+        let then = &state[usize::from(words[2][6] & 0x7f)];
+        // The branch adds its own output to the parent's
+        assert_eq!(then[96 + 5], then[64 + 11]);
+        assert_eq!(then[96 + 6], words[1][11]);
+        Ok(())
+    }
+
+    #[test]
+    fn if_then_else_rejects_mismatched_outputs() -> Result<(), WeirollError> {
+        let mut planner = Planner::default();
+        planner.set_conditional(math());
+        let condition = planner.staticcall_address::<ERC20::approveCall>(
+            math(),
+            vec![math().into(), U256::from(1).into()],
+        )?;
+
+        let mut then = planner.branch();
+        let sum = then.call_address::<Math::addCall>(
+            math(),
+            vec![U256::from(1).into(), U256::from(2).into()],
+        )?;
+        then.set_label(&sum, "out")?;
+        let mut otherwise = planner.branch();
+        let approved = otherwise.call_address::<ERC20::approveCall>(
+            math(),
+            vec![math().into(), U256::from(1).into()],
+        )?;
+        otherwise.set_label(&approved, "out")?;
+
+        assert_eq!(
+            planner.if_then_else(condition, then, otherwise),
+            Err(WeirollError::BranchTypeMismatch {
+                label: "out".into()
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn fold_carries_accumulator_across_iterations() -> Result<(), WeirollError> {
+        let mut planner = Planner::default();
+        let items = planner.call_raw(
+            math(),
+            [1, 2, 3, 4],
+            vec![],
+            DynSolType::Array(Box::new(DynSolType::Uint(256))),
+        )?;
+        let zero = planner
+            .call_address::<Math::addCall>(math(), vec![U256::ZERO.into(), U256::ZERO.into()])?;
+
+        let add = |p: &mut Planner<'_>, element: ReturnValue, total: ReturnValue| {
+            p.call_address::<Math::addCall>(math(), vec![total.into(), element.into()])
+        };
+        assert!(matches!(
+            planner.fold(items.clone(), zero.clone(), add),
+            Err(WeirollError::MissingLoop)
+        ));
+        planner.set_loop(try_catch());
+        assert!(matches!(
+            planner.for_each(zero.clone(), |_, _| Ok(())),
+            Err(WeirollError::ArgumentTypeMismatch { argument: 0, .. })
+        ));
+        for element in [
+            DynSolType::FixedArray(Box::new(DynSolType::Uint(256)), 2),
+            DynSolType::Tuple(vec![DynSolType::Address, DynSolType::Uint(256)]),
+        ] {
+            let mut planner = Planner::default();
+            planner.set_loop(try_catch());
+            let pairs = planner.call_raw(
+                math(),
+                [1, 2, 3, 4],
+                vec![],
+                DynSolType::Array(Box::new(element)),
+            )?;
+            assert!(matches!(
+                planner.for_each(pairs, |_, _| Ok(())),
+                Err(WeirollError::ArgumentTypeMismatch { argument: 0, .. })
+            ));
+        }
+
+        planner.set_max_iterations(10);
+        let total = planner.fold(items, zero.clone(), add)?;
+        assert_eq!(total, zero);
+        planner.call_address::<Math::addCall>(math(), vec![total.into(), U256::from(1).into()])?;
+
+        let (words, state) = planner.plan()?;
+        assert_eq!(words.len(), 4);
+        assert_eq!(words[2][..4], Loop::forEachCall::SELECTOR);
+        assert_eq!(
+            state[usize::from(words[2][6])][..],
+            U256::from(10).to_be_bytes::<32>()
+        );
+
+        // The loop body reads the element, then adds it into the accumulator's slot
+        let body = &state[usize::from(words[2][7] & 0x7f)];
+        assert_eq!(body[32..36], Loop::elementCall::SELECTOR);
+        assert_eq!(body[64 + 5], words[1][11]);
+        assert_eq!(body[64 + 11], words[1][11]);
+        assert_eq!(words[3][5], words[1][11]);
+        Ok(())
+    }
+}
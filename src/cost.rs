@@ -0,0 +1,374 @@
+//! Estimating what a plan costs to send and run, without an RPC.
+//!
+//! [`Planner::cost`] prices the `execute` calldata exactly, and the work the VM does around each
+//! command roughly: building its calldata from the state and writing its output back. The gas
+//! of the calls themselves is not included, so estimates are for comparing variants of a plan
+//! rather than for setting a gas limit.
+//!
+//! ```ignore
+//! let cost = planner.cost()?;
+//! println!("{} bytes, {} gas", cost.calldata_size, cost.total_gas());
+//! ```
+
+use crate::Planner;
+use crate::bindings::testable_vm::TestableVM::executeCall;
+use crate::dialect::Slot;
+use crate::error::{CommandContext, WeirollError};
+
+use alloy::primitives::Bytes;
+use alloy::sol_types::SolCall;
+
+/// Gas every transaction pays before its calldata.
+const TX_BASE_GAS: u64 = 21_000;
+/// EIP-2028 calldata gas per zero byte, and per token under EIP-7623.
+const STANDARD_TOKEN_GAS: u64 = 4;
+/// EIP-7623 floor gas per token.
+const FLOOR_TOKEN_GAS: u64 = 10;
+/// EIP-7623 tokens per non-zero byte.
+const NONZERO_TOKENS: u64 = 4;
+
+// VM overheads, measured with `CommandBuilderHarness` as the gas of each `test*` function less
+// that of its `test*BaseGas` counterpart, and rounded. The tests check them against the harness.
+
+/// Building the calldata of a command with no arguments.
+const BUILD_INPUTS_GAS: u64 = 684;
+/// Each static argument.
+const STATIC_ARG_GAS: u64 = 731;
+/// Each dynamic argument, plus [`DYNAMIC_ARG_WORD_GAS`] per word of its contents.
+const DYNAMIC_ARG_GAS: u64 = 1_805;
+const DYNAMIC_ARG_WORD_GAS: u64 = 16;
+/// Passing the whole state, plus [`STATE_ARG_SLOT_GAS`] per state slot.
+const STATE_ARG_GAS: u64 = 2_108;
+const STATE_ARG_SLOT_GAS: u64 = 560;
+/// Writing a static output.
+const STATIC_OUTPUT_GAS: u64 = 66;
+/// Writing a dynamic output, plus [`DYNAMIC_OUTPUT_WORD_GAS`] per word of it.
+const DYNAMIC_OUTPUT_GAS: u64 = 107;
+const DYNAMIC_OUTPUT_WORD_GAS: u64 = 13;
+/// Replacing the whole state, plus [`STATE_OUTPUT_SLOT_GAS`] per state slot.
+const STATE_OUTPUT_GAS: u64 = 234;
+const STATE_OUTPUT_SLOT_GAS: u64 = 1_289;
+
+/// The estimated cost of a plan, see [`Planner::cost`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PlanCost {
+    /// Bytes of `execute` calldata
+    pub calldata_size: usize,
+    /// Calldata bytes which are zero
+    pub zero_bytes: usize,
+    /// Calldata bytes which are not zero
+    pub nonzero_bytes: usize,
+    /// Calldata gas at EIP-2028 prices of 4 per zero byte and 16 per other byte
+    pub calldata_gas: u64,
+    /// The EIP-7623 floor on calldata gas, 10 per token
+    pub floor_gas: u64,
+    /// The VM's overhead for each top-level command, in plan order
+    pub commands: Vec<CommandCost>,
+}
+
+/// The estimated VM overhead of a single command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommandCost {
+    /// Identifies the command
+    pub context: CommandContext,
+    /// Gas to build the command's calldata from the state
+    pub build_inputs: u64,
+    /// Gas to write the command's output to the state
+    pub write_outputs: u64,
+}
+
+impl CommandCost {
+    /// The command's total VM overhead.
+    pub fn gas(&self) -> u64 {
+        self.build_inputs + self.write_outputs
+    }
+}
+
+impl PlanCost {
+    /// EIP-7623 calldata tokens: one per zero byte and four per other byte.
+    pub fn tokens(&self) -> u64 {
+        self.zero_bytes as u64 + NONZERO_TOKENS * self.nonzero_bytes as u64
+    }
+
+    /// The VM's overhead for all top-level commands.
+    pub fn vm_gas(&self) -> u64 {
+        self.commands.iter().map(CommandCost::gas).sum()
+    }
+
+    /// The estimated gas of sending the plan to the VM: the transaction base cost plus calldata
+    /// and VM overhead, or the EIP-7623 floor if that is higher. The calls made by commands are
+    /// not included.
+    pub fn total_gas(&self) -> u64 {
+        TX_BASE_GAS + (self.calldata_gas + self.vm_gas()).max(self.floor_gas)
+    }
+}
+
+impl Planner<'_> {
+    /// Estimates what sending this plan to the VM costs.
+    ///
+    /// Calldata is priced exactly. The VM overhead of each command is estimated from the size of
+    /// the state it reads; return values are counted as empty, as their size is only known once
+    /// the plan runs. Commands of subplans are not included, as how often they run is also only
+    /// known then.
+    pub fn cost(&self) -> Result<PlanCost, WeirollError> {
+        let (encoded, ps) = self.plan_with_state(false)?;
+        let commands = encoded.into_iter().map(|(_, word)| word).collect();
+        let calldata = executeCall {
+            commands,
+            state: ps.state.clone(),
+        }
+        .abi_encode();
+
+        let zero_bytes = calldata.iter().filter(|byte| **byte == 0).count();
+        let nonzero_bytes = calldata.len() - zero_bytes;
+        let tokens = zero_bytes as u64 + NONZERO_TOKENS * nonzero_bytes as u64;
+
+        let commands = self
+            .command_entries()
+            .enumerate()
+            .filter_map(|(index, (_, command))| {
+                let (args, output) = ps.command_slots.get(&vec![index])?;
+                Some(CommandCost {
+                    context: Self::context(index, command),
+                    build_inputs: build_inputs_gas(args, &ps.state),
+                    write_outputs: write_outputs_gas(*output, &ps.state),
+                })
+            })
+            .collect();
+
+        Ok(PlanCost {
+            calldata_size: calldata.len(),
+            zero_bytes,
+            nonzero_bytes,
+            calldata_gas: STANDARD_TOKEN_GAS * tokens,
+            floor_gas: FLOOR_TOKEN_GAS * tokens,
+            commands,
+        })
+    }
+}
+
+/// Words of the value in state slot `index`.
+fn words(state: &[Bytes], index: u8) -> u64 {
+    state
+        .get(usize::from(index))
+        .map_or(0, |value| value.len().div_ceil(32) as u64)
+}
+
+fn build_inputs_gas(args: &[Slot], state: &[Bytes]) -> u64 {
+    args.iter()
+        .map(|arg| match *arg {
+            Slot::Static(_) => STATIC_ARG_GAS,
+            Slot::Dynamic(index) => DYNAMIC_ARG_GAS + DYNAMIC_ARG_WORD_GAS * words(state, index),
+            Slot::State => STATE_ARG_GAS + STATE_ARG_SLOT_GAS * state.len() as u64,
+            Slot::Unused => 0,
+        })
+        .sum::<u64>()
+        + BUILD_INPUTS_GAS
+}
+
+fn write_outputs_gas(output: Slot, state: &[Bytes]) -> u64 {
+    match output {
+        Slot::Static(_) => STATIC_OUTPUT_GAS,
+        Slot::Dynamic(index) => DYNAMIC_OUTPUT_GAS + DYNAMIC_OUTPUT_WORD_GAS * words(state, index),
+        Slot::State => STATE_OUTPUT_GAS + STATE_OUTPUT_SLOT_GAS * state.len() as u64,
+        Slot::Unused => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::command_builder_harness::CommandBuilderHarness as Harness;
+    use crate::bindings::math::Math;
+    use crate::bindings::strings::Strings;
+    use crate::evm::Evm;
+    use alloy::primitives::{Address, FixedBytes, U256, address};
+    use alloy::sol_types::SolValue;
+
+    fn math() -> Address {
+        address!("0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee")
+    }
+
+    #[test]
+    fn calldata_counts_match_execute_calldata() {
+        let mut planner = Planner::default();
+        let sum = planner
+            .call_address::<Math::addCall>(math(), vec![U256::from(1).into(), U256::from(2).into()])
+            .unwrap();
+        planner
+            .call_address::<Math::mulCall>(math(), vec![sum.into(), U256::from(3).into()])
+            .unwrap();
+
+        let cost = planner.cost().unwrap();
+        let calldata = planner.execute_calldata().unwrap();
+        let zero = calldata.iter().filter(|byte| **byte == 0).count();
+
+        assert_eq!(cost.calldata_size, calldata.len());
+        assert_eq!(cost.zero_bytes, zero);
+        assert_eq!(cost.nonzero_bytes, calldata.len() - zero);
+        assert_eq!(
+            cost.calldata_gas,
+            calldata
+                .iter()
+                .map(|byte| if *byte == 0 { 4 } else { 16 })
+                .sum::<u64>()
+        );
+        assert_eq!(cost.floor_gas, 10 * cost.tokens());
+        assert_eq!(
+            cost.total_gas(),
+            21_000 + (cost.calldata_gas + cost.vm_gas()).max(cost.floor_gas)
+        );
+    }
+
+    #[test]
+    fn commands_are_priced_by_slot() {
+        let mut planner = Planner::default();
+        let sum = planner
+            .call_address::<Math::addCall>(math(), vec![U256::from(1).into(), U256::from(2).into()])
+            .unwrap();
+        planner
+            .call_address::<Math::mulCall>(math(), vec![sum.into(), U256::from(3).into()])
+            .unwrap();
+        planner
+            .call_address::<Strings::strlenCall>(math(), vec!["hello".to_string().into()])
+            .unwrap();
+
+        let cost = planner.cost().unwrap();
+        let [add, mul, strlen] = cost.commands.as_slice() else {
+            panic!("expected three commands, got {:?}", cost.commands);
+        };
+
+        assert_eq!(add.context.index, 0);
+        assert_eq!(add.build_inputs, 684 + 2 * 731);
+        assert_eq!(add.write_outputs, 66);
+        assert_eq!(mul.write_outputs, 0);
+        // "hello" is stored as its length and one word of contents
+        assert_eq!(strlen.build_inputs, 684 + 1_805 + 2 * 16);
+        assert_eq!(cost.vm_gas(), add.gas() + mul.gas() + strlen.gas());
+    }
+
+    #[test]
+    fn subplan_commands_are_not_counted() {
+        let mut subplanner = Planner::default();
+        subplanner
+            .call_address::<Strings::strlenCall>(math(), vec!["hello".to_string().into()])
+            .unwrap();
+        let mut planner = Planner::default();
+        planner
+            .call_address::<Math::addCall>(math(), vec![U256::from(1).into(), U256::from(2).into()])
+            .unwrap();
+        planner
+            .add_subplan_address::<crate::bindings::testable_vm::TestableVM::executeCall>(
+                math(),
+                vec![
+                    crate::Value::Subplan(&subplanner),
+                    crate::Value::State(vec![]),
+                ],
+            )
+            .unwrap();
+
+        let cost = planner.cost().unwrap();
+        let [add, subplan] = cost.commands.as_slice() else {
+            panic!("expected two commands, got {:?}", cost.commands);
+        };
+        assert_eq!(add.build_inputs, 684 + 2 * 731);
+        assert_eq!(subplan.write_outputs, 234 + 1_289 * 4);
+    }
+
+    /// Each overhead is within a tenth, or 50 gas, of what `CommandBuilderHarness` measures.
+    #[test]
+    fn overheads_match_command_builder_harness() {
+        let mut evm = Evm::default();
+        let harness = evm.deploy(&Harness::BYTECODE);
+        let mut gas = |call: Vec<u8>| evm.gas(harness, call.into());
+        let close = |measured: u64, estimated: u64| {
+            measured.abs_diff(estimated) <= (estimated / 10).max(50)
+        };
+
+        let word = || Bytes::from(vec![7; 32]);
+        let value = |words: usize| Bytes::from(vec![7; 32 * words]);
+
+        let build_inputs: [(Vec<Bytes>, Vec<u8>, Vec<Slot>); 5] = [
+            (vec![], vec![], vec![]),
+            (
+                vec![word(); 3],
+                vec![0, 1, 2],
+                (0..3).map(Slot::Static).collect(),
+            ),
+            (vec![value(1)], vec![0x80], vec![Slot::Dynamic(0)]),
+            (vec![value(8)], vec![0x80], vec![Slot::Dynamic(0)]),
+            (vec![word(); 4], vec![0xfe], vec![Slot::State]),
+        ];
+        for (state, indices, slots) in build_inputs {
+            let mut padded = [0xff; 32];
+            padded[..indices.len()].copy_from_slice(&indices);
+            let (selector, indices) = (FixedBytes::from([1, 2, 3, 4]), FixedBytes::from(padded));
+            let measured = gas(Harness::testBuildInputsCall {
+                state: state.clone(),
+                selector,
+                indices,
+            }
+            .abi_encode())
+                - gas(Harness::testBuildInputsBaseGasCall {
+                    state: state.clone(),
+                    selector,
+                    indices,
+                }
+                .abi_encode());
+            let estimated = build_inputs_gas(&slots, &state);
+            assert!(
+                close(measured, estimated),
+                "building {slots:?}: measured {measured}, estimated {estimated}"
+            );
+        }
+
+        let encoded = |value: &Bytes| Bytes::from(value.abi_encode());
+        // A replaced state is priced by the slots of the state replacing it, measured from a
+        // single-slot state as the constants were
+        let write_outputs: [(Vec<Bytes>, u8, Bytes, u64); 4] = [
+            (
+                vec![word()],
+                0x00,
+                word(),
+                write_outputs_gas(Slot::Static(0), &[]),
+            ),
+            (
+                vec![value(1)],
+                0x80,
+                encoded(&value(1)),
+                write_outputs_gas(Slot::Dynamic(0), &[value(1)]),
+            ),
+            (
+                vec![value(8)],
+                0x80,
+                encoded(&value(8)),
+                write_outputs_gas(Slot::Dynamic(0), &[value(8)]),
+            ),
+            (
+                vec![word()],
+                0xfe,
+                vec![word(); 4].abi_encode().into(),
+                write_outputs_gas(Slot::State, &[word(), word(), word(), word()]),
+            ),
+        ];
+        for (state, index, output, estimated) in write_outputs {
+            let index = FixedBytes::from([index]);
+            let measured = gas(Harness::testWriteOutputsCall {
+                state: state.clone(),
+                index,
+                output: output.clone(),
+            }
+            .abi_encode())
+                - gas(Harness::testWriteOutputsBaseGasCall {
+                    state: state.clone(),
+                    index,
+                    output,
+                }
+                .abi_encode());
+            assert!(
+                close(measured, estimated),
+                "writing to {index}: measured {measured}, estimated {estimated}"
+            );
+        }
+    }
+}
@@ -0,0 +1,297 @@
+//! Encoding planned commands for a particular VM.
+//!
+//! The planner decides which state slot each argument and output uses; a [`VmDialect`] turns
+//! that into command words. [`WeirollV1`] encodes for the upstream weiroll VM and is used unless
+//! [`Planner::set_dialect`] selects another, so a fork with different flags or an
+//! extended-command format can be supported by implementing the trait:
+//!
+//! ```ignore
+//! #[derive(Debug)]
+//! struct WideArgs;
+//!
+//! impl VmDialect for WideArgs {
+//!     fn encode(&self, command: &CommandParts) -> Result<Vec<FixedBytes<32>>, WeirollError> {
+//!         ...
+//!     }
+//! }
+//!
+//! planner.set_dialect(WideArgs);
+//! ```
+
+use crate::Planner;
+use crate::cmds::CommandFlags;
+use crate::error::{CommandContext, WeirollError};
+use crate::view::CallType;
+
+use alloy::primitives::{Address, FixedBytes};
+use std::fmt;
+use std::sync::Arc;
+
+/// Where a command reads an argument from or writes its output to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Slot {
+    /// A state slot holding a 32-byte value
+    Static(u8),
+    /// A state slot holding a variable-length value
+    Dynamic(u8),
+    /// The whole state, as a `bytes[]`
+    State,
+    /// No slot; the output is discarded
+    Unused,
+}
+
+/// A command with its arguments and output resolved to state slots, ready to be encoded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommandParts {
+    /// Identifies the command in errors
+    pub context: CommandContext,
+    pub selector: FixedBytes<4>,
+    pub call_type: CallType,
+    /// Whether the VM should wrap the output in a `bytes`, so tuples can be stored in one slot
+    pub tuple_return: bool,
+    /// Flag bits the planner does not interpret, see [`Planner::set_extra_flags`]
+    pub extra_flags: u8,
+    pub args: Vec<Slot>,
+    pub output: Slot,
+    pub target: Address,
+}
+
+/// The command encoding of a weiroll VM.
+pub trait VmDialect: fmt::Debug + Send + Sync {
+    /// Checks the VM can execute `command`.
+    fn validate(&self, command: &CommandParts) -> Result<(), WeirollError> {
+        WeirollV1.validate(command)
+    }
+
+    /// Encodes a reference to `slot` made by `command`.
+    fn encode_slot(&self, command: &CommandParts, slot: Slot) -> Result<u8, WeirollError> {
+        WeirollV1.encode_slot(command, slot)
+    }
+
+    /// Encodes `command`, which has been validated, as one or more command words.
+    fn encode(&self, command: &CommandParts) -> Result<Vec<FixedBytes<32>>, WeirollError>;
+}
+
+/// The upstream weiroll VM.
+///
+/// A command word holds the selector, a flags byte, six argument slots, the output slot and the
+/// target address. Commands with more than six arguments set the extended flag and list up to
+/// 32 argument slots in a second word.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WeirollV1;
+
+impl WeirollV1 {
+    const MAX_SLOT: u8 = 0x7f;
+    /// A dynamic slot sets the high bit, so 0x7e and 0x7f would read as the state and unused slots
+    const MAX_DYNAMIC_SLOT: u8 = 0x7d;
+    const MAX_ARGS: usize = 32;
+}
+
+impl VmDialect for WeirollV1 {
+    fn validate(&self, command: &CommandParts) -> Result<(), WeirollError> {
+        if command.extra_flags != 0 {
+            return Err(WeirollError::UnsupportedFlags {
+                command: command.context,
+                flags: command.extra_flags,
+            });
+        }
+
+        if command.args.len() > Self::MAX_ARGS {
+            return Err(WeirollError::TooManyArguments {
+                command: command.context,
+                max: Self::MAX_ARGS,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn encode_slot(&self, command: &CommandParts, slot: Slot) -> Result<u8, WeirollError> {
+        match slot {
+            Slot::Static(index) if index > Self::MAX_SLOT => Err(WeirollError::SlotOutOfRange {
+                command: command.context,
+                slot: index.into(),
+            }),
+            Slot::Dynamic(index) if index > Self::MAX_DYNAMIC_SLOT => {
+                Err(WeirollError::SlotOutOfRange {
+                    command: command.context,
+                    slot: index.into(),
+                })
+            }
+            Slot::Static(index) => Ok(index),
+            Slot::Dynamic(index) => Ok(index | 0x80),
+            Slot::State => Ok(0xfe),
+            Slot::Unused => Ok(0xff),
+        }
+    }
+
+    fn encode(&self, command: &CommandParts) -> Result<Vec<FixedBytes<32>>, WeirollError> {
+        let mut flags = command.call_type.flags();
+        if command.tuple_return {
+            flags |= CommandFlags::TUPLE_RETURN;
+        }
+
+        let args = command
+            .args
+            .iter()
+            .map(|slot| self.encode_slot(command, *slot))
+            .collect::<Result<Vec<u8>, _>>()?;
+        let output = self.encode_slot(command, command.output)?;
+
+        let word = |flags: CommandFlags, args: &[u8]| {
+            let mut word = [0xff; 32];
+            word[..4].copy_from_slice(command.selector.as_slice());
+            word[4] = flags.bits();
+            word[5..5 + args.len()].copy_from_slice(args);
+            word[11] = output;
+            word[12..].copy_from_slice(command.target.as_slice());
+            FixedBytes::from(word)
+        };
+
+        if args.len() <= 6 {
+            return Ok(vec![word(flags, &args)]);
+        }
+
+        let mut indices = [0xff; 32];
+        indices[..args.len()].copy_from_slice(&args);
+        Ok(vec![
+            word(flags | CommandFlags::EXTENDED_COMMAND, &[0; 6]),
+            indices.into(),
+        ])
+    }
+}
+
+impl Planner<'_> {
+    /// Encodes commands for `dialect` rather than the upstream weiroll VM.
+    ///
+    /// Subplans are encoded with the dialect of the planner being planned.
+    pub fn set_dialect(&mut self, dialect: impl VmDialect + 'static) {
+        self.dialect = Some(Arc::new(dialect));
+    }
+
+    pub(crate) fn dialect(&self) -> &dyn VmDialect {
+        self.dialect.as_deref().unwrap_or(&WeirollV1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::math::Math;
+    use crate::{ReturnValue, Value};
+    use alloy::primitives::{U256, address};
+    use alloy::sol;
+    use alloy::sol_types::SolCall;
+
+    sol! {
+        interface Wide {
+            function wide(uint256 a, uint256 b, uint256 c, uint256 d, uint256 e, uint256 f, uint256 g) external returns (uint256);
+        }
+    }
+
+    fn addr() -> Address {
+        address!("0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee")
+    }
+
+    /// Puts up to eight arguments in the command word, for a VM without flags or output slots.
+    #[derive(Debug)]
+    struct EightArgs;
+
+    impl VmDialect for EightArgs {
+        fn validate(&self, command: &CommandParts) -> Result<(), WeirollError> {
+            if command.args.len() > 8 {
+                return Err(WeirollError::TooManyArguments {
+                    command: command.context,
+                    max: 8,
+                });
+            }
+            Ok(())
+        }
+
+        fn encode(&self, command: &CommandParts) -> Result<Vec<FixedBytes<32>>, WeirollError> {
+            let mut word = [0xff; 32];
+            word[..4].copy_from_slice(command.selector.as_slice());
+            for (i, slot) in command.args.iter().enumerate() {
+                word[4 + i] = self.encode_slot(command, *slot)?;
+            }
+            word[12..].copy_from_slice(command.target.as_slice());
+            Ok(vec![word.into()])
+        }
+    }
+
+    fn wide(planner: &mut Planner<'_>) -> Result<ReturnValue, WeirollError> {
+        let args: Vec<Value> = (1..=7).map(|i| U256::from(i).into()).collect();
+        planner.call_address::<Wide::wideCall>(addr(), args)
+    }
+
+    #[test]
+    fn default_dialect_encodes_extended_commands() -> Result<(), WeirollError> {
+        let mut planner = Planner::default();
+        wide(&mut planner)?;
+
+        let (commands, _) = planner.plan()?;
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0][4], 0x41);
+        assert_eq!(commands[0][5..11], [0; 6]);
+        assert_eq!(commands[0][11], 0xff);
+        assert_eq!(commands[1][..8], [0, 1, 2, 3, 4, 5, 6, 0xff]);
+
+        planner.set_extra_flags(&planner.commands()[0].output.clone(), 0x20)?;
+        assert!(matches!(
+            planner.plan(),
+            Err(WeirollError::UnsupportedFlags { flags: 0x20, .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn slots_stay_clear_of_reserved_bytes() {
+        let command = CommandParts {
+            context: CommandContext {
+                index: 0,
+                selector: Wide::wideCall::SELECTOR.into(),
+                target: addr(),
+            },
+            selector: Wide::wideCall::SELECTOR.into(),
+            call_type: CallType::Call,
+            tuple_return: false,
+            extra_flags: 0,
+            args: vec![],
+            output: Slot::Unused,
+            target: addr(),
+        };
+        let encode = |slot| WeirollV1.encode_slot(&command, slot);
+
+        assert_eq!(encode(Slot::Static(0x7f)).unwrap(), 0x7f);
+        assert_eq!(encode(Slot::Dynamic(0x7d)).unwrap(), 0xfd);
+        for (slot, index) in [
+            (Slot::Static(0x80), 0x80),
+            (Slot::Dynamic(0x7e), 0x7e),
+            (Slot::Dynamic(0x7f), 0x7f),
+        ] {
+            assert!(matches!(
+                encode(slot),
+                Err(WeirollError::SlotOutOfRange { slot, .. }) if slot == index
+            ));
+        }
+    }
+
+    #[test]
+    fn custom_dialect_encodes_commands() -> Result<(), WeirollError> {
+        let mut planner = Planner::default();
+        planner.set_dialect(EightArgs);
+        wide(&mut planner)?;
+
+        let (commands, _) = planner.plan()?;
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0][4..12], [0, 1, 2, 3, 4, 5, 6, 0xff]);
+
+        let args: Vec<Value> = (1..=9).map(|i| U256::from(i).into()).collect();
+        planner.call_address::<Math::sumCall>(addr(), args)?;
+        assert!(matches!(
+            planner.plan(),
+            Err(WeirollError::TooManyArguments { max: 8, .. })
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,310 @@
+//! Rendering plans as Graphviz graphs for review.
+//!
+//! [`Planner::to_dot`] draws each command as a node, labelled with its position, selector (or
+//! label), call type and target. Edges run from the inputs of a command to the command: from the
+//! commands whose outputs it consumes, and from leaf nodes for literals and the state. Subplans
+//! are drawn as clusters, with an edge from the cluster to the command running it.
+//!
+//! ```ignore
+//! std::fs::write("plan.dot", planner.to_dot(true)?)?;
+//! // dot -Tsvg plan.dot > plan.svg
+//! ```
+
+use crate::Planner;
+use crate::cmds::{Command, CommandType, Literal, Value};
+use crate::dialect::Slot;
+use crate::error::WeirollError;
+use crate::planner::{CommandKey, PlannerState};
+use crate::view::CallType;
+
+use alloy::primitives::U256;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+impl Planner<'_> {
+    /// Renders the plan as a Graphviz `digraph`.
+    ///
+    /// With `slots`, the plan is planned and each node also shows the state slots its arguments
+    /// are read from and its output is written to, so slot reuse can be reviewed; this fails if
+    /// the plan cannot be planned.
+    pub fn to_dot(&self, slots: bool) -> Result<String, WeirollError> {
+        let state = if slots {
+            Some(self.plan_with_state(false)?.1)
+        } else {
+            None
+        };
+
+        let mut dot = Dot {
+            state,
+            ..Default::default()
+        };
+        dot.out
+            .push_str("digraph plan {\n    compound=true;\n    node [shape=box];\n");
+        dot.commands(self, &[]);
+        for (node, label) in &dot.leaves {
+            let _ = writeln!(dot.out, "    {node} [shape=ellipse, label=\"{label}\"];");
+        }
+        for edge in &dot.edges {
+            let _ = writeln!(dot.out, "    {edge};");
+        }
+        dot.out.push_str("}\n");
+
+        Ok(dot.out)
+    }
+}
+
+#[derive(Default)]
+struct Dot {
+    out: String,
+    /// Planned slots, when nodes are annotated with them
+    state: Option<PlannerState>,
+    /// Node ids of the commands whose outputs are visible
+    nodes: BTreeMap<CommandKey, String>,
+    /// Node ids of literals, shared by every command reading the same literal
+    literals: BTreeMap<Literal, String>,
+    /// Literal and state nodes with their labels, drawn outside any cluster
+    leaves: Vec<(String, String)>,
+    /// Edges, drawn last so that nodes stay in the cluster declaring them
+    edges: Vec<String>,
+    clusters: usize,
+}
+
+impl Dot {
+    /// Draws the commands of the planner at `subplan`, the indices of the subplan commands
+    /// enclosing it, returning the node id of the first.
+    fn commands(&mut self, planner: &Planner<'_>, subplan: &[usize]) -> Option<String> {
+        let indent = "    ".repeat(subplan.len() + 1);
+        let mut first = None;
+
+        for (index, (key, command)) in planner.command_entries().enumerate() {
+            let path = [subplan, &[index]].concat();
+            let node = format!(
+                "c{}",
+                path.iter()
+                    .map(usize::to_string)
+                    .collect::<Vec<_>>()
+                    .join("_")
+            );
+            let label = self.label(&path, command);
+            let _ = writeln!(self.out, "{indent}{node} [label=\"{label}\"];");
+            first.get_or_insert_with(|| node.clone());
+
+            for (argument, arg) in command.call.args.iter().enumerate() {
+                self.arg(arg, &node, &argument.to_string(), &path);
+            }
+            self.nodes.insert(key, node);
+        }
+
+        first
+    }
+
+    fn label(&self, path: &[usize], command: &Command<'_>) -> String {
+        let index = path.last().copied().unwrap_or_default();
+        let call = &command.call;
+        let name = match &command.label {
+            Some(label) => escape(label),
+            None => alloy::hex::encode_prefixed(call.selector),
+        };
+        let call_type = match CallType::from_flags(call.flags) {
+            CallType::Call => "CALL".to_string(),
+            CallType::DelegateCall => "DELEGATECALL".to_string(),
+            CallType::StaticCall => "STATICCALL".to_string(),
+            CallType::CallWithValue => format!("CALL{{value: {}}}", call.value.unwrap_or_default()),
+        };
+        let mut label = format!("#{index} {name}\\n{call_type} {}", call.address);
+
+        if let Some((args, output)) = self
+            .state
+            .as_ref()
+            .and_then(|state| state.command_slots.get(path))
+        {
+            let args = args.iter().map(|arg| slot(*arg)).collect::<Vec<_>>();
+            let _ = write!(label, "\\nin: [{}] out: {}", args.join(", "), slot(*output));
+        } else if command.kind == CommandType::RawCall {
+            label.push_str("\\nout: state");
+        }
+
+        label
+    }
+
+    fn arg(&mut self, arg: &Value<'_>, node: &str, argument: &str, path: &[usize]) {
+        match arg {
+            Value::Literal(literal) => {
+                let leaf = self.literal(literal);
+                self.edges
+                    .push(format!("{leaf} -> {node} [label=\"{argument}\"]"));
+            }
+            Value::Return(ret) => {
+                if let Some(producer) = self.nodes.get(&ret.command) {
+                    self.edges
+                        .push(format!("{producer} -> {node} [label=\"{argument}\"]"));
+                }
+            }
+            Value::State(_) => {
+                if !self.leaves.iter().any(|(leaf, _)| leaf == "state") {
+                    self.leaves.push(("state".to_string(), "state".to_string()));
+                }
+                self.edges
+                    .push(format!("state -> {node} [label=\"{argument}\"]"));
+            }
+            Value::Subplan(_) | Value::OwnedSubplan(_) => {
+                let Some(subplan) = arg.subplan() else {
+                    return;
+                };
+                let indent = "    ".repeat(path.len());
+                let cluster = format!("cluster_{}", self.clusters);
+                self.clusters += 1;
+
+                let _ = writeln!(self.out, "{indent}subgraph {cluster} {{");
+                let _ = writeln!(self.out, "{indent}    label=\"subplan\";");
+                // Subplans planned separately have their own keys, which may shadow ours
+                let outer = self.nodes.clone();
+                let first = self.commands(subplan, path).unwrap_or_else(|| {
+                    let empty = format!("{cluster}_empty");
+                    let _ = writeln!(self.out, "{indent}    {empty} [label=\"empty\"];");
+                    empty
+                });
+                let _ = writeln!(self.out, "{indent}}}");
+                for (key, node) in outer {
+                    self.nodes.insert(key, node);
+                }
+
+                self.edges.push(format!(
+                    "{first} -> {node} [ltail={cluster}, label=\"{argument}\"]"
+                ));
+            }
+            Value::Composite(composite) => {
+                for (element, value) in composite.elements().iter().enumerate() {
+                    self.arg(value, node, &format!("{argument}.{element}"), path);
+                }
+            }
+        }
+    }
+
+    fn literal(&mut self, literal: &Literal) -> String {
+        if let Some(leaf) = self.literals.get(literal) {
+            return leaf.clone();
+        }
+
+        let leaf = format!("l{}", self.literals.len());
+        let bytes = literal.bytes();
+        let mut label = if literal.dynamic || bytes.len() != 32 {
+            format!("{} bytes", bytes.len())
+        } else {
+            let value = U256::from_be_slice(&bytes);
+            if value <= U256::from(u64::MAX) {
+                value.to_string()
+            } else {
+                alloy::hex::encode_prefixed(&bytes)
+            }
+        };
+
+        if let Some(slot) = self
+            .state
+            .as_ref()
+            .and_then(|state| state.literal_slot_map.get(literal))
+        {
+            let _ = write!(label, "\\nslot {slot}");
+        }
+
+        self.literals.insert(literal.clone(), leaf.clone());
+        self.leaves.push((leaf.clone(), label));
+        leaf
+    }
+}
+
+fn slot(slot: Slot) -> String {
+    match slot {
+        Slot::Static(index) => index.to_string(),
+        Slot::Dynamic(index) => format!("{index} (dyn)"),
+        Slot::State => "state".to_string(),
+        Slot::Unused => "-".to_string(),
+    }
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::math::Math;
+    use crate::bindings::testable_vm::TestableVM;
+    use alloy::primitives::{Address, address};
+
+    fn addr() -> Address {
+        address!("0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee")
+    }
+
+    #[test]
+    fn commands_literals_and_dependencies() {
+        let mut planner = Planner::default();
+        let sum = planner
+            .call_address::<Math::addCall>(addr(), vec![U256::from(1).into(), U256::from(2).into()])
+            .unwrap();
+        planner.set_label(&sum, "sum").unwrap();
+        planner
+            .call_address::<Math::mulCall>(addr(), vec![sum.into(), U256::from(2).into()])
+            .unwrap();
+
+        let dot = planner.to_dot(false).unwrap();
+        assert!(dot.starts_with("digraph plan {"));
+        assert!(dot.contains(&format!("c0 [label=\"#0 sum\\nCALL {}\"];", addr())));
+        assert!(dot.contains("c1 [label=\"#1 0xc8a4ac9c\\nCALL"));
+        assert!(dot.contains("l0 [shape=ellipse, label=\"1\"];"));
+        assert!(dot.contains("l1 [shape=ellipse, label=\"2\"];"));
+        assert!(dot.contains("c0 -> c1 [label=\"0\"];"));
+        // Both commands read the same literal
+        assert!(dot.contains("l1 -> c0 [label=\"1\"];"));
+        assert!(dot.contains("l1 -> c1 [label=\"1\"];"));
+        assert!(!dot.contains("in: ["));
+    }
+
+    #[test]
+    fn slots_show_reuse() {
+        let mut planner = Planner::default();
+        let a = planner
+            .call_address::<Math::addCall>(addr(), vec![U256::from(1).into(), U256::from(2).into()])
+            .unwrap();
+        let b = planner
+            .call_address::<Math::addCall>(addr(), vec![a.into(), U256::from(3).into()])
+            .unwrap();
+        planner
+            .call_address::<Math::addCall>(addr(), vec![b.into(), U256::from(4).into()])
+            .unwrap();
+
+        let dot = planner.to_dot(true).unwrap();
+        assert!(dot.contains("l0 [shape=ellipse, label=\"1\\nslot 0\"];"));
+        // Each output reuses the slot of an input which expires with the command writing it
+        assert!(dot.contains("c0 [label=\"#0 0x771602f7\\nCALL"));
+        assert!(dot.contains("\\nin: [0, 1] out: 1\"];"));
+        assert!(dot.contains("\\nin: [1, 2] out: 1\"];"));
+        assert!(dot.contains("\\nin: [1, 3] out: -\"];"));
+    }
+
+    #[test]
+    fn subplans_are_clusters() {
+        let mut subplanner = Planner::default();
+        subplanner
+            .call_address::<Math::addCall>(addr(), vec![U256::from(1).into(), U256::from(2).into()])
+            .unwrap();
+        let mut planner = Planner::default();
+        planner
+            .add_subplan_address::<TestableVM::executeCall>(
+                addr(),
+                vec![Value::Subplan(&subplanner), Value::State(vec![])],
+            )
+            .unwrap();
+
+        let dot = planner.to_dot(true).unwrap();
+        assert!(
+            dot.contains("    subgraph cluster_0 {\n        label=\"subplan\";\n        c0_0 [")
+        );
+        assert!(dot.contains("c0_0 [label=\"#0 0x771602f7\\nCALL"));
+        assert!(dot.contains("in: [0, 1] out: -\"];\n    }"));
+        assert!(dot.contains("c0_0 -> c0 [ltail=cluster_0, label=\"0\"];"));
+        assert!(dot.contains("state -> c0 [label=\"1\"];"));
+        assert!(dot.contains("out: state\"];"));
+    }
+}
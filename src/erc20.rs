@@ -0,0 +1,149 @@
+//! ERC20 patterns built from [`IERC20`] calls and the [`Math`](crate::bindings::math::Math)
+//! library.
+//!
+//! ```ignore
+//! let (received, _) = erc20::balance_delta(&mut planner, math, weth, vm, |p| {
+//!     erc20::approve_and_call(p, usdc, router, amount, |p| swap(p, amount))
+//! })?;
+//! ```
+
+use crate::Planner;
+use crate::bindings::ierc20::IERC20;
+use crate::cmds::{ReturnValue, Value};
+use crate::error::WeirollError;
+
+use alloy::primitives::{Address, U256};
+
+/// Measures how much `holder`'s balance of `token` changes while running `build`.
+///
+/// STATICCALLs `balanceOf` before and after the commands `build` plans, and DELEGATECALLs `sub`
+/// on the Math library at `math` to compute the increase. The plan reverts on underflow, so use
+/// this for balances which are expected to grow. Returns the increase and the result of `build`.
+pub fn balance_delta<'a, T>(
+    planner: &mut Planner<'a>,
+    math: Address,
+    token: Address,
+    holder: impl Into<Value<'a>>,
+    build: impl FnOnce(&mut Planner<'a>) -> Result<T, WeirollError>,
+) -> Result<(ReturnValue, T), WeirollError> {
+    let holder = holder.into();
+
+    let before =
+        planner.staticcall_address::<IERC20::balanceOfCall>(token, vec![holder.clone()])?;
+    let built = build(planner)?;
+    let after = planner.staticcall_address::<IERC20::balanceOfCall>(token, vec![holder])?;
+
+    let delta = planner.math(math).sub(after, before)?;
+    Ok((delta, built))
+}
+
+/// Approves `spender` for `amount` of `token`, runs `build`, then resets the allowance to zero
+/// so none is left over if `spender` pulled less than `amount`.
+pub fn approve_and_call<'a, T>(
+    planner: &mut Planner<'a>,
+    token: Address,
+    spender: Address,
+    amount: impl Into<Value<'a>>,
+    build: impl FnOnce(&mut Planner<'a>) -> Result<T, WeirollError>,
+) -> Result<T, WeirollError> {
+    planner.call_address::<IERC20::approveCall>(token, vec![spender.into(), amount.into()])?;
+    let built = build(planner)?;
+    planner.call_address::<IERC20::approveCall>(token, vec![spender.into(), U256::ZERO.into()])?;
+
+    Ok(built)
+}
+
+/// Sets the allowance of `spender` to zero before approving `amount`.
+///
+/// Tokens such as USDT refuse to change a non-zero allowance to another non-zero value.
+pub fn reset_and_approve<'a>(
+    planner: &mut Planner<'a>,
+    token: Address,
+    spender: Address,
+    amount: impl Into<Value<'a>>,
+) -> Result<ReturnValue, WeirollError> {
+    planner.call_address::<IERC20::approveCall>(token, vec![spender.into(), U256::ZERO.into()])?;
+    planner.call_address::<IERC20::approveCall>(token, vec![spender.into(), amount.into()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::math::Math;
+    use crate::view::{ArgView, CallType};
+    use alloy::primitives::address;
+    use alloy::sol_types::SolCall;
+
+    fn math() -> Address {
+        address!("0x1111111111111111111111111111111111111111")
+    }
+
+    fn token() -> Address {
+        address!("0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2")
+    }
+
+    fn spender() -> Address {
+        address!("0x2222222222222222222222222222222222222222")
+    }
+
+    #[test]
+    fn balance_delta_brackets_fragment() -> Result<(), WeirollError> {
+        let holder = address!("0x4d5F47FA6A74757f35C14fD3a6Ef8E3C9BC514E8");
+
+        let mut planner = Planner::default();
+        let (delta, transferred) = balance_delta(&mut planner, math(), token(), holder, |p| {
+            p.call_address::<IERC20::transferCall>(
+                token(),
+                vec![holder.into(), U256::from(5).into()],
+            )
+        })?;
+
+        let commands = planner.commands();
+        assert_eq!(commands.len(), 4);
+        assert_eq!(commands[0].selector, IERC20::balanceOfCall::SELECTOR);
+        assert_eq!(commands[0].call_type, CallType::StaticCall);
+        assert_eq!(commands[1].output, transferred);
+        assert_eq!(commands[2].selector, IERC20::balanceOfCall::SELECTOR);
+        assert_eq!(commands[3].selector, Math::subCall::SELECTOR);
+        assert_eq!(commands[3].call_type, CallType::DelegateCall);
+        assert_eq!(commands[3].output, delta);
+
+        let operands: Vec<_> = commands[3]
+            .args
+            .iter()
+            .map(|arg| match arg {
+                ArgView::Return(ret) => Some(ret.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            operands,
+            vec![
+                Some(commands[2].output.clone()),
+                Some(commands[0].output.clone())
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn approvals_reset_allowance() -> Result<(), WeirollError> {
+        let mut planner = Planner::default();
+        approve_and_call(&mut planner, token(), spender(), U256::from(7), |_| Ok(()))?;
+        reset_and_approve(&mut planner, token(), spender(), U256::from(9))?;
+
+        let amounts: Vec<U256> = planner
+            .commands()
+            .iter()
+            .map(|c| {
+                assert_eq!(c.selector, IERC20::approveCall::SELECTOR);
+                match &c.args[1] {
+                    ArgView::Literal(bytes) => U256::from_be_slice(bytes),
+                    other => panic!("unexpected argument {other:?}"),
+                }
+            })
+            .collect();
+        assert_eq!(amounts, [7, 0, 0, 9].map(U256::from).to_vec());
+        Ok(())
+    }
+}
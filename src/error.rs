@@ -1,44 +1,201 @@
+use alloy::dyn_abi::DynSolType;
+use alloy::primitives::{Address, FixedBytes};
+use alloy::transports::TransportError;
+use std::fmt;
 use thiserror::Error;
 
+/// Identifies the command an error relates to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CommandContext {
+    /// Position of the command in its planner
+    pub index: usize,
+    pub selector: FixedBytes<4>,
+    pub target: Address,
+}
+
+impl fmt::Display for CommandContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "command {} (selector {} on {})",
+            self.index, self.selector, self.target
+        )
+    }
+}
+
 #[derive(Error, Debug, PartialEq)]
 pub enum WeirollError {
-    #[error("unable to plan")]
-    PlanError,
-
-    #[error("Call with value must have a value parameter")]
-    MissingValue,
+    #[error("{command}: call with value must have a value parameter")]
+    MissingValue { command: CommandContext },
 
     // todo: panic on these?
-    #[error("internal error: missing return slot")]
-    MissingReturnSlot,
+    #[error("internal error: {command}: missing return slot for argument {argument}")]
+    MissingReturnSlot {
+        command: CommandContext,
+        argument: usize,
+    },
+
+    #[error("internal error: {command}: invalid return slot")]
+    InvalidReturnSlot { command: CommandContext },
+
+    #[error("internal error: {command}: missing literal value for argument {argument}")]
+    MissingLiteralValue {
+        command: CommandContext,
+        argument: usize,
+    },
+
+    #[error("internal error: {command}: missing subplan")]
+    MissingSubplan { command: CommandContext },
 
-    #[error("internal error: invalid return slot")]
-    InvalidReturnSlot,
+    #[error("{command}: expected {expected} arguments, got {actual}")]
+    ArgumentCountMismatch {
+        command: CommandContext,
+        expected: usize,
+        actual: usize,
+    },
 
-    #[error("internal error: missing literal value")]
-    MissingLiteralValue,
+    #[error("{command}: argument {argument} expects {expected}, got {actual}")]
+    ArgumentTypeMismatch {
+        command: CommandContext,
+        argument: usize,
+        expected: DynSolType,
+        actual: DynSolType,
+    },
 
-    #[error("internal error: missing subplan")]
-    MissingSubplan,
+    #[error("{command}: unable to match a return value to a struct field")]
+    UnresolvedField { command: CommandContext },
 
-    #[error("argument count mismatch")]
-    ArgumentCountMismatch,
+    #[error("{command}: subplans can only take one planner argument")]
+    MultipleSubplans { command: CommandContext },
 
-    #[error("Subplans can only take one planner argument")]
-    MultipleSubplans,
+    #[error("{command}: subplans can only take one state argument")]
+    MultipleState { command: CommandContext },
 
-    #[error("Subplans can only take one state argument")]
-    MultipleState,
+    #[error("{command}: subplans must take planner and state arguments")]
+    MissingStateOrSubplan { command: CommandContext },
 
-    #[error("Subplans must take planner and state arguments")]
-    MissingStateOrSubplan,
+    #[error("{command}: subplan failed to plan")]
+    Subplan {
+        command: CommandContext,
+        source: Box<WeirollError>,
+    },
 
     #[error("integer overflow")]
     InternalOverflow(#[from] std::num::TryFromIntError),
 
-    #[error("command not visible here")]
-    CommandNotVisible,
+    #[error("{command}: argument {argument} uses a return value which is not visible here")]
+    CommandNotVisible {
+        command: CommandContext,
+        argument: usize,
+    },
+
+    #[error("return value was not produced by this plan")]
+    UnknownReturnValue,
+
+    #[error("{command}: output is still consumed by commands {dependents:?}")]
+    HasDependents {
+        command: CommandContext,
+        dependents: Vec<usize>,
+    },
+
+    #[error("{command}: unable to decode return value")]
+    ReturnDecode {
+        command: CommandContext,
+        source: alloy::dyn_abi::Error,
+    },
+
+    #[error(
+        "{command}: calldata of {length} bytes is not a selector followed by whole 32-byte words"
+    )]
+    UnencodableCalldata {
+        command: CommandContext,
+        length: usize,
+    },
+
+    #[error("{command}: argument {argument} has no packed encoding")]
+    UnpackableArgument {
+        command: CommandContext,
+        argument: usize,
+    },
+
+    #[error("a Builder contract must be set to plan arrays and tuples of return values")]
+    MissingBuilder,
+
+    #[error("a TryCatch contract must be set to plan commands which may fail")]
+    MissingTryCatch,
+
+    #[error("a Conditional contract must be set to plan branches")]
+    MissingConditional,
+
+    #[error("branches define output {label:?} with different types")]
+    BranchTypeMismatch { label: String },
+
+    #[error("a Loop contract must be set to plan loops")]
+    MissingLoop,
+
+    #[error("loop accumulator is {expected}, but the loop body returns {actual}")]
+    AccumulatorTypeMismatch {
+        expected: DynSolType,
+        actual: DynSolType,
+    },
+
+    #[error("a Carry contract must be set to split a plan where outputs are still live")]
+    MissingCarry,
+
+    #[error("{command}: does not fit in the budget on its own")]
+    ExceedsBudget { command: CommandContext },
+
+    #[error(
+        "{consumer}: consumes the output of {producer}, so the plan cannot be split between them without a Carry contract"
+    )]
+    LiveAcrossSplit {
+        producer: CommandContext,
+        consumer: CommandContext,
+    },
+
+    #[error("{command}: flags {flags:#04x} are not supported by the VM dialect")]
+    UnsupportedFlags { command: CommandContext, flags: u8 },
+
+    #[error("{command}: the VM dialect accepts at most {max} arguments")]
+    TooManyArguments { command: CommandContext, max: usize },
+
+    #[error("{command}: state slot {slot} is out of range for the VM dialect")]
+    SlotOutOfRange {
+        command: CommandContext,
+        slot: usize,
+    },
 
     #[error("unable to parse Solidity type")]
     AbiTypeParse(#[from] alloy::dyn_abi::Error),
+
+    #[error("script statement `{statement}` failed")]
+    Script {
+        statement: &'static str,
+        source: Box<WeirollError>,
+    },
+}
+
+#[derive(Error, Debug)]
+pub enum ProviderError {
+    #[error(transparent)]
+    Plan(#[from] WeirollError),
+
+    #[error(
+        "command {} calling {target} failed: {message}",
+        .command.map_or_else(|| String::from("unknown"), |command| command.to_string())
+    )]
+    ExecutionFailed {
+        /// Position of the failing command in the planner, if it could be determined
+        command: Option<usize>,
+        /// Index of the failing command word, as reported by the VM, which always reports 0
+        command_index: usize,
+        target: Address,
+        message: String,
+    },
+
+    #[error(transparent)]
+    Transport(#[from] TransportError),
+
+    #[error("unable to decode VM return data")]
+    Decode(#[from] alloy::sol_types::Error),
 }
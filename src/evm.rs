@@ -0,0 +1,149 @@
+//! An in-process EVM for tests, which runs plans on the bundled contracts without a node.
+
+use crate::Planner;
+use crate::bindings::testable_vm::TestableVM;
+use crate::error::ProviderError;
+use crate::provider::{PlanSimulation, PreparedPlan};
+
+use alloy::primitives::{Address, Bytes, U256, address};
+use alloy::sol_types::{SolCall, SolError};
+use revm::context::TxEnv;
+use revm::context_interface::result::{ExecutionResult, Output};
+use revm::database::{CacheDB, EmptyDB};
+use revm::handler::MainnetContext;
+use revm::primitives::TxKind;
+use revm::primitives::hardfork::SpecId;
+use revm::state::AccountInfo;
+use revm::{Context, ExecuteCommitEvm, ExecuteEvm, MainBuilder, MainContext, MainnetEvm};
+
+/// Sends every transaction.
+pub(crate) const SENDER: Address = address!("0x5e5de50000000000000000000000000000000000");
+
+/// The chain id of every block.
+pub(crate) const CHAIN_ID: u64 = 1;
+
+const GAS_LIMIT: u64 = 30_000_000;
+
+pub(crate) struct Evm {
+    evm: MainnetEvm<MainnetContext<CacheDB<EmptyDB>>>,
+}
+
+impl Default for Evm {
+    fn default() -> Self {
+        let mut db = CacheDB::<EmptyDB>::default();
+        db.insert_account_info(
+            SENDER,
+            AccountInfo {
+                balance: U256::MAX,
+                ..Default::default()
+            },
+        );
+
+        let evm = Context::mainnet()
+            .with_db(db)
+            .modify_cfg_chained(|cfg| {
+                cfg.chain_id = CHAIN_ID;
+                cfg.spec = SpecId::CANCUN;
+                cfg.disable_nonce_check = true;
+            })
+            .build_mainnet();
+
+        Self { evm }
+    }
+}
+
+impl Evm {
+    /// Deploys `initcode`, returning the address of the new contract.
+    pub(crate) fn deploy(&mut self, initcode: &Bytes) -> Address {
+        match self.transact(TxKind::Create, initcode.clone(), true) {
+            ExecutionResult::Success {
+                output: Output::Create(_, Some(address)),
+                ..
+            } => address,
+            other => panic!("deployment failed: {other:?}"),
+        }
+    }
+
+    /// The runtime code at `address`.
+    pub(crate) fn code(&self, address: Address) -> Bytes {
+        self.evm.ctx.journaled_state.database.cache.accounts[&address]
+            .info
+            .code
+            .as_ref()
+            .map(|code| code.original_bytes())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn set_timestamp(&mut self, timestamp: u64) {
+        self.evm.ctx.block.timestamp = U256::from(timestamp);
+    }
+
+    /// Sends `data` to `to`, keeping any changes, and returns the output or the revert data.
+    pub(crate) fn call(&mut self, to: Address, data: Bytes) -> Result<Bytes, Bytes> {
+        match self.transact(TxKind::Call(to), data, true) {
+            ExecutionResult::Success { output, .. } => Ok(output.into_data()),
+            ExecutionResult::Revert { output, .. } => Err(output),
+            ExecutionResult::Halt { reason, .. } => panic!("call halted: {reason:?}"),
+        }
+    }
+
+    /// Calls `call` on `to` and decodes its return value.
+    pub(crate) fn call_sol<C: SolCall>(
+        &mut self,
+        to: Address,
+        call: C,
+    ) -> Result<C::Return, Bytes> {
+        let output = self.call(to, call.abi_encode().into())?;
+        Ok(C::abi_decode_returns(&output).expect("undecodable return value"))
+    }
+
+    /// The gas a successful call of `data` on `to` uses, without keeping its changes.
+    pub(crate) fn gas(&mut self, to: Address, data: Bytes) -> u64 {
+        match self.transact(TxKind::Call(to), data, false) {
+            ExecutionResult::Success { gas_used, .. } => gas_used,
+            other => panic!("call failed: {other:?}"),
+        }
+    }
+
+    /// Executes `planner` on the [`TestableVM`] at `vm` as
+    /// [`simulate_plan`](crate::WeirollProviderExt::simulate_plan) does, but keeping the changes.
+    pub(crate) fn execute(
+        &mut self,
+        vm: Address,
+        planner: &Planner<'_>,
+    ) -> Result<PlanSimulation, ProviderError> {
+        let (prepared, returns) = PreparedPlan::new(planner, true)?;
+        match self.call(vm, prepared.calldata.clone()) {
+            Ok(output) => Ok(PlanSimulation {
+                state: TestableVM::executeCall::abi_decode_returns(&output)?,
+                returns,
+            }),
+            Err(revert) => Err(prepared.execution_failed(
+                TestableVM::ExecutionFailed::abi_decode(&revert)
+                    .expect("revert is not ExecutionFailed"),
+            )),
+        }
+    }
+
+    fn transact(&mut self, kind: TxKind, data: Bytes, commit: bool) -> ExecutionResult {
+        let tx = TxEnv::builder()
+            .caller(SENDER)
+            .kind(kind)
+            .data(data)
+            .gas_limit(GAS_LIMIT)
+            .build()
+            .expect("invalid transaction");
+
+        if commit {
+            self.evm.transact_commit(tx)
+        } else {
+            self.evm.transact(tx).map(|result| result.result)
+        }
+        .expect("transaction was not executed")
+    }
+}
+
+/// Deploys the [`TestableVM`], returning its address.
+pub(crate) fn testable_vm(evm: &mut Evm) -> Address {
+    evm.deploy(&TestableVM::BYTECODE)
+}
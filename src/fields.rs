@@ -0,0 +1,133 @@
+//! Support for struct-literal calls whose fields mix literals and [`ReturnValue`]s.
+//!
+//! [`call_contract!`](crate::call_contract) builds the call struct once with every
+//! [`ReturnValue`] field set to its default, then once more per [`ReturnValue`] with that field
+//! set to a [`Probe`] value. Comparing the encodings tells the planner which parameter each
+//! [`ReturnValue`] belongs to, without needing to know the field order up front.
+
+use crate::cmds::ReturnValue;
+
+use alloy::primitives::{Address, Bytes, FixedBytes, Signed, Uint};
+use alloy::sol_types::SolCall;
+
+/// A call struct along with one probe struct per [`ReturnValue`] field.
+#[derive(Debug)]
+pub struct CallFields<C: SolCall> {
+    pub(crate) base: C,
+    pub(crate) returns: Vec<(ReturnValue, C)>,
+}
+
+impl<C: SolCall> CallFields<C> {
+    pub fn new(base: C, returns: Vec<(ReturnValue, C)>) -> Self {
+        Self { base, returns }
+    }
+}
+
+/// A non-default value of a Solidity type, used to locate [`ReturnValue`] fields.
+pub trait Probe: Default {
+    fn probe() -> Self;
+}
+
+/// Converts a struct-literal field into the field's type.
+///
+/// Literals are used as-is. [`ReturnValue`]s are recorded in `returns` and replaced by the
+/// default value, or by a [`Probe`] when `probe` names this field.
+pub trait ToField<T> {
+    fn to_field(
+        &self,
+        name: &'static str,
+        probe: Option<&str>,
+        returns: &mut Vec<(&'static str, ReturnValue)>,
+    ) -> T;
+}
+
+impl<T: Clone> ToField<T> for T {
+    fn to_field(
+        &self,
+        _name: &'static str,
+        _probe: Option<&str>,
+        _returns: &mut Vec<(&'static str, ReturnValue)>,
+    ) -> T {
+        self.clone()
+    }
+}
+
+impl<T: Probe> ToField<T> for ReturnValue {
+    fn to_field(
+        &self,
+        name: &'static str,
+        probe: Option<&str>,
+        returns: &mut Vec<(&'static str, ReturnValue)>,
+    ) -> T {
+        match probe {
+            Some(field) if field == name => T::probe(),
+            Some(_) => T::default(),
+            None => {
+                returns.push((name, self.clone()));
+                T::default()
+            }
+        }
+    }
+}
+
+macro_rules! impl_probe_int {
+    ($($t:ty),*) => {
+        $(
+            impl Probe for $t {
+                fn probe() -> Self {
+                    1
+                }
+            }
+        )*
+    };
+}
+
+impl_probe_int!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+impl<const BITS: usize, const LIMBS: usize> Probe for Uint<BITS, LIMBS> {
+    fn probe() -> Self {
+        Self::ONE
+    }
+}
+
+impl<const BITS: usize, const LIMBS: usize> Probe for Signed<BITS, LIMBS> {
+    fn probe() -> Self {
+        Self::ONE
+    }
+}
+
+impl<const N: usize> Probe for FixedBytes<N> {
+    fn probe() -> Self {
+        Self::repeat_byte(1)
+    }
+}
+
+impl Probe for Address {
+    fn probe() -> Self {
+        Self::with_last_byte(1)
+    }
+}
+
+impl Probe for bool {
+    fn probe() -> Self {
+        true
+    }
+}
+
+impl Probe for Bytes {
+    fn probe() -> Self {
+        Self::from_static(&[1])
+    }
+}
+
+impl Probe for String {
+    fn probe() -> Self {
+        String::from("1")
+    }
+}
+
+impl<T: Default> Probe for Vec<T> {
+    fn probe() -> Self {
+        vec![T::default()]
+    }
+}
@@ -0,0 +1,13 @@
+//! Binding for `contracts/Assertions.sol`.
+
+alloy::sol! {
+    /// Reverts with `message` when a condition does not hold.
+    #[allow(missing_docs)]
+    #[sol(rpc, bytecode = "0x61007f80600c6000396000f360003560e01c8063a34edc031461002b57806388b44c851461003757806323b54a171461004557600080fd5b60043515156024610054565b602435600435146044610054565b60243560043510156044610054565b901561005c57005b3560040180360380826024376308c379a060e01b60005260206004526024016000fd", deployed_bytecode = "0x60003560e01c8063a34edc031461002b57806388b44c851461003757806323b54a171461004557600080fd5b60043515156024610054565b602435600435146044610054565b60243560043510156044610054565b901561005c57005b3560040180360380826024376308c379a060e01b60005260206004526024016000fd")]
+    #[derive(Debug, PartialEq, Eq)]
+    contract Assertions {
+        function assertTrue(bool condition, string calldata message) external pure;
+        function assertEq(uint256 a, uint256 b, string calldata message) external pure;
+        function assertGte(uint256 a, uint256 b, string calldata message) external pure;
+    }
+}
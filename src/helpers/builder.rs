@@ -0,0 +1,16 @@
+//! Binding for `contracts/Builder.sol`.
+
+alloy::sol! {
+    /// Re-encodes the arguments of a call as a single array or tuple.
+    ///
+    /// Each function takes its elements as trailing arguments, which Solidity cannot declare,
+    /// and returns the encoded value rather than `bytes`.
+    #[allow(missing_docs)]
+    #[sol(rpc, bytecode = "0x61007380600c6000396000f360003560e01c8063fb4775861461002b57806395d4ad111461002b578063552736601461002b57600080fd5b602060005260043603806004602037906355273660141561006c5760205160051b60400160405b818110156100695760208151038152602001610052565b50505b6020016000f3", deployed_bytecode = "0x60003560e01c8063fb4775861461002b57806395d4ad111461002b578063552736601461002b57600080fd5b602060005260043603806004602037906355273660141561006c5760205160051b60400160405b818110156100695760208151038152602001610052565b50505b6020016000f3")]
+    #[derive(Debug, PartialEq, Eq)]
+    contract Builder {
+        function buildTuple() external pure returns (bytes memory);
+        function buildArray(uint256 length) external pure returns (bytes memory);
+        function buildDynamicArray(uint256 length) external pure returns (bytes memory);
+    }
+}
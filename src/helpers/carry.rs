@@ -0,0 +1,16 @@
+//! Binding for `contracts/Carry.sol`.
+
+alloy::sol! {
+    /// Carries values between the transactions of a split plan.
+    ///
+    /// Must be CALLed by the VM. `load` returns a value encoded as the command which produced
+    /// it returned it, so it has no fixed return type.
+    #[allow(missing_docs)]
+    #[sol(rpc, bytecode = "0x6100c480600c6000396000f360003560e01c8063eccf17a21461003d578063cdb4c1771461005357806399d548aa1461009657600080fd5b33600052600435602052604060002090565b61004561002b565b602081556024359060010155005b61005b61002b565b602435600401803603908190602037602060005260200180825560005b81811015610094578051838260051c0160010155602001610078565b005b61009e61002b565b805460005b818110156100bf57828160051c016001015481526020016100a3565b506000f3", deployed_bytecode = "0x60003560e01c8063eccf17a21461003d578063cdb4c1771461005357806399d548aa1461009657600080fd5b33600052600435602052604060002090565b61004561002b565b602081556024359060010155005b61005b61002b565b602435600401803603908190602037602060005260200180825560005b81811015610094578051838260051c0160010155602001610078565b005b61009e61002b565b805460005b818110156100bf57828160051c016001015481526020016100a3565b506000f3")]
+    #[derive(Debug, PartialEq, Eq)]
+    contract Carry {
+        function store(uint256 index, bytes32 value) external;
+        function storeDynamic(uint256 index, bytes value) external;
+        function load(uint256 index) external view;
+    }
+}
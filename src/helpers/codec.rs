@@ -0,0 +1,15 @@
+//! Binding for `contracts/Codec.sol`.
+
+alloy::sol! {
+    /// Encodes any number of values into a single `bytes` value.
+    ///
+    /// Each function takes its values as trailing arguments, which Solidity cannot declare.
+    #[allow(missing_docs)]
+    #[sol(rpc, bytecode = "0x61010580600c6000396000f360003560e01c8063bd14598f1461002b5780636bdbf8e614610045578063f178d5ac1461008057600080fd5b602060005260043603806020528060046040376040016000f35b604060043560051c60051b60040160045b818110156100795780356004018035906020018190853783019250602001610056565b50506100ea565b604060043560040160005b81358110156100e7578082016020013560001a8160051b6024013581156100cb57816080166100bd578160200360031b1b5b8452607f16830192506100df565b600401803590602001819086379050830192505b60010161008b565b50505b6000815260408103602052601f01601f191660206000526000f3", deployed_bytecode = "0x60003560e01c8063bd14598f1461002b5780636bdbf8e614610045578063f178d5ac1461008057600080fd5b602060005260043603806020528060046040376040016000f35b604060043560051c60051b60040160045b818110156100795780356004018035906020018190853783019250602001610056565b50506100ea565b604060043560040160005b81358110156100e7578082016020013560001a8160051b6024013581156100cb57816080166100bd578160200360031b1b5b8452607f16830192506100df565b600401803590602001819086379050830192505b60010161008b565b50505b6000815260408103602052601f01601f191660206000526000f3")]
+    #[derive(Debug, PartialEq, Eq)]
+    contract Codec {
+        function encode() external pure returns (bytes memory);
+        function encodePacked(bytes memory layout) external pure returns (bytes memory);
+        function concat() external pure returns (bytes memory);
+    }
+}
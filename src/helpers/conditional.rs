@@ -0,0 +1,14 @@
+//! Binding for `contracts/Conditional.sol`.
+
+alloy::sol! {
+    /// Selects whether a subplan runs.
+    ///
+    /// Both functions must be DELEGATECALLed by the VM.
+    #[allow(missing_docs)]
+    #[sol(rpc, bytecode = "0x61009580600c6000396000f360003560e01c80638fac009314610020578063b5b6e16f1461002757600080fd5b600161002a565b60005b6004351515141561007b5763de792d5f6000526024360380602460203760208051036020526020604051036040526000600082600401601c6000305af13d600060003e610076573d6000fd5b3d6000f35b604435600401803603808260203760206000526020016000f3", deployed_bytecode = "0x60003560e01c80638fac009314610020578063b5b6e16f1461002757600080fd5b600161002a565b60005b6004351515141561007b5763de792d5f6000526024360380602460203760208051036020526020604051036040526000600082600401601c6000305af13d600060003e610076573d6000fd5b3d6000f35b604435600401803603808260203760206000526020016000f3")]
+    #[derive(Debug, PartialEq, Eq)]
+    contract Conditional {
+        function executeIf(bool condition, bytes32[] commands, bytes[] state) external returns (bytes[] memory);
+        function executeUnless(bool condition, bytes32[] commands, bytes[] state) external returns (bytes[] memory);
+    }
+}
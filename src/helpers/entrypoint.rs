@@ -0,0 +1,15 @@
+//! Binding for `contracts/PlanEntrypoint.sol`.
+
+alloy::sol! {
+    /// Executes plans signed as EIP-712 [`SignedPlan`](crate::signing::SignedPlan)s.
+    ///
+    /// Plans are forwarded to `vm.execute` with the signer's address appended to the calldata.
+    #[allow(missing_docs)]
+    #[sol(rpc, bytecode = "0x6102cc80600c6000396000f360003560e01c8063dd770d81146100e95780637ecebe001461002c5780633644e5151461005c575b600080fd5b60043573ffffffffffffffffffffffffffffffffffffffff16600052600060205260406000205460005260206000f35b61006461006d565b60005260206000f35b7f8b73c3c69bb8fe3d512ecc4cf759cc79239f7b179b0ffacaa9a75d522b39400f6000527ffe31a9b7c467b3a28aa754b043afd2af38febd40c986bc1e4b3155fc7a367e666020527fc89efdaa54c0f20c7adf612882df0950f5a951637e0307cdcb4c672f298b8bc6604052466060523060805260a060002090565b426084351061002757602435600401803560051b809160200161030037610300206102005260443560040180359060200160005b82811015610153578060051b820135820180358460051b61030001818360200182372090508160051b610300015260010161011d565b505060051b61030020610220527f1e32d949ae8f18d1066d0dd1cc2b3dbc29ea76f93d4170d21d218231368ca9fa600052610200516020526102205160405260606000206040527f44c574e7f1a575b42d254887a1ae316f18ae10a7434de6d743b61f7ac921eef360005260043560205260643560605260843560805260a0600020610240526101e161006d565b61190160f01b60005260025261024051602252604260002060a4356004018035604114156100275790600052806060013560001a602052806020013560405260400135807f7fffffffffffffffffffffffffffffff5d576e7357a4501ddfe92f46681b20a01061002757606052602060006080600060015afa503d15610027576000518060005260006020526040600020805480606435141561002757600101905563de792d5f60e01b60005260043603600480376024356004526044356024528060601b365260006000601436016000346004355af13d600060003e6102c7573d6000fd5b3d6000f3", deployed_bytecode = "0x60003560e01c8063dd770d81146100e95780637ecebe001461002c5780633644e5151461005c575b600080fd5b60043573ffffffffffffffffffffffffffffffffffffffff16600052600060205260406000205460005260206000f35b61006461006d565b60005260206000f35b7f8b73c3c69bb8fe3d512ecc4cf759cc79239f7b179b0ffacaa9a75d522b39400f6000527ffe31a9b7c467b3a28aa754b043afd2af38febd40c986bc1e4b3155fc7a367e666020527fc89efdaa54c0f20c7adf612882df0950f5a951637e0307cdcb4c672f298b8bc6604052466060523060805260a060002090565b426084351061002757602435600401803560051b809160200161030037610300206102005260443560040180359060200160005b82811015610153578060051b820135820180358460051b61030001818360200182372090508160051b610300015260010161011d565b505060051b61030020610220527f1e32d949ae8f18d1066d0dd1cc2b3dbc29ea76f93d4170d21d218231368ca9fa600052610200516020526102205160405260606000206040527f44c574e7f1a575b42d254887a1ae316f18ae10a7434de6d743b61f7ac921eef360005260043560205260643560605260843560805260a0600020610240526101e161006d565b61190160f01b60005260025261024051602252604260002060a4356004018035604114156100275790600052806060013560001a602052806020013560405260400135807f7fffffffffffffffffffffffffffffff5d576e7357a4501ddfe92f46681b20a01061002757606052602060006080600060015afa503d15610027576000518060005260006020526040600020805480606435141561002757600101905563de792d5f60e01b60005260043603600480376024356004526044356024528060601b365260006000601436016000346004355af13d600060003e6102c7573d6000fd5b3d6000f3")]
+    #[derive(Debug, PartialEq, Eq)]
+    contract PlanEntrypoint {
+        function executeSigned(address vm, bytes32[] commands, bytes[] state, uint256 nonce, uint256 deadline, bytes signature) external payable returns (bytes[] memory);
+        function nonces(address signer) external view returns (uint256);
+        function DOMAIN_SEPARATOR() external view returns (bytes32);
+    }
+}
@@ -0,0 +1,14 @@
+//! Binding for `contracts/Loop.sol`.
+
+alloy::sol! {
+    /// Runs a subplan once per element of an array.
+    ///
+    /// Both functions must be DELEGATECALLed by the VM.
+    #[allow(missing_docs)]
+    #[sol(rpc, bytecode = "0x61014380600c6000396000f360003560e01c80639fe62d4e1461004b5780632884fd001461002057600080fd5b7f2d529f7d98aea30400ee3328d8ad8d1841da39d942c20567cb91c4148c805fc25c60005260206000f35b600435600401803580602435106101275760205260200160405263de792d5f61010052604061012052604435600401803560051b6020018060400161014052809161016037610160018060605260643560040180360380608052908237505b602051600051101561012c5760005160051b60405101357f2d529f7d98aea30400ee3328d8ad8d1841da39d942c20567cb91c4148c805fc25d600060006080516060510161011c900361011c6000305af11561011c5760203d038060805260206060513e6000516001016000526100aa565b3d600060003e3d6000fd5b600080fd5b602060206060510352608051602001602060605103f3", deployed_bytecode = "0x60003560e01c80639fe62d4e1461004b5780632884fd001461002057600080fd5b7f2d529f7d98aea30400ee3328d8ad8d1841da39d942c20567cb91c4148c805fc25c60005260206000f35b600435600401803580602435106101275760205260200160405263de792d5f61010052604061012052604435600401803560051b6020018060400161014052809161016037610160018060605260643560040180360380608052908237505b602051600051101561012c5760005160051b60405101357f2d529f7d98aea30400ee3328d8ad8d1841da39d942c20567cb91c4148c805fc25d600060006080516060510161011c900361011c6000305af11561011c5760203d038060805260206060513e6000516001016000526100aa565b3d600060003e3d6000fd5b600080fd5b602060206060510352608051602001602060605103f3")]
+    #[derive(Debug, PartialEq, Eq)]
+    contract Loop {
+        function forEach(bytes32[] items, uint256 maxIterations, bytes32[] commands, bytes[] state) external returns (bytes[] memory);
+        function element() external view returns (bytes32);
+    }
+}
@@ -0,0 +1,438 @@
+//! Bindings for the helper contracts bundled with this crate.
+//!
+//! Unlike [`bindings`](crate::bindings), which are generated from the upstream weiroll
+//! contracts, these contracts live in `contracts/` of this repository. `make helpers` compiles
+//! them with forge and writes their bytecode into these bindings; `make check-helpers`, run in
+//! CI, fails if the bindings differ from what forge compiles.
+
+pub mod assertions;
+pub mod builder;
+pub mod carry;
+pub mod codec;
+pub mod conditional;
+pub mod entrypoint;
+pub mod looping;
+pub mod try_catch;
+
+#[cfg(test)]
+mod tests {
+    use super::assertions::Assertions;
+    use super::builder::Builder;
+    use super::carry::Carry;
+    use super::codec::Codec;
+    use super::conditional::Conditional;
+    use super::entrypoint::PlanEntrypoint;
+    use super::looping::Loop;
+    use super::try_catch::TryCatch;
+    use crate::bindings::{math::Math, revert::Revert, strings::Strings};
+    use crate::error::ProviderError;
+    use crate::evm::{CHAIN_ID, Evm, testable_vm};
+    use crate::signing::SignedPlan;
+    use crate::split::Budget;
+    use crate::{Planner, Value};
+    use alloy::dyn_abi::DynSolValue;
+    use alloy::primitives::{Bytes, Signature, U256, address, uint};
+    use alloy::signers::SignerSync;
+    use alloy::signers::local::PrivateKeySigner;
+
+    #[test]
+    fn bytecode_deploys_runtime_code() {
+        let helpers: [(&Bytes, &Bytes); 8] = [
+            (&Assertions::BYTECODE, &Assertions::DEPLOYED_BYTECODE),
+            (&Builder::BYTECODE, &Builder::DEPLOYED_BYTECODE),
+            (&Carry::BYTECODE, &Carry::DEPLOYED_BYTECODE),
+            (&Codec::BYTECODE, &Codec::DEPLOYED_BYTECODE),
+            (&Conditional::BYTECODE, &Conditional::DEPLOYED_BYTECODE),
+            (
+                &PlanEntrypoint::BYTECODE,
+                &PlanEntrypoint::DEPLOYED_BYTECODE,
+            ),
+            (&Loop::BYTECODE, &Loop::DEPLOYED_BYTECODE),
+            (&TryCatch::BYTECODE, &TryCatch::DEPLOYED_BYTECODE),
+        ];
+
+        let mut evm = Evm::default();
+        for (bytecode, deployed) in helpers {
+            let address = evm.deploy(bytecode);
+            assert_eq!(&evm.code(address), deployed);
+        }
+    }
+
+    #[test]
+    fn failed_assertion_reverts_with_its_message() {
+        let mut evm = Evm::default();
+        let vm = testable_vm(&mut evm);
+        let math = evm.deploy(&Math::BYTECODE);
+        let assertions = evm.deploy(&Assertions::BYTECODE);
+
+        let plan = |min: u64| {
+            let mut planner = Planner::default();
+            let sum = planner
+                .math(math)
+                .add(U256::from(1), U256::from(2))
+                .unwrap();
+            planner
+                .assertions(assertions)
+                .assert_gte(sum.clone(), U256::from(min), String::from("too little"))
+                .unwrap();
+            (planner, sum)
+        };
+
+        let (planner, sum) = plan(3);
+        let simulation = evm.execute(vm, &planner).unwrap();
+        assert_eq!(
+            simulation.get(&sum).unwrap(),
+            DynSolValue::Uint(U256::from(3), 256)
+        );
+
+        let (planner, _) = plan(4);
+        let err = evm.execute(vm, &planner).unwrap_err();
+        assert!(matches!(
+            err,
+            ProviderError::ExecutionFailed {
+                command: Some(1),
+                target,
+                message,
+                ..
+            } if target == assertions && message == "too little"
+        ));
+    }
+
+    #[test]
+    fn builder_assembles_arrays_of_return_values() {
+        let mut evm = Evm::default();
+        let vm = testable_vm(&mut evm);
+        let math = evm.deploy(&Math::BYTECODE);
+
+        let mut planner = Planner::default();
+        planner.set_builder(evm.deploy(&Builder::BYTECODE));
+        let a = planner
+            .math(math)
+            .add(U256::from(1), U256::from(2))
+            .unwrap();
+        let total = planner
+            .math(math)
+            .sum(Value::array([a.into(), U256::from(4).into()]))
+            .unwrap();
+
+        let simulation = evm.execute(vm, &planner).unwrap();
+        assert_eq!(
+            simulation.get(&total).unwrap(),
+            DynSolValue::Uint(U256::from(7), 256)
+        );
+    }
+
+    #[test]
+    fn codec_encodes_return_values() {
+        let mut evm = Evm::default();
+        let vm = testable_vm(&mut evm);
+        let math = evm.deploy(&Math::BYTECODE);
+        let strings = evm.deploy(&Strings::BYTECODE);
+        let codec = evm.deploy(&Codec::BYTECODE);
+        let recipient = address!("0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee");
+
+        let mut planner = Planner::default();
+        let sum = planner
+            .math(math)
+            .add(U256::from(1), U256::from(2))
+            .unwrap();
+        let joined = planner
+            .strings(strings)
+            .strcat(String::from("ab"), String::from("cd"))
+            .unwrap();
+        let values = || vec![sum.clone().into(), joined.clone().into(), recipient.into()];
+        let encoded = planner.codec(codec).encode(values()).unwrap();
+        let packed = planner.codec(codec).encode_packed(values()).unwrap();
+        let concatenated = planner
+            .codec(codec)
+            .concat(vec![
+                joined.clone().into(),
+                DynSolValue::Bytes(b"ef".to_vec()).into(),
+            ])
+            .unwrap();
+
+        let simulation = evm.execute(vm, &planner).unwrap();
+        let expected = DynSolValue::Tuple(vec![
+            DynSolValue::Uint(U256::from(3), 256),
+            DynSolValue::String(String::from("abcd")),
+            DynSolValue::Address(recipient),
+        ]);
+        assert_eq!(
+            simulation.get(&encoded).unwrap(),
+            DynSolValue::Bytes(expected.abi_encode_params())
+        );
+        assert_eq!(
+            simulation.get(&packed).unwrap(),
+            DynSolValue::Bytes(expected.abi_encode_packed())
+        );
+        assert_eq!(
+            simulation.get(&concatenated).unwrap(),
+            DynSolValue::Bytes(b"abcdef".to_vec())
+        );
+    }
+
+    #[test]
+    fn try_catch_reports_whether_the_body_succeeded() {
+        let mut evm = Evm::default();
+        let vm = testable_vm(&mut evm);
+        let math = evm.deploy(&Math::BYTECODE);
+        let revert = evm.deploy(&Revert::BYTECODE);
+
+        let mut planner = Planner::default();
+        planner.set_try_catch(evm.deploy(&TryCatch::BYTECODE));
+        let added = planner
+            .try_call(|p| p.math(math).add(U256::from(1), U256::from(2)))
+            .unwrap();
+        let total = planner
+            .math(math)
+            .add(added.result.unwrap(), U256::from(10))
+            .unwrap();
+        let failed = planner
+            .try_call(|p| p.call_address::<Revert::failCall>(revert, vec![]))
+            .unwrap();
+
+        let simulation = evm.execute(vm, &planner).unwrap();
+        assert_eq!(
+            simulation.get(&added.success).unwrap(),
+            DynSolValue::Bool(true)
+        );
+        assert_eq!(
+            simulation.get(&total).unwrap(),
+            DynSolValue::Uint(U256::from(13), 256)
+        );
+        assert_eq!(
+            simulation.get(&failed.success).unwrap(),
+            DynSolValue::Bool(false)
+        );
+    }
+
+    #[test]
+    fn conditional_runs_one_branch() {
+        let mut evm = Evm::default();
+        let vm = testable_vm(&mut evm);
+        let math = evm.deploy(&Math::BYTECODE);
+        let revert = evm.deploy(&Revert::BYTECODE);
+        let try_catch = evm.deploy(&TryCatch::BYTECODE);
+        let conditional = evm.deploy(&Conditional::BYTECODE);
+
+        for (succeeds, expected) in [(true, 33), (false, 300)] {
+            let mut planner = Planner::default();
+            planner.set_try_catch(try_catch);
+            planner.set_conditional(conditional);
+            let mut then = planner.branch();
+            let mut otherwise = planner.branch();
+            let x = planner
+                .math(math)
+                .add(U256::from(1), U256::from(2))
+                .unwrap();
+            let condition = planner
+                .try_call(|p| match succeeds {
+                    true => p.math(math).add(U256::ZERO, U256::ZERO),
+                    false => p.call_address::<Revert::failCall>(revert, vec![]),
+                })
+                .unwrap()
+                .success;
+
+            let own = then.math(math).add(U256::from(10), U256::from(20)).unwrap();
+            let sum = then.math(math).add(own, x.clone()).unwrap();
+            then.set_label(&sum, "out").unwrap();
+            let product = otherwise.math(math).mul(x, U256::from(100)).unwrap();
+            otherwise.set_label(&product, "out").unwrap();
+
+            let outputs = planner.if_then_else(condition, then, otherwise).unwrap();
+            let out = planner
+                .math(math)
+                .add(outputs["out"].clone(), U256::ZERO)
+                .unwrap();
+            let simulation = evm.execute(vm, &planner).unwrap();
+            assert_eq!(
+                simulation.get(&out).unwrap(),
+                DynSolValue::Uint(U256::from(expected), 256)
+            );
+        }
+    }
+
+    #[test]
+    fn loop_iterates_up_to_the_maximum() {
+        let mut evm = Evm::default();
+        let vm = testable_vm(&mut evm);
+        let math = evm.deploy(&Math::BYTECODE);
+        let builder = evm.deploy(&Builder::BYTECODE);
+        let looping = evm.deploy(&Loop::BYTECODE);
+
+        let plan = |max: usize| {
+            let mut planner = Planner::default();
+            planner.set_builder(builder);
+            planner.set_loop(looping);
+            planner.set_max_iterations(max);
+            let a = planner
+                .math(math)
+                .add(U256::from(1), U256::from(2))
+                .unwrap();
+            let array = Value::array([a.into(), U256::from(4).into(), U256::from(5).into()]);
+            let items = match &planner.expand_args(vec![array]).unwrap()[..] {
+                [Value::Return(items)] => items.clone(),
+                _ => panic!("array was not built"),
+            };
+            let zero = planner.math(math).add(U256::ZERO, U256::ZERO).unwrap();
+            let total = planner
+                .fold(items, zero, |p, element, total| {
+                    p.math(math).add(total, element)
+                })
+                .unwrap();
+            (planner, total)
+        };
+
+        let (planner, total) = plan(3);
+        let simulation = evm.execute(vm, &planner).unwrap();
+        assert_eq!(
+            simulation.get(&total).unwrap(),
+            DynSolValue::Uint(U256::from(12), 256)
+        );
+
+        let (planner, _) = plan(2);
+        let err = evm.execute(vm, &planner).unwrap_err();
+        assert!(matches!(
+            err,
+            ProviderError::ExecutionFailed {
+                command: Some(3),
+                target,
+                ..
+            } if target == looping
+        ));
+    }
+
+    #[test]
+    fn entrypoint_executes_each_signed_plan_once() {
+        let mut evm = Evm::default();
+        let vm = testable_vm(&mut evm);
+        let math = evm.deploy(&Math::BYTECODE);
+        let entrypoint = evm.deploy(&PlanEntrypoint::BYTECODE);
+        let signer = PrivateKeySigner::random();
+        let sign = |signed: &SignedPlan| {
+            signer
+                .sign_hash_sync(&signed.signing_hash(entrypoint, CHAIN_ID))
+                .unwrap()
+        };
+
+        let separator = evm
+            .call_sol(entrypoint, PlanEntrypoint::DOMAIN_SEPARATORCall {})
+            .unwrap();
+        assert_eq!(
+            separator,
+            SignedPlan::domain(entrypoint, CHAIN_ID).separator()
+        );
+
+        let mut planner = Planner::default();
+        let sum = planner
+            .math(math)
+            .add(U256::from(1), U256::from(2))
+            .unwrap();
+        planner.math(math).mul(sum, U256::from(7)).unwrap();
+
+        let signed = SignedPlan::new(vm, &planner, U256::from(1), U256::MAX).unwrap();
+        assert!(
+            evm.call(entrypoint, signed.execute_calldata(&sign(&signed)))
+                .is_err()
+        );
+
+        let signed = SignedPlan::new(vm, &planner, U256::ZERO, U256::MAX).unwrap();
+        let signature = sign(&signed);
+        let state = evm
+            .call(entrypoint, signed.execute_calldata(&signature))
+            .unwrap();
+        // The relayed plan returns the state running it directly does
+        let direct = evm.call(vm, planner.execute_calldata().unwrap()).unwrap();
+        assert_eq!(state, direct);
+        let nonce = PlanEntrypoint::noncesCall {
+            signer: signer.address(),
+        };
+        assert_eq!(
+            evm.call_sol(entrypoint, nonce.clone()).unwrap(),
+            U256::from(1)
+        );
+        assert!(
+            evm.call(entrypoint, signed.execute_calldata(&signature))
+                .is_err()
+        );
+
+        let signed = SignedPlan::new(vm, &planner, U256::from(1), U256::from(1000)).unwrap();
+        let signature = sign(&signed);
+        evm.set_timestamp(1001);
+        assert!(
+            evm.call(entrypoint, signed.execute_calldata(&signature))
+                .is_err()
+        );
+        evm.set_timestamp(1000);
+        assert!(
+            evm.call(entrypoint, signed.execute_calldata(&signature))
+                .is_ok()
+        );
+        assert_eq!(evm.call_sol(entrypoint, nonce).unwrap(), U256::from(2));
+    }
+
+    #[test]
+    fn entrypoint_rejects_malleable_signatures() {
+        let mut evm = Evm::default();
+        let vm = testable_vm(&mut evm);
+        let entrypoint = evm.deploy(&PlanEntrypoint::BYTECODE);
+        let signer = PrivateKeySigner::random();
+
+        let signed = SignedPlan::new(vm, &Planner::default(), U256::ZERO, U256::MAX).unwrap();
+        let signature = signer
+            .sign_hash_sync(&signed.signing_hash(entrypoint, CHAIN_ID))
+            .unwrap();
+        // The same signature with `s` mirrored into the upper half of the curve order
+        let order = uint!(0xfffffffffffffffffffffffffffffffebaaedce6af48a03bbfd25e8cd0364141_U256);
+        let high_s = Signature::new(signature.r(), order - signature.s(), !signature.v());
+
+        assert!(
+            evm.call(entrypoint, signed.execute_calldata(&high_s))
+                .is_err()
+        );
+        assert!(
+            evm.call(entrypoint, signed.execute_calldata(&signature))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn carry_hands_values_to_later_parts() {
+        let mut evm = Evm::default();
+        let vm = testable_vm(&mut evm);
+        let math = evm.deploy(&Math::BYTECODE);
+        let strings = evm.deploy(&Strings::BYTECODE);
+
+        let mut planner = Planner::default();
+        planner.set_carry(evm.deploy(&Carry::BYTECODE));
+        let sum = planner
+            .math(math)
+            .add(U256::from(1), U256::from(2))
+            .unwrap();
+        let joined = planner
+            .strings(strings)
+            .strcat(String::from("ab"), String::from("cd"))
+            .unwrap();
+        planner.math(math).add(sum.clone(), U256::from(3)).unwrap();
+        let length = planner.strings(strings).strlen(joined).unwrap();
+        let total = planner.math(math).add(sum, U256::from(4)).unwrap();
+
+        let budget = Budget {
+            commands: Some(4),
+            ..Default::default()
+        };
+        let parts = planner.split(budget).unwrap();
+        assert_eq!(parts.len(), 3);
+
+        let mut simulations = parts.iter().map(|part| evm.execute(vm, part).unwrap());
+        simulations.next();
+        assert_eq!(
+            simulations.next().unwrap().get(&length).unwrap(),
+            DynSolValue::Uint(U256::from(4), 256)
+        );
+        assert_eq!(
+            simulations.next().unwrap().get(&total).unwrap(),
+            DynSolValue::Uint(U256::from(7), 256)
+        );
+    }
+}
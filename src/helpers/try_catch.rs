@@ -0,0 +1,15 @@
+//! Binding for `contracts/TryCatch.sol`.
+
+alloy::sol! {
+    /// Runs a subplan which is allowed to revert.
+    ///
+    /// Both functions must be DELEGATECALLed by the VM, and `succeeded` planned directly after
+    /// `tryExecute`.
+    #[allow(missing_docs)]
+    #[sol(rpc, bytecode = "0x6100b480600c6000396000f360003560e01c8063848350c71461004b578063934152501461002057600080fd5b7f0b1b108ab2ef536084eda67e5860cccb12883b63e468186ae9ee36d8d77c39485c60005260206000f35b63de792d5f6000526004360360046020376000600036601c6000305af1807f0b1b108ab2ef536084eda67e5860cccb12883b63e468186ae9ee36d8d77c39485d6100a957602435600401803603808260203760206000526020016000f35b3d600060003e3d6000f3", deployed_bytecode = "0x60003560e01c8063848350c71461004b578063934152501461002057600080fd5b7f0b1b108ab2ef536084eda67e5860cccb12883b63e468186ae9ee36d8d77c39485c60005260206000f35b63de792d5f6000526004360360046020376000600036601c6000305af1807f0b1b108ab2ef536084eda67e5860cccb12883b63e468186ae9ee36d8d77c39485d6100a957602435600401803603808260203760206000526020016000f35b3d600060003e3d6000f3")]
+    #[derive(Debug, PartialEq, Eq)]
+    contract TryCatch {
+        function tryExecute(bytes32[] commands, bytes[] state) external returns (bytes[] memory);
+        function succeeded() external view returns (bool);
+    }
+}
@@ -0,0 +1,430 @@
+//! Fluent planning of calls on alloy contract instances.
+//!
+//! [`contract_planner!`](crate::contract_planner) generates a wrapper with one method per
+//! contract function, and implements [`PlanContract`] for the contract's instance type so calls
+//! read `weth.plan(&mut planner).balanceOf(AAVE).staticcall()?`. Wrappers for the bundled
+//! [`bindings`](crate::bindings) are provided.
+//!
+//! `sol!` does not expose a contract's functions to other macros, so wrappers for other
+//! instances list the functions they plan. Each method's parameters are checked against the
+//! fields of its call struct at compile time. Arguments are [`Value`]s, so their types are not:
+//! [`ReturnValue`] arguments are checked against the parameter types when the call is planned,
+//! and literals are encoded as given.
+
+use crate::Planner;
+use crate::cmds::{ReturnValue, Value};
+use crate::error::WeirollError;
+use crate::view::CallType;
+
+use alloy::dyn_abi::DynSolType;
+use alloy::primitives::{Address, U256};
+use alloy::sol_types::{SolCall, SolType};
+use std::marker::PhantomData;
+
+/// Contract instances which can plan calls with a generated wrapper.
+pub trait PlanContract {
+    type Planner<'p, 'a: 'p>;
+
+    /// Returns a wrapper which plans calls to this contract into `planner`.
+    fn plan<'p, 'a: 'p>(&self, planner: &'p mut Planner<'a>) -> Self::Planner<'p, 'a>;
+}
+
+/// A call with its arguments, waiting for a call type.
+#[must_use = "a planned call does nothing until a call type is chosen"]
+#[derive(Debug)]
+pub struct PlannedCall<'p, 'a, C> {
+    planner: &'p mut Planner<'a>,
+    address: Address,
+    args: Vec<Value<'a>>,
+    call: PhantomData<C>,
+}
+
+impl<'p, 'a, C: SolCall> PlannedCall<'p, 'a, C> {
+    pub fn new(planner: &'p mut Planner<'a>, address: Address, args: Vec<Value<'a>>) -> Self {
+        Self {
+            planner,
+            address,
+            args,
+            call: PhantomData,
+        }
+    }
+
+    /// Checks the number of arguments, and the types of [`ReturnValue`] arguments.
+    fn check_args(&self) -> Result<(), WeirollError> {
+        let params = match <C::Parameters<'_> as SolType>::SOL_NAME.parse()? {
+            DynSolType::Tuple(types) => types,
+            other => vec![other],
+        };
+        let command = self.planner.next_context(self.address, C::SELECTOR);
+
+        if params.len() != self.args.len() {
+            return Err(WeirollError::ArgumentCountMismatch {
+                command,
+                expected: params.len(),
+                actual: self.args.len(),
+            });
+        }
+
+        for (argument, (arg, expected)) in self.args.iter().zip(&params).enumerate() {
+            if let Value::Return(ret) = arg
+                && let Some(actual) = self.planner.return_type(ret.command)
+                && actual != expected
+            {
+                return Err(WeirollError::ArgumentTypeMismatch {
+                    command,
+                    argument,
+                    expected: expected.clone(),
+                    actual: actual.clone(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn call(self) -> Result<ReturnValue, WeirollError> {
+        self.check_args()?;
+        self.planner.call_address::<C>(self.address, self.args)
+    }
+
+    pub fn delegatecall(self) -> Result<ReturnValue, WeirollError> {
+        self.check_args()?;
+        self.planner
+            .delegatecall_address::<C>(self.address, self.args)
+    }
+
+    pub fn staticcall(self) -> Result<ReturnValue, WeirollError> {
+        self.check_args()?;
+        self.planner
+            .staticcall_address::<C>(self.address, self.args)
+    }
+
+    pub fn call_with_value(self, value: U256) -> Result<ReturnValue, WeirollError> {
+        self.check_args()?;
+        self.planner
+            .call_address_with_value::<C>(self.address, value, self.args)
+    }
+
+    /// Plans the call with `call_type`; CALLs with value are planned with a value of zero.
+    pub fn plan_as(self, call_type: CallType) -> Result<ReturnValue, WeirollError> {
+        match call_type {
+            CallType::Call | CallType::CallWithValue => self.call(),
+            CallType::DelegateCall => self.delegatecall(),
+            CallType::StaticCall => self.staticcall(),
+        }
+    }
+
+    /// Plans the call as a subplan; the arguments must include a subplan and the state.
+    pub fn subplan(self) -> Result<ReturnValue, WeirollError> {
+        self.check_args()?;
+        self.planner
+            .add_subplan_address::<C>(self.address, self.args)
+    }
+}
+
+/// Generates a fluent call planner for a contract.
+///
+/// ```ignore
+/// weiroll::contract_planner! {
+///     /// Plans calls to an ERC20 token.
+///     pub struct ERC20Planner for ERC20::ERC20Instance {
+///         balanceOf(account) => ERC20::balanceOfCall;
+///         transfer(recipient, amount) => ERC20::transferCall;
+///     }
+/// }
+/// ```
+///
+/// Parameters must be named, and ordered, as the fields of the call struct: a missing, extra or
+/// misnamed parameter fails to compile. Each method takes its arguments as `impl Into<Value>`,
+/// so literals and [`ReturnValue`]s can be mixed; the types of [`ReturnValue`]s are checked when a
+/// call type is chosen. Omit `for Instance` to generate only the wrapper, constructed with `new`.
+///
+/// Declare a `library` rather than a `struct` for a stateless library. Its methods may be named
+/// freely and plan a DELEGATECALL straight away, unless `call` or `staticcall` is chosen first:
+///
+/// ```ignore
+/// weiroll::contract_planner! {
+///     /// Plans calls to the `Events` library.
+///     pub library EventsLib {
+///         log_uint(message) => Events::logUintCall;
+///     }
+/// }
+///
+/// EventsLib::new(&mut planner, events).log_uint(total)?;
+/// EventsLib::new(&mut planner, events).call().log_uint(total)?;
+/// ```
+#[macro_export]
+macro_rules! contract_planner {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident for $($instance:ident)::+ {
+            $($method:ident ( $($arg:ident),* $(,)? ) => $call:path;)*
+        }
+    ) => {
+        $crate::contract_planner! {
+            $(#[$meta])*
+            $vis struct $name {
+                $($method ( $($arg),* ) => $call;)*
+            }
+        }
+
+        impl<P, N> $crate::PlanContract for $($instance)::+<P, N>
+        where
+            P: ::alloy::providers::Provider<N>,
+            N: ::alloy::network::Network,
+        {
+            type Planner<'p, 'a: 'p> = $name<'p, 'a>;
+
+            fn plan<'p, 'a: 'p>(&self, planner: &'p mut $crate::Planner<'a>) -> $name<'p, 'a> {
+                $name::new(planner, *self.address())
+            }
+        }
+    };
+
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($method:ident ( $($arg:ident),* $(,)? ) => $call:path;)*
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name<'p, 'a> {
+            planner: &'p mut $crate::Planner<'a>,
+            address: ::alloy::primitives::Address,
+        }
+
+        #[allow(non_snake_case, dead_code)]
+        impl<'p, 'a> $name<'p, 'a> {
+            pub fn new(planner: &'p mut $crate::Planner<'a>, address: ::alloy::primitives::Address) -> Self {
+                Self { planner, address }
+            }
+
+            $(
+                pub fn $method(
+                    self,
+                    $($arg: impl ::core::convert::Into<$crate::Value<'a>>),*
+                ) -> $crate::PlannedCall<'p, 'a, $call> {
+                    // Never run: only checks the parameters against the call's fields
+                    #[allow(unreachable_code)]
+                    let _ = || {
+                        $call { $($arg: ::core::unreachable!()),* }
+                    };
+                    $crate::PlannedCall::new(self.planner, self.address, vec![$($arg.into()),*])
+                }
+            )*
+        }
+    };
+
+    (
+        $(#[$meta:meta])*
+        $vis:vis library $name:ident {
+            $($method:ident ( $($arg:ident),* $(,)? ) => $call:path;)*
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name<'p, 'a> {
+            planner: &'p mut $crate::Planner<'a>,
+            address: ::alloy::primitives::Address,
+            call_type: $crate::CallType,
+        }
+
+        #[allow(dead_code)]
+        impl<'p, 'a> $name<'p, 'a> {
+            pub fn new(planner: &'p mut $crate::Planner<'a>, address: ::alloy::primitives::Address) -> Self {
+                Self {
+                    planner,
+                    address,
+                    call_type: $crate::CallType::DelegateCall,
+                }
+            }
+
+            /// Invokes the library with CALL rather than DELEGATECALL.
+            pub fn call(mut self) -> Self {
+                self.call_type = $crate::CallType::Call;
+                self
+            }
+
+            /// Invokes the library with STATICCALL rather than DELEGATECALL.
+            pub fn staticcall(mut self) -> Self {
+                self.call_type = $crate::CallType::StaticCall;
+                self
+            }
+
+            $(
+                pub fn $method(
+                    self,
+                    $($arg: impl ::core::convert::Into<$crate::Value<'a>>),*
+                ) -> ::core::result::Result<$crate::ReturnValue, $crate::WeirollError> {
+                    // Never run: only checks the parameters against the call's fields
+                    #[allow(unreachable_code)]
+                    let _ = || {
+                        $call { $($arg: ::core::unreachable!()),* }
+                    };
+                    let args = vec![$($arg.into()),*];
+                    $crate::PlannedCall::<$call>::new(self.planner, self.address, args)
+                        .plan_as(self.call_type)
+                }
+            )*
+        }
+    };
+}
+
+mod bundled {
+    use crate::bindings::{
+        erc20::ERC20, events::Events, ierc20::IERC20, lib_tupler::LibTupler, math::Math,
+        strings::Strings, testable_vm::TestableVM,
+    };
+
+    crate::contract_planner! {
+        /// Plans calls to an [`ERC20`] token.
+        pub struct ERC20Planner for ERC20::ERC20Instance {
+            allowance(owner, spender) => ERC20::allowanceCall;
+            approve(spender, amount) => ERC20::approveCall;
+            balanceOf(account) => ERC20::balanceOfCall;
+            decimals() => ERC20::decimalsCall;
+            name() => ERC20::nameCall;
+            symbol() => ERC20::symbolCall;
+            totalSupply() => ERC20::totalSupplyCall;
+            transfer(recipient, amount) => ERC20::transferCall;
+            transferFrom(sender, recipient, amount) => ERC20::transferFromCall;
+        }
+    }
+
+    crate::contract_planner! {
+        /// Plans calls to an [`IERC20`] token.
+        pub struct IERC20Planner for IERC20::IERC20Instance {
+            allowance(owner, spender) => IERC20::allowanceCall;
+            approve(spender, amount) => IERC20::approveCall;
+            balanceOf(account) => IERC20::balanceOfCall;
+            totalSupply() => IERC20::totalSupplyCall;
+            transfer(recipient, amount) => IERC20::transferCall;
+            transferFrom(sender, recipient, amount) => IERC20::transferFromCall;
+        }
+    }
+
+    crate::contract_planner! {
+        /// Plans calls to the [`Math`] library.
+        pub struct MathPlanner for Math::MathInstance {
+            add(a, b) => Math::addCall;
+            mul(a, b) => Math::mulCall;
+            sub(a, b) => Math::subCall;
+            sum(values) => Math::sumCall;
+        }
+    }
+
+    crate::contract_planner! {
+        /// Plans calls to the [`Strings`] library.
+        pub struct StringsPlanner for Strings::StringsInstance {
+            strcat(a, b) => Strings::strcatCall;
+            strlen(x) => Strings::strlenCall;
+        }
+    }
+
+    crate::contract_planner! {
+        /// Plans calls to the [`Events`] library.
+        pub struct EventsPlanner for Events::EventsInstance {
+            logAddress(message) => Events::logAddressCall;
+            logBytes(message) => Events::logBytesCall;
+            logBytes32(message) => Events::logBytes32Call;
+            logString(message) => Events::logStringCall;
+            logUint(message) => Events::logUintCall;
+        }
+    }
+
+    crate::contract_planner! {
+        /// Plans calls to the [`LibTupler`] library.
+        pub struct LibTuplerPlanner for LibTupler::LibTuplerInstance {
+            extractElement(tuple, index) => LibTupler::extractElementCall;
+        }
+    }
+
+    crate::contract_planner! {
+        /// Plans calls to a [`TestableVM`], typically as subplans.
+        pub struct TestableVMPlanner for TestableVM::TestableVMInstance {
+            execute(commands, state) => TestableVM::executeCall;
+        }
+    }
+}
+
+pub use bundled::{
+    ERC20Planner, EventsPlanner, IERC20Planner, LibTuplerPlanner, MathPlanner, StringsPlanner,
+    TestableVMPlanner,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::{erc20::ERC20, events::Events};
+    use alloy::primitives::address;
+    use alloy::providers::{ProviderBuilder, mock::Asserter};
+
+    #[test]
+    fn fluent_calls_match_call_contract() {
+        let provider = ProviderBuilder::new().connect_mocked_client(Asserter::new());
+        let weth = ERC20::new(
+            address!("0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2"),
+            &provider,
+        );
+        let events = Events::new(
+            address!("0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee"),
+            &provider,
+        );
+        let aave = address!("0x4d5F47FA6A74757f35C14fD3a6Ef8E3C9BC514E8");
+
+        let mut fluent = Planner::default();
+        let balance = weth.plan(&mut fluent).balanceOf(aave).staticcall().unwrap();
+        events.plan(&mut fluent).logUint(balance).call().unwrap();
+
+        let mut expected = Planner::default();
+        let balance =
+            crate::call_contract!(staticcall, &mut expected, &weth, ERC20::balanceOfCall[aave])
+                .unwrap();
+        crate::call_contract!(&mut expected, &events, Events::logUintCall[balance]).unwrap();
+
+        assert_eq!(fluent.plan().unwrap(), expected.plan().unwrap());
+    }
+
+    #[test]
+    fn planned_call_checks_argument_count() {
+        let mut planner = Planner::default();
+        let err = PlannedCall::<ERC20::balanceOfCall>::new(
+            &mut planner,
+            Address::ZERO,
+            vec![Address::ZERO.into(), Address::ZERO.into()],
+        )
+        .call()
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            WeirollError::ArgumentCountMismatch {
+                command,
+                expected: 1,
+                actual: 2,
+            } if command.selector == ERC20::balanceOfCall::SELECTOR
+        ));
+    }
+
+    #[test]
+    fn planned_call_checks_return_types() {
+        let mut planner = Planner::default();
+        let name = PlannedCall::<ERC20::nameCall>::new(&mut planner, Address::ZERO, vec![])
+            .staticcall()
+            .unwrap();
+        let err = PlannedCall::<ERC20::balanceOfCall>::new(
+            &mut planner,
+            Address::ZERO,
+            vec![name.into()],
+        )
+        .staticcall()
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            WeirollError::ArgumentTypeMismatch {
+                argument: 0,
+                expected: DynSolType::Address,
+                actual: DynSolType::String,
+                ..
+            }
+        ));
+    }
+}
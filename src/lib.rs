@@ -1,13 +1,52 @@
+pub mod aa;
 pub mod bindings;
 mod calls;
 mod cmds;
+mod compose;
+mod composite;
+mod control;
+mod cost;
+mod dialect;
+mod dot;
+pub mod erc20;
 mod error;
+#[cfg(test)]
+mod evm;
+mod fields;
+pub mod helpers;
+mod instance;
+pub mod lint;
+mod optimize;
 mod planner;
+mod provider;
+mod raw;
+pub mod safe;
+mod script;
+pub mod signing;
+mod split;
+pub mod stdlib;
+mod view;
 
 pub use calls::FunctionCall;
-pub use cmds::{Value, ReturnValue};
-pub use error::WeirollError;
+pub use cmds::{ReturnValue, Value};
+pub use compose::RemapTable;
+pub use composite::Composite;
+pub use control::{Attempt, DEFAULT_MAX_ITERATIONS};
+pub use cost::{CommandCost, PlanCost};
+pub use dialect::{CommandParts, Slot, VmDialect, WeirollV1};
+pub use error::{CommandContext, ProviderError, WeirollError};
+#[doc(hidden)]
+pub use fields::ToField;
+pub use fields::{CallFields, Probe};
+pub use instance::{
+    ERC20Planner, EventsPlanner, IERC20Planner, LibTuplerPlanner, MathPlanner, PlanContract,
+    PlannedCall, StringsPlanner, TestableVMPlanner,
+};
+pub use optimize::Optimization;
 pub use planner::Planner;
+pub use provider::{PlanSimulation, WeirollProviderExt, testable_vm_override};
+pub use split::Budget;
+pub use view::{ArgView, CallType, CommandKind, CommandView};
 
 /// Plan a contract call into a [`Planner`].
 ///
@@ -16,8 +55,9 @@ pub use planner::Planner;
 /// - `Contract::callName[args...]` (**values mode**): positional args, each coerced via `.into()`.
 ///   This is the mode you want when passing prior planner outputs like [`ReturnValue`].
 /// - `Contract::callName { field: value, ... }` (**struct-literal mode**): expands to a real
-///   `callName { ... }` struct literal and is fully type-checked, but cannot accept [`ReturnValue`]
-///   fields.
+///   `callName { ... }` struct literal and is fully type-checked. Fields may be [`ReturnValue`]s;
+///   their position is resolved from the call's parameters and the producing command's return
+///   type must match the field's Solidity type. The field's Rust type must implement [`Probe`].
 #[macro_export]
 macro_rules! call_contract {
     // ---- Public API: values mode (positional args) ----
@@ -45,7 +85,32 @@ macro_rules! call_contract {
         $crate::call_contract!(@dispatch value($value), $planner, $contract, $call [ $($arg),* ])
     }};
 
-    // ---- Public API: SolCall mode (type-checked struct literal) ----
+    // ---- Public API: struct-literal mode (fields may be ReturnValues) ----
+    ($planner:expr, $contract:expr, $call:path { $($field:ident : $value:expr),* $(,)? }) => {{
+        $crate::call_contract!(@dispatch call, $planner, $contract, fields $call { $($field: $value),* })
+    }};
+
+    (call, $planner:expr, $contract:expr, $call:path { $($field:ident : $value:expr),* $(,)? }) => {{
+        $crate::call_contract!(@dispatch call, $planner, $contract, fields $call { $($field: $value),* })
+    }};
+
+    (delegate, $planner:expr, $contract:expr, $call:path { $($field:ident : $value:expr),* $(,)? }) => {{
+        $crate::call_contract!(@dispatch delegatecall, $planner, $contract, fields $call { $($field: $value),* })
+    }};
+
+    (delegatecall, $planner:expr, $contract:expr, $call:path { $($field:ident : $value:expr),* $(,)? }) => {{
+        $crate::call_contract!(@dispatch delegatecall, $planner, $contract, fields $call { $($field: $value),* })
+    }};
+
+    (staticcall, $planner:expr, $contract:expr, $call:path { $($field:ident : $value:expr),* $(,)? }) => {{
+        $crate::call_contract!(@dispatch staticcall, $planner, $contract, fields $call { $($field: $value),* })
+    }};
+
+    (value($value:expr), $planner:expr, $contract:expr, $call:path { $($field:ident : $fvalue:expr),* $(,)? }) => {{
+        $crate::call_contract!(@dispatch value($value), $planner, $contract, fields $call { $($field: $fvalue),* })
+    }};
+
+    // ---- Public API: SolCall mode (any expression evaluating to a call struct) ----
     ($planner:expr, $contract:expr, $call:expr) => {{
         $crate::call_contract!(@dispatch call, $planner, $contract, ( $call ))
     }};
@@ -93,7 +158,7 @@ macro_rules! call_contract {
         let __planner = &mut *$planner;
         let __address = *$contract.address();
         let __value: ::alloy::primitives::U256 = ($value).into();
-        __planner.call_with_value_address::<$call>(__address, __value, vec![$($arg.into(),)*])
+        __planner.call_address_with_value::<$call>(__address, __value, vec![$($arg.into(),)*])
     }};
 
     (@dispatch call, $planner:expr, $contract:expr, ( $call:expr ) ) => {{
@@ -118,18 +183,78 @@ macro_rules! call_contract {
         let __planner = &mut *$planner;
         let __address = *$contract.address();
         let __value: ::alloy::primitives::U256 = ($value).into();
-        __planner.call_with_value_sol(__address, __value, $call)
+        __planner.call_sol_with_value(__address, __value, $call)
+    }};
+
+    (@dispatch call, $planner:expr, $contract:expr, fields $call:path { $($field:ident : $value:expr),* } ) => {{
+        let __planner = &mut *$planner;
+        let __address = *$contract.address();
+        match $crate::call_contract!(@fields $call { $($field: $value),* }) {
+            __fields => __planner.call_fields(__address, __fields),
+        }
+    }};
+
+    (@dispatch delegatecall, $planner:expr, $contract:expr, fields $call:path { $($field:ident : $value:expr),* } ) => {{
+        let __planner = &mut *$planner;
+        let __address = *$contract.address();
+        match $crate::call_contract!(@fields $call { $($field: $value),* }) {
+            __fields => __planner.delegatecall_fields(__address, __fields),
+        }
+    }};
+
+    (@dispatch staticcall, $planner:expr, $contract:expr, fields $call:path { $($field:ident : $value:expr),* } ) => {{
+        let __planner = &mut *$planner;
+        let __address = *$contract.address();
+        match $crate::call_contract!(@fields $call { $($field: $value),* }) {
+            __fields => __planner.staticcall_fields(__address, __fields),
+        }
+    }};
+
+    (@dispatch value($value:expr), $planner:expr, $contract:expr, fields $call:path { $($field:ident : $fvalue:expr),* } ) => {{
+        let __planner = &mut *$planner;
+        let __address = *$contract.address();
+        let __value: ::alloy::primitives::U256 = ($value).into();
+        match $crate::call_contract!(@fields $call { $($field: $fvalue),* }) {
+            __fields => __planner.call_fields_with_value(__address, __value, __fields),
+        }
     }};
+
+    // Evaluates each field once, then builds the base call and one probe per ReturnValue field.
+    (@fields $call:path { $($field:ident : $value:expr),* }) => {
+        match ($($value,)*) {
+            ($($field,)*) => {
+                let __build = |__probe: ::core::option::Option<&str>,
+                               __returns: &mut ::std::vec::Vec<(&'static str, $crate::ReturnValue)>| {
+                    $call {
+                        $($field: $crate::ToField::to_field(&$field, stringify!($field), __probe, __returns),)*
+                    }
+                };
+                let mut __returns = ::std::vec::Vec::new();
+                let __base = __build(::core::option::Option::None, &mut __returns);
+                let __probes = __returns
+                    .into_iter()
+                    .map(|(__name, __ret)| {
+                        (__ret, __build(::core::option::Option::Some(__name), &mut ::std::vec::Vec::new()))
+                    })
+                    .collect();
+                $crate::CallFields::new(__base, __probes)
+            }
+        }
+    };
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloy::dyn_abi::DynSolType;
     use alloy::primitives::{Address, U256, address};
 
     alloy::sol! {
         interface MacroTestContract {
             function setValue(uint256 value) external;
+            function getValue() external view returns (uint256);
+            function getName() external view returns (string);
+            function transfer(address to, uint256 amount, string memo) external;
         }
     }
 
@@ -173,4 +298,109 @@ mod tests {
 
         assert_eq!(commands[0], commands[1]);
     }
+
+    #[test]
+    fn struct_literal_accepts_return_value_fields() {
+        let mut planner = Planner::default();
+        let contract = DummyContract {
+            address: address!("0xdead00000000000000000000000000000000beef"),
+        };
+        let to = address!("0x0000000000000000000000000000000000000002");
+
+        let amount = crate::call_contract!(
+            staticcall,
+            &mut planner,
+            &contract,
+            MacroTestContract::getValueCall {}
+        )
+        .expect("plan getValue");
+
+        crate::call_contract!(
+            &mut planner,
+            &contract,
+            MacroTestContract::transferCall {
+                memo: String::from("hi"),
+                amount: amount.clone(),
+                to: to,
+            }
+        )
+        .expect("struct literal should accept ReturnValue fields");
+
+        let mut positional = Planner::default();
+        let amount = crate::call_contract!(
+            staticcall,
+            &mut positional,
+            &contract,
+            MacroTestContract::getValueCall[]
+        )
+        .expect("plan getValue");
+        crate::call_contract!(
+            &mut positional,
+            &contract,
+            MacroTestContract::transferCall[to, amount, String::from("hi")]
+        )
+        .expect("plan transfer");
+
+        assert_eq!(planner.plan().unwrap(), positional.plan().unwrap());
+    }
+
+    #[test]
+    fn struct_literal_rejects_mismatched_return_value() {
+        let mut planner = Planner::default();
+        let contract = DummyContract {
+            address: address!("0xdead00000000000000000000000000000000beef"),
+        };
+
+        let name = crate::call_contract!(
+            staticcall,
+            &mut planner,
+            &contract,
+            MacroTestContract::getNameCall {}
+        )
+        .expect("plan getName");
+
+        let err = crate::call_contract!(
+            &mut planner,
+            &contract,
+            MacroTestContract::setValueCall { value: name }
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            WeirollError::ArgumentTypeMismatch {
+                command,
+                argument: 0,
+                expected: DynSolType::Uint(256),
+                actual: DynSolType::String,
+            } if command.index == 1
+        ));
+    }
+
+    #[test]
+    fn value_mode_sends_value() {
+        let mut planner = Planner::default();
+        let contract = DummyContract {
+            address: address!("0xdead00000000000000000000000000000000beef"),
+        };
+
+        crate::call_contract!(
+            value(U256::from(5)),
+            &mut planner,
+            &contract,
+            MacroTestContract::setValueCall[1u64]
+        )
+        .expect("values mode with value");
+        crate::call_contract!(
+            value(U256::from(5)),
+            &mut planner,
+            &contract,
+            MacroTestContract::setValueCall {
+                value: U256::from(1),
+            }
+        )
+        .expect("struct literal mode with value");
+
+        let (commands, _state) = planner.plan().expect("plan");
+        assert_eq!(commands[0], commands[1]);
+    }
 }
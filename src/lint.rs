@@ -0,0 +1,582 @@
+//! Static checks over planned commands.
+//!
+//! A [`Linter`] runs a set of [`LintRule`]s over a [`Planner`]'s commands, and over the commands
+//! of any subplans, and reports [`Diagnostic`]s. The default linter includes every built-in rule;
+//! rules can be replaced, disabled or given a different severity, and custom rules can be added
+//! by implementing [`LintRule`].
+//!
+//! ```ignore
+//! let linter = Linter::default()
+//!     .with_rule(DelegatecallAllowlist::new([math, strings]))
+//!     .with_severity(UnusedReturnValue::NAME, Severity::Info);
+//!
+//! for diagnostic in planner.lint(&linter) {
+//!     println!("{diagnostic}");
+//! }
+//! ```
+
+use crate::Planner;
+use crate::bindings::ierc20::IERC20;
+use crate::cmds::ReturnValue;
+use crate::error::CommandContext;
+
+use alloy::primitives::{Address, U256};
+use alloy::sol_types::SolCall;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+pub use crate::view::{ArgView, CallType, CommandKind, CommandView};
+
+/// How serious a [`Diagnostic`] is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// A problem a rule found with one command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    /// Position of the offending command in the commands passed to the rule
+    pub command: usize,
+    pub message: String,
+}
+
+impl Finding {
+    pub fn new(command: usize, message: impl Into<String>) -> Self {
+        Self {
+            command,
+            message: message.into(),
+        }
+    }
+}
+
+/// A [`Finding`] reported by a [`Linter`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub rule: &'static str,
+    pub severity: Severity,
+    /// Indices of the subplan commands enclosing `command`, outermost first
+    pub subplan: Vec<usize>,
+    pub command: CommandContext,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}] ", self.severity, self.rule)?;
+        for index in &self.subplan {
+            write!(f, "subplan {index}: ")?;
+        }
+        write!(f, "{}: {}", self.command, self.message)
+    }
+}
+
+/// A check over the commands of one planner.
+pub trait LintRule {
+    /// A short, unique, kebab-case name used to configure the rule.
+    fn name(&self) -> &'static str;
+
+    /// The severity of findings unless the [`Linter`] overrides it.
+    fn severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    fn check(&self, commands: &[CommandView<'_>]) -> Vec<Finding>;
+}
+
+/// Runs [`LintRule`]s over a plan.
+pub struct Linter {
+    rules: Vec<Box<dyn LintRule>>,
+    severities: BTreeMap<&'static str, Severity>,
+}
+
+impl Default for Linter {
+    /// A linter with every built-in rule and an empty DELEGATECALL allowlist.
+    fn default() -> Self {
+        Self::empty()
+            .with_rule(DelegatecallAllowlist::default())
+            .with_rule(UnusedReturnValue)
+            .with_rule(ZeroValueCall)
+            .with_rule(UnlimitedApproval)
+            .with_rule(ReadAfterReplaceState)
+    }
+}
+
+impl fmt::Debug for Linter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Linter")
+            .field(
+                "rules",
+                &self.rules.iter().map(|r| r.name()).collect::<Vec<_>>(),
+            )
+            .field("severities", &self.severities)
+            .finish()
+    }
+}
+
+impl Linter {
+    /// A linter without any rules.
+    pub fn empty() -> Self {
+        Self {
+            rules: Vec::new(),
+            severities: BTreeMap::new(),
+        }
+    }
+
+    /// Adds `rule`, replacing any rule with the same name.
+    pub fn with_rule(mut self, rule: impl LintRule + 'static) -> Self {
+        match self.rules.iter().position(|r| r.name() == rule.name()) {
+            Some(index) => self.rules[index] = Box::new(rule),
+            None => self.rules.push(Box::new(rule)),
+        }
+        self
+    }
+
+    /// Removes the rule named `name`.
+    pub fn without_rule(mut self, name: &str) -> Self {
+        self.rules.retain(|r| r.name() != name);
+        self
+    }
+
+    /// Reports findings of the rule named `name` with `severity`.
+    pub fn with_severity(mut self, name: &'static str, severity: Severity) -> Self {
+        self.severities.insert(name, severity);
+        self
+    }
+
+    /// Checks `planner` and its subplans, returning diagnostics in command order.
+    pub fn lint(&self, planner: &Planner<'_>) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        self.lint_commands(&planner.commands(), &mut Vec::new(), &mut diagnostics);
+        diagnostics
+    }
+
+    fn lint_commands(
+        &self,
+        commands: &[CommandView<'_>],
+        subplan: &mut Vec<usize>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let mut found = Vec::new();
+        for rule in &self.rules {
+            let severity = self
+                .severities
+                .get(rule.name())
+                .copied()
+                .unwrap_or_else(|| rule.severity());
+
+            for finding in rule.check(commands) {
+                let Some(command) = commands.get(finding.command) else {
+                    continue;
+                };
+                found.push(Diagnostic {
+                    rule: rule.name(),
+                    severity,
+                    subplan: subplan.clone(),
+                    command: command.context(),
+                    message: finding.message,
+                });
+            }
+        }
+        found.sort_by_key(|d| d.command.index);
+
+        // Diagnostics of a subplan follow those of the command which executes it
+        let mut found = found.into_iter().peekable();
+        for command in commands {
+            while let Some(d) = found.next_if(|d| d.command.index == command.index) {
+                diagnostics.push(d);
+            }
+
+            for arg in &command.args {
+                if let ArgView::Subplan(planner) = arg {
+                    subplan.push(command.index);
+                    self.lint_commands(&planner.commands(), subplan, diagnostics);
+                    subplan.pop();
+                }
+            }
+        }
+    }
+}
+
+impl Planner<'_> {
+    /// Checks the plan with `linter`, see [`lint`](crate::lint).
+    pub fn lint(&self, linter: &Linter) -> Vec<Diagnostic> {
+        linter.lint(self)
+    }
+}
+
+/// Flags DELEGATECALLs to targets outside an allowlist.
+///
+/// A DELEGATECALLed contract runs with the VM's storage and balance, so only trusted libraries
+/// should be targeted. Subplans and state replacements are DELEGATECALLs too.
+#[derive(Clone, Debug, Default)]
+pub struct DelegatecallAllowlist {
+    allowed: BTreeSet<Address>,
+}
+
+impl DelegatecallAllowlist {
+    pub const NAME: &'static str = "delegatecall-allowlist";
+
+    pub fn new(allowed: impl IntoIterator<Item = Address>) -> Self {
+        Self {
+            allowed: allowed.into_iter().collect(),
+        }
+    }
+}
+
+impl LintRule for DelegatecallAllowlist {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn check(&self, commands: &[CommandView<'_>]) -> Vec<Finding> {
+        commands
+            .iter()
+            .filter(|c| c.call_type == CallType::DelegateCall && !self.allowed.contains(&c.target))
+            .map(|c| {
+                Finding::new(
+                    c.index,
+                    format!("DELEGATECALL to {} which is not allowlisted", c.target),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Flags commands whose output is never consumed.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UnusedReturnValue;
+
+impl UnusedReturnValue {
+    pub const NAME: &'static str = "unused-return-value";
+}
+
+/// Collects every [`ReturnValue`] consumed by `commands` or their subplans.
+fn consumed(commands: &[CommandView<'_>], used: &mut Vec<ReturnValue>) {
+    for command in commands {
+        for arg in &command.args {
+            match arg {
+                ArgView::Return(ret) => used.push(ret.clone()),
+                ArgView::Subplan(planner) => consumed(&planner.commands(), used),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl LintRule for UnusedReturnValue {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn check(&self, commands: &[CommandView<'_>]) -> Vec<Finding> {
+        let mut used = Vec::new();
+        consumed(commands, &mut used);
+
+        commands
+            .iter()
+            .filter(|c| c.has_output() && !used.contains(&c.output))
+            .map(|c| {
+                Finding::new(
+                    c.index,
+                    format!("returns {} which is never used", c.return_type),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Flags CALL_WITH_VALUE commands which send no value.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ZeroValueCall;
+
+impl ZeroValueCall {
+    pub const NAME: &'static str = "zero-value-call";
+}
+
+impl LintRule for ZeroValueCall {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn check(&self, commands: &[CommandView<'_>]) -> Vec<Finding> {
+        commands
+            .iter()
+            .filter(|c| c.call_type == CallType::CallWithValue && c.value == Some(U256::ZERO))
+            .map(|c| Finding::new(c.index, "sends a value of zero; use a plain CALL"))
+            .collect()
+    }
+}
+
+/// Flags ERC20 `approve` calls granting an unlimited allowance.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UnlimitedApproval;
+
+impl UnlimitedApproval {
+    pub const NAME: &'static str = "unlimited-approval";
+}
+
+impl LintRule for UnlimitedApproval {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn check(&self, commands: &[CommandView<'_>]) -> Vec<Finding> {
+        let unlimited = U256::MAX.to_be_bytes::<32>();
+
+        commands
+            .iter()
+            .filter(|c| c.selector == IERC20::approveCall::SELECTOR)
+            .filter(|c| matches!(c.args.get(1), Some(ArgView::Literal(amount)) if amount[..] == unlimited))
+            .map(|c| {
+                Finding::new(
+                    c.index,
+                    format!("approves an unlimited allowance on {}", c.target),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Flags commands which read state slots after a [`replace_state`](Planner::replace_state).
+///
+/// Each replacement is checked against the commands up to the next one. The replacement state is
+/// whatever the call returned, so literals and outputs of commands planned before it may no
+/// longer be where the planner put them.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReadAfterReplaceState;
+
+impl ReadAfterReplaceState {
+    pub const NAME: &'static str = "read-after-replace-state";
+}
+
+impl LintRule for ReadAfterReplaceState {
+    fn name(&self) -> &'static str {
+        Self::NAME
+    }
+
+    fn check(&self, commands: &[CommandView<'_>]) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        // The latest replacement, and the outputs of the commands planned before it
+        let mut replaced = None;
+        let mut before: Vec<&ReturnValue> = Vec::new();
+
+        for (position, command) in commands.iter().enumerate() {
+            if let Some(replaced) = replaced {
+                for (argument, arg) in command.args.iter().enumerate() {
+                    let reads = match arg {
+                        ArgView::Literal(_) => "a literal",
+                        ArgView::Return(ret) if before.contains(&ret) => "an earlier output",
+                        _ => continue,
+                    };
+                    findings.push(Finding::new(
+                        command.index,
+                        format!(
+                            "argument {argument} reads {reads} which the state replacement by command {replaced} may have overwritten"
+                        ),
+                    ));
+                }
+            }
+
+            if command.kind == CommandKind::ReplaceState {
+                replaced = Some(command.index);
+                before = commands[..position].iter().map(|c| &c.output).collect();
+            }
+        }
+
+        findings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Value;
+    use crate::bindings::{math::Math, strings::Strings};
+    use alloy::primitives::address;
+    use alloy::sol;
+
+    sol! {
+        interface StateContract {
+            function useState(bytes[] state) external returns (bytes[]);
+        }
+    }
+
+    fn addr() -> Address {
+        address!("0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee")
+    }
+
+    fn rules(diagnostics: &[Diagnostic]) -> Vec<(&'static str, usize)> {
+        diagnostics
+            .iter()
+            .map(|d| (d.rule, d.command.index))
+            .collect()
+    }
+
+    #[test]
+    fn default_rules_flag_unsafe_commands() {
+        let token = address!("0x1111111111111111111111111111111111111111");
+        let mut planner = Planner::default();
+        let sum = planner
+            .delegatecall_address::<Math::addCall>(
+                addr(),
+                vec![U256::from(1).into(), U256::from(2).into()],
+            )
+            .unwrap();
+        planner
+            .staticcall_address::<Math::addCall>(addr(), vec![sum.into(), U256::from(3).into()])
+            .unwrap();
+        planner
+            .call_address_with_value::<IERC20::approveCall>(
+                token,
+                U256::ZERO,
+                vec![addr().into(), U256::MAX.into()],
+            )
+            .unwrap();
+
+        let diagnostics = planner.lint(&Linter::default());
+        assert_eq!(
+            rules(&diagnostics),
+            vec![
+                (DelegatecallAllowlist::NAME, 0),
+                (UnusedReturnValue::NAME, 1),
+                (UnusedReturnValue::NAME, 2),
+                (ZeroValueCall::NAME, 2),
+                (UnlimitedApproval::NAME, 2),
+            ]
+        );
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+
+        let linter = Linter::default()
+            .with_rule(DelegatecallAllowlist::new([addr()]))
+            .without_rule(UnusedReturnValue::NAME)
+            .with_severity(ZeroValueCall::NAME, Severity::Info);
+        let diagnostics = planner.lint(&linter);
+        assert_eq!(
+            rules(&diagnostics),
+            vec![(ZeroValueCall::NAME, 2), (UnlimitedApproval::NAME, 2)]
+        );
+        assert_eq!(diagnostics[0].severity, Severity::Info);
+    }
+
+    #[test]
+    fn flags_reads_after_replace_state() {
+        let mut planner = Planner::default();
+        let joined = planner
+            .delegatecall_address::<Strings::strcatCall>(
+                addr(),
+                vec![String::from("a").into(), String::from("b").into()],
+            )
+            .unwrap();
+        planner
+            .replace_state::<StateContract::useStateCall>(addr(), vec![Value::State(vec![])])
+            .unwrap();
+        planner
+            .delegatecall_address::<Strings::strlenCall>(addr(), vec![joined.into()])
+            .unwrap();
+
+        let linter = Linter::empty().with_rule(ReadAfterReplaceState);
+        let diagnostics = planner.lint(&linter);
+        assert_eq!(rules(&diagnostics), vec![(ReadAfterReplaceState::NAME, 2)]);
+        assert!(diagnostics[0].message.contains("an earlier output"));
+    }
+
+    #[test]
+    fn flags_reads_after_every_replace_state() {
+        let mut planner = Planner::default();
+        planner
+            .replace_state::<StateContract::useStateCall>(addr(), vec![Value::State(vec![])])
+            .unwrap();
+        let joined = planner
+            .delegatecall_address::<Strings::strcatCall>(
+                addr(),
+                vec![String::from("a").into(), String::from("b").into()],
+            )
+            .unwrap();
+        let length = planner
+            .delegatecall_address::<Strings::strlenCall>(addr(), vec![joined.clone().into()])
+            .unwrap();
+        planner
+            .replace_state::<StateContract::useStateCall>(addr(), vec![Value::State(vec![])])
+            .unwrap();
+        planner
+            .delegatecall_address::<Strings::strlenCall>(addr(), vec![joined.into()])
+            .unwrap();
+        planner
+            .call_address::<Math::addCall>(addr(), vec![length.into(), U256::from(1).into()])
+            .unwrap();
+
+        let linter = Linter::empty().with_rule(ReadAfterReplaceState);
+        let diagnostics = planner.lint(&linter);
+        assert_eq!(
+            rules(&diagnostics),
+            vec![
+                (ReadAfterReplaceState::NAME, 1),
+                (ReadAfterReplaceState::NAME, 1),
+                (ReadAfterReplaceState::NAME, 4),
+                (ReadAfterReplaceState::NAME, 5),
+                (ReadAfterReplaceState::NAME, 5),
+            ]
+        );
+        assert!(diagnostics[2].message.contains("by command 3"));
+    }
+
+    struct NoMath;
+
+    impl LintRule for NoMath {
+        fn name(&self) -> &'static str {
+            "no-math"
+        }
+
+        fn check(&self, commands: &[CommandView<'_>]) -> Vec<Finding> {
+            commands
+                .iter()
+                .filter(|c| c.selector == Math::addCall::SELECTOR)
+                .map(|c| Finding::new(c.index, "math is not allowed"))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn custom_rules_run_inside_subplans() {
+        let mut sub = Planner::default();
+        sub.call_address::<Math::addCall>(addr(), vec![U256::from(1).into(), U256::from(2).into()])
+            .unwrap();
+
+        let mut planner = Planner::default();
+        planner
+            .add_subplan_address::<crate::bindings::testable_vm::TestableVM::executeCall>(
+                addr(),
+                vec![Value::Subplan(&sub), Value::State(vec![])],
+            )
+            .unwrap();
+
+        let diagnostics = planner.lint(&Linter::empty().with_rule(NoMath));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].subplan, vec![0]);
+        assert_eq!(diagnostics[0].command.index, 0);
+        assert_eq!(
+            diagnostics[0].to_string(),
+            format!(
+                "warning[no-math] subplan 0: command 0 (selector 0x771602f7 on {}): math is not allowed",
+                addr()
+            )
+        );
+    }
+}
@@ -0,0 +1,358 @@
+//! An opt-in pass which removes redundant STATICCALLs from a plan.
+
+use crate::Planner;
+use crate::cmds::{Command, CommandFlags, CommandType, ReturnValue, Value};
+use crate::planner::{CommandKey, PlannerId};
+
+use alloy::dyn_abi::DynSolType;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// What [`Planner::optimize`] changed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Optimization {
+    /// Number of STATICCALLs removed because nothing consumed their output
+    pub removed: usize,
+    /// Number of STATICCALLs merged into an identical earlier one
+    pub merged: usize,
+    replacements: BTreeMap<(PlannerId, CommandKey), ReturnValue>,
+}
+
+impl Optimization {
+    /// Returns the value to use in place of `ret` after optimizing.
+    ///
+    /// Outputs of merged commands resolve to the command they were merged into; other values
+    /// are returned unchanged.
+    pub fn resolve(&self, ret: &ReturnValue) -> ReturnValue {
+        self.replacements
+            .get(&(ret.planner, ret.command))
+            .unwrap_or(ret)
+            .clone()
+    }
+}
+
+fn is_staticcall(command: &Command) -> bool {
+    command.kind == CommandType::Call
+        && command.call.flags & CommandFlags::CALLTYPE_MASK == CommandFlags::STATICCALL
+}
+
+/// Whether two commands make the same call with the same arguments.
+fn same_call(a: &Command, b: &Command) -> bool {
+    a.call.address == b.call.address
+        && a.call.selector == b.call.selector
+        && a.call.return_type == b.call.return_type
+        && a.call.args.len() == b.call.args.len()
+        && a.call
+            .args
+            .iter()
+            .zip(&b.call.args)
+            .all(|(a, b)| match (a, b) {
+                (Value::Literal(a), Value::Literal(b)) => a == b,
+                (Value::Return(a), Value::Return(b)) => {
+                    (a.planner, a.command) == (b.planner, b.command)
+                }
+                _ => false,
+            })
+}
+
+/// Collects the commands whose outputs are consumed by `planner`'s subplans.
+fn subplan_consumed(planner: &Planner<'_>, used: &mut BTreeSet<CommandKey>) {
+    for (_, command) in planner.command_entries() {
+        for arg in &command.call.args {
+            if let Some(subplan) = arg.subplan() {
+                consumed(planner, subplan, used);
+            }
+        }
+    }
+}
+
+/// Collects the commands of `owner` whose outputs are consumed by `planner` or its subplans.
+fn consumed(owner: &Planner<'_>, planner: &Planner<'_>, used: &mut BTreeSet<CommandKey>) {
+    for (_, command) in planner.command_entries() {
+        command.consumed(owner.id, used);
+    }
+}
+
+/// Points `value`, or the elements of a composite `value`, at the output of `to` wherever it
+/// consumes the output of `from` in `planner`.
+fn replace_value(value: &mut Value<'_>, planner: PlannerId, from: CommandKey, to: CommandKey) {
+    match value {
+        Value::Return(ret) if ret.planner == planner && ret.command == from => {
+            ret.command = to;
+        }
+        Value::Composite(composite) => {
+            for element in composite.elements_mut() {
+                replace_value(element, planner, from, to);
+            }
+        }
+        _ => {}
+    }
+}
+
+impl Planner<'_> {
+    /// Removes STATICCALLs whose outputs are never used, and merges STATICCALLs into an
+    /// identical earlier one when no state-changing command lies between them. STATICCALLs
+    /// without an output, such as assertions, are kept, as they may revert.
+    ///
+    /// Commands are never reordered. Arguments referring to a merged command are rewritten;
+    /// any [`ReturnValue`]s held outside the planner should be passed through
+    /// [`Optimization::resolve`] before further use. Subplans are borrowed and left untouched,
+    /// so commands whose outputs they consume are kept as they are.
+    pub fn optimize(&mut self) -> Optimization {
+        let mut optimization = Optimization::default();
+        self.merge_staticcalls(&mut optimization);
+        self.remove_unused_staticcalls(&mut optimization);
+        optimization
+    }
+
+    fn merge_staticcalls(&mut self, optimization: &mut Optimization) {
+        let mut pinned = BTreeSet::new();
+        subplan_consumed(self, &mut pinned);
+
+        // STATICCALLs since the last command which may have changed state
+        let mut candidates: Vec<CommandKey> = Vec::new();
+
+        for key in self.order.clone() {
+            let command = &self.commands[key];
+            if !is_staticcall(command) {
+                candidates.clear();
+                continue;
+            }
+
+            let original = candidates
+                .iter()
+                .find(|earlier| same_call(&self.commands[**earlier], command))
+                .copied();
+
+            match original {
+                Some(original) if !pinned.contains(&key) => {
+                    self.remove_command(key);
+                    self.replace_return(key, original);
+
+                    let replacement = ReturnValue {
+                        dynamic: self.commands[original].call.return_type.is_dynamic(),
+                        command: original,
+                        planner: self.id,
+                    };
+                    for ret in optimization.replacements.values_mut() {
+                        if ret.command == key {
+                            *ret = replacement.clone();
+                        }
+                    }
+                    optimization
+                        .replacements
+                        .insert((self.id, key), replacement);
+                    optimization.merged += 1;
+                }
+                _ => candidates.push(key),
+            }
+        }
+    }
+
+    /// Points every argument consuming the output of `from` at the output of `to`.
+    fn replace_return(&mut self, from: CommandKey, to: CommandKey) {
+        let planner = self.id;
+        for command in self.commands.values_mut() {
+            for arg in command.call.args.iter_mut() {
+                replace_value(arg, planner, from, to);
+            }
+        }
+    }
+
+    fn remove_unused_staticcalls(&mut self, optimization: &mut Optimization) {
+        // Removing a command may leave the commands it consumed unused
+        loop {
+            let mut used = BTreeSet::new();
+            consumed(self, self, &mut used);
+
+            let unused: Vec<CommandKey> = self
+                .command_entries()
+                .filter(|(key, command)| {
+                    is_staticcall(command)
+                        && command.call.return_type != DynSolType::Tuple(vec![])
+                        && !used.contains(key)
+                })
+                .map(|(key, _)| key)
+                .collect();
+
+            if unused.is_empty() {
+                break;
+            }
+
+            for key in unused {
+                self.remove_command(key);
+                optimization.removed += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::{erc20::ERC20, events::Events, math::Math};
+    use crate::helpers::assertions::Assertions;
+    use crate::view::ArgView;
+    use alloy::primitives::{Address, U256, address};
+
+    fn token() -> Address {
+        address!("0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2")
+    }
+
+    fn holder() -> Address {
+        address!("0x4d5F47FA6A74757f35C14fD3a6Ef8E3C9BC514E8")
+    }
+
+    fn events() -> Address {
+        address!("0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee")
+    }
+
+    fn balance_of(planner: &mut Planner<'_>) -> ReturnValue {
+        planner
+            .staticcall_address::<ERC20::balanceOfCall>(token(), vec![holder().into()])
+            .unwrap()
+    }
+
+    fn log(planner: &mut Planner<'_>, ret: ReturnValue) {
+        planner
+            .call_address::<Events::logUintCall>(events(), vec![ret.into()])
+            .unwrap();
+    }
+
+    #[test]
+    fn merges_duplicate_staticcalls() {
+        let mut planner = Planner::default();
+        let first = balance_of(&mut planner);
+        let second = balance_of(&mut planner);
+        let sum = planner
+            .staticcall_address::<Math::addCall>(
+                events(),
+                vec![first.clone().into(), second.clone().into()],
+            )
+            .unwrap();
+        log(&mut planner, sum);
+
+        let optimization = planner.optimize();
+        assert_eq!(optimization.merged, 1);
+        assert_eq!(optimization.removed, 0);
+        assert_eq!(optimization.resolve(&second), first);
+
+        let mut expected = Planner::default();
+        let balance = balance_of(&mut expected);
+        let sum = expected
+            .staticcall_address::<Math::addCall>(
+                events(),
+                vec![balance.clone().into(), balance.into()],
+            )
+            .unwrap();
+        log(&mut expected, sum);
+
+        assert_eq!(planner.plan().unwrap(), expected.plan().unwrap());
+    }
+
+    #[test]
+    fn keeps_duplicates_across_state_changes() {
+        let mut planner = Planner::default();
+        let before = balance_of(&mut planner);
+        log(&mut planner, before);
+        let after = balance_of(&mut planner);
+        log(&mut planner, after);
+
+        let optimization = planner.optimize();
+        assert_eq!(optimization, Optimization::default());
+        assert_eq!(planner.commands().len(), 4);
+    }
+
+    #[test]
+    fn removes_unused_staticcalls_transitively() {
+        let mut planner = Planner::default();
+        let balance = balance_of(&mut planner);
+        planner
+            .staticcall_address::<Math::addCall>(
+                events(),
+                vec![balance.into(), U256::from(1).into()],
+            )
+            .unwrap();
+        let kept = planner
+            .staticcall_address::<Math::mulCall>(
+                events(),
+                vec![U256::from(2).into(), U256::from(3).into()],
+            )
+            .unwrap();
+        log(&mut planner, kept.clone());
+
+        let optimization = planner.optimize();
+        assert_eq!(optimization.removed, 2);
+
+        let commands = planner.commands();
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0].output, kept);
+
+        // Commands planned afterwards still come last
+        let late = balance_of(&mut planner);
+        assert_eq!(planner.commands()[2].output, late);
+    }
+
+    #[test]
+    fn keeps_staticcalls_without_outputs() {
+        let mut planner = Planner::default();
+        let balance = balance_of(&mut planner);
+        planner
+            .staticcall_address::<Assertions::assertGteCall>(
+                events(),
+                vec![
+                    balance.into(),
+                    U256::from(1).into(),
+                    String::from("no balance").into(),
+                ],
+            )
+            .unwrap();
+
+        let optimization = planner.optimize();
+        assert_eq!(optimization.removed, 0);
+        assert_eq!(planner.commands().len(), 2);
+    }
+
+    #[test]
+    fn merging_leaves_other_planners_outputs_alone() {
+        let mut other = Planner::default();
+        balance_of(&mut other);
+        let foreign = balance_of(&mut other);
+
+        let mut planner = Planner::default();
+        let first = balance_of(&mut planner);
+        let second = balance_of(&mut planner);
+        assert_eq!(foreign.command, second.command);
+        let sum = planner
+            .staticcall_address::<Math::addCall>(
+                events(),
+                vec![foreign.clone().into(), second.into()],
+            )
+            .unwrap();
+        log(&mut planner, sum);
+
+        let optimization = planner.optimize();
+        assert_eq!(optimization.merged, 1);
+        assert_eq!(optimization.resolve(&foreign), foreign);
+        assert!(matches!(
+            &planner.commands()[1].args[..],
+            [ArgView::Return(a), ArgView::Return(b)] if *a == foreign && *b == first
+        ));
+    }
+
+    #[test]
+    fn replaces_returns_inside_composites() {
+        let mut planner = Planner::default();
+        let first = balance_of(&mut planner);
+        let second = balance_of(&mut planner);
+
+        let mut value = Value::array([second.clone().into(), U256::from(1).into()]);
+        replace_value(&mut value, planner.id, second.command, first.command);
+        let Value::Composite(composite) = value else {
+            panic!("expected a composite");
+        };
+        assert!(matches!(
+            composite.elements(),
+            [Value::Return(ret), Value::Literal(_)] if *ret == first
+        ));
+    }
+}
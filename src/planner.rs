@@ -1,33 +1,76 @@
+use crate::bindings::testable_vm::TestableVM::executeCall;
 use crate::calls::FunctionCall;
 use crate::cmds::{Command, CommandFlags, CommandType, Literal, ReturnValue, Value};
-use crate::error::WeirollError;
+use crate::dialect::{CommandParts, Slot, VmDialect};
+use crate::error::{CommandContext, WeirollError};
+use crate::fields::CallFields;
+use crate::view::CallType;
 
 use alloy::dyn_abi::DynSolType;
 use alloy::dyn_abi::DynSolValue;
 use alloy::primitives::{Address, Bytes, FixedBytes, U256};
 use alloy::sol_types::{SolCall, SolType};
-use bytes::BufMut;
-use bytes::BytesMut;
 #[allow(deprecated)]
 use slotmap::{DefaultKey, HopSlotMap};
 use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 
-type CommandKey = DefaultKey;
+pub(crate) type CommandKey = DefaultKey;
+
+/// Tells planners apart, as a [`CommandKey`] is only unique within the planner which made it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct PlannerId(u64);
+
+impl Default for PlannerId {
+    fn default() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
 
 #[derive(Debug, Default)]
 pub struct Planner<'a> {
     #[allow(deprecated)]
-    commands: HopSlotMap<CommandKey, Command<'a>>,
+    pub(crate) commands: HopSlotMap<CommandKey, Command<'a>>,
+    // The slotmap reuses the slots of removed commands, so plan order is tracked separately.
+    pub(crate) order: Vec<CommandKey>,
+    // Assembles composite arguments, see `set_builder`
+    pub(crate) builder: Option<Address>,
+    // Encodes commands, see `set_dialect`
+    pub(crate) dialect: Option<Arc<dyn VmDialect>>,
+    // Runs subplans which may revert, see `set_try_catch`
+    pub(crate) try_catch: Option<Address>,
+    // Selects which branch runs, see `set_conditional`
+    pub(crate) conditional: Option<Address>,
+    // Runs loop bodies, see `set_loop` and `set_max_iterations`
+    pub(crate) loop_helper: Option<Address>,
+    pub(crate) max_iterations: Option<usize>,
+    // Stores outputs consumed across the cuts of `split`, see `set_carry`
+    pub(crate) carry: Option<Address>,
+    // Commands which write their output to the slot of another output, by its planner and key,
+    // see `if_then_else` and `fold`
+    pub(crate) merged: BTreeMap<CommandKey, (PlannerId, CommandKey)>,
+    // Stamped on the return values of this planner's commands
+    pub(crate) id: PlannerId,
 }
 
 #[derive(Debug, Default)]
 pub struct PlannerState {
-    return_slot_map: BTreeMap<CommandKey, u8>,
-    literal_slot_map: BTreeMap<Literal, u8>,
+    pub(crate) return_slot_map: BTreeMap<CommandKey, u8>,
+    pub(crate) literal_slot_map: BTreeMap<Literal, u8>,
     free_slots: Vec<u8>,
     state_expirations: BTreeMap<CommandKey, Vec<u8>>,
     command_visibility: BTreeMap<CommandKey, CommandKey>,
-    state: Vec<Bytes>,
+    // When set, every call output gets its own slot which is never reused, so
+    // it can be read back from the final state (used for simulation).
+    retain_returns: bool,
+    pub(crate) state: Vec<Bytes>,
+    // The slots each command reads its arguments from and writes its output to, by the indices
+    // of the subplan commands enclosing it followed by its own index
+    pub(crate) command_slots: BTreeMap<Vec<usize>, (Vec<Slot>, Slot)>,
+    // Indices of the subplan commands enclosing the commands being built
+    subplan: Vec<usize>,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -52,29 +95,123 @@ impl<'a> Planner<'a> {
     where
         C: SolCall,
     {
+        let args = Self::sol_call_values(&call)?
+            .into_iter()
+            .map(|v| Value::Literal(Literal::from(v)))
+            .collect();
+
+        self.call_address::<C>(address, args)
+    }
+
+    /// Decodes the arguments of `call` into one value per parameter.
+    fn sol_call_values<C: SolCall>(call: &C) -> Result<Vec<DynSolValue>, WeirollError> {
         let params_type: DynSolType = <C::Parameters<'_> as SolType>::SOL_NAME.parse()?;
 
         let mut encoded_args = Vec::new();
-        <C as SolCall>::abi_encode_raw(&call, &mut encoded_args);
+        <C as SolCall>::abi_encode_raw(call, &mut encoded_args);
 
         let decoded = match params_type {
             DynSolType::Tuple(_) => params_type.abi_decode_sequence(&encoded_args)?,
             other => other.abi_decode(&encoded_args)?,
         };
 
-        let values: Vec<DynSolValue> = match decoded {
+        Ok(match decoded {
             DynSolValue::Tuple(v) => v,
             v => vec![v],
+        })
+    }
+
+    /// Resolves struct-literal fields into positional arguments.
+    ///
+    /// Each [`ReturnValue`] is placed at the parameter whose value differs between the base call
+    /// and its probe, and must produce the same Solidity type as that parameter.
+    fn call_fields_args<C: SolCall>(
+        &self,
+        address: Address,
+        fields: CallFields<C>,
+    ) -> Result<Vec<Value<'a>>, WeirollError> {
+        let command = self.next_context(address, C::SELECTOR);
+        let params_type: DynSolType = <C::Parameters<'_> as SolType>::SOL_NAME.parse()?;
+        let param_types = match params_type {
+            DynSolType::Tuple(types) => types,
+            other => vec![other],
         };
 
-        let args = values
-            .into_iter()
+        let base = Self::sol_call_values(&fields.base)?;
+        let mut args: Vec<Value> = base
+            .iter()
+            .cloned()
             .map(|v| Value::Literal(Literal::from(v)))
             .collect();
 
+        for (ret, probe) in fields.returns {
+            let probe = Self::sol_call_values(&probe)?;
+            let index = base
+                .iter()
+                .zip(probe.iter())
+                .position(|(a, b)| a != b)
+                .ok_or(WeirollError::UnresolvedField { command })?;
+
+            if let Some(return_type) = self.return_type(ret.command)
+                && *return_type != param_types[index]
+            {
+                return Err(WeirollError::ArgumentTypeMismatch {
+                    command,
+                    argument: index,
+                    expected: param_types[index].clone(),
+                    actual: return_type.clone(),
+                });
+            }
+
+            args[index] = Value::Return(ret);
+        }
+
+        Ok(args)
+    }
+
+    /// Plans a CALL from struct-literal fields, which may include [`ReturnValue`]s.
+    ///
+    /// Usually invoked through [`call_contract!`](crate::call_contract).
+    pub fn call_fields<C: SolCall>(
+        &mut self,
+        address: Address,
+        fields: CallFields<C>,
+    ) -> Result<ReturnValue, WeirollError> {
+        let args = self.call_fields_args(address, fields)?;
         self.call_address::<C>(address, args)
     }
 
+    /// Plans a DELEGATECALL from struct-literal fields, which may include [`ReturnValue`]s.
+    pub fn delegatecall_fields<C: SolCall>(
+        &mut self,
+        address: Address,
+        fields: CallFields<C>,
+    ) -> Result<ReturnValue, WeirollError> {
+        let args = self.call_fields_args(address, fields)?;
+        self.delegatecall_address::<C>(address, args)
+    }
+
+    /// Plans a STATICCALL from struct-literal fields, which may include [`ReturnValue`]s.
+    pub fn staticcall_fields<C: SolCall>(
+        &mut self,
+        address: Address,
+        fields: CallFields<C>,
+    ) -> Result<ReturnValue, WeirollError> {
+        let args = self.call_fields_args(address, fields)?;
+        self.staticcall_address::<C>(address, args)
+    }
+
+    /// Plans a CALL with value from struct-literal fields, which may include [`ReturnValue`]s.
+    pub fn call_fields_with_value<C: SolCall>(
+        &mut self,
+        address: Address,
+        value: U256,
+        fields: CallFields<C>,
+    ) -> Result<ReturnValue, WeirollError> {
+        let args = self.call_fields_args(address, fields)?;
+        self.call_address_with_value::<C>(address, value, args)
+    }
+
     pub fn delegatecall_sol<C>(
         &mut self,
         address: Address,
@@ -106,22 +243,7 @@ impl<'a> Planner<'a> {
     where
         C: SolCall,
     {
-        let params_type: DynSolType = <C::Parameters<'_> as SolType>::SOL_NAME.parse()?;
-
-        let mut encoded_args = Vec::new();
-        <C as SolCall>::abi_encode_raw(&call, &mut encoded_args);
-
-        let decoded = match params_type {
-            DynSolType::Tuple(_) => params_type.abi_decode_sequence(&encoded_args)?,
-            other => other.abi_decode(&encoded_args)?,
-        };
-
-        let values: Vec<DynSolValue> = match decoded {
-            DynSolValue::Tuple(v) => v,
-            v => vec![v],
-        };
-
-        let args = values
+        let args = Self::sol_call_values(&call)?
             .into_iter()
             .map(|v| Value::Literal(Literal::from(v)))
             .collect();
@@ -138,22 +260,7 @@ impl<'a> Planner<'a> {
     where
         C: SolCall,
     {
-        let params_type: DynSolType = <C::Parameters<'_> as SolType>::SOL_NAME.parse()?;
-
-        let mut encoded_args = Vec::new();
-        <C as SolCall>::abi_encode_raw(&call, &mut encoded_args);
-
-        let decoded = match params_type {
-            DynSolType::Tuple(_) => params_type.abi_decode_sequence(&encoded_args)?,
-            other => other.abi_decode(&encoded_args)?,
-        };
-
-        let values: Vec<DynSolValue> = match decoded {
-            DynSolValue::Tuple(v) => v,
-            v => vec![v],
-        };
-
-        let args = values
+        let args = Self::sol_call_values(&call)?
             .into_iter()
             .map(|v| Value::Literal(Literal::from(v)))
             .collect();
@@ -264,6 +371,7 @@ impl<'a> Planner<'a> {
         return_type: DynSolType,
         calltype: CallKind,
     ) -> Result<ReturnValue, WeirollError> {
+        let args = self.expand_args(args)?;
         let dynamic = return_type.is_dynamic();
         let call = FunctionCall {
             address,
@@ -274,12 +382,17 @@ impl<'a> Planner<'a> {
             return_type,
         };
 
-        let command = self.commands.insert(Command {
+        let command = self.push_command(Command {
             call,
             kind: CommandType::Call,
+            label: None,
         });
 
-        Ok(ReturnValue { command, dynamic })
+        Ok(ReturnValue {
+            dynamic,
+            command,
+            planner: self.id,
+        })
     }
 
     fn insert_call_with_value<C: SolCall>(
@@ -289,6 +402,7 @@ impl<'a> Planner<'a> {
         return_type: DynSolType,
         value: U256,
     ) -> Result<ReturnValue, WeirollError> {
+        let args = self.expand_args(args)?;
         let dynamic = return_type.is_dynamic();
         let call = FunctionCall {
             address,
@@ -299,12 +413,32 @@ impl<'a> Planner<'a> {
             return_type,
         };
 
-        let command = self.commands.insert(Command {
+        let command = self.push_command(Command {
             call,
             kind: CommandType::Call,
+            label: None,
         });
 
-        Ok(ReturnValue { command, dynamic })
+        Ok(ReturnValue {
+            dynamic,
+            command,
+            planner: self.id,
+        })
+    }
+
+    /// Adds a subplan, taking the return type from `C`.
+    pub fn add_subplan_address<C: SolCall>(
+        &mut self,
+        address: Address,
+        args: Vec<Value<'a>>,
+    ) -> Result<ReturnValue, WeirollError> {
+        let return_type: DynSolType = <C::ReturnTuple<'_> as SolType>::SOL_NAME.parse()?;
+        let return_type = match return_type {
+            DynSolType::Tuple(mut elems) if elems.len() == 1 => elems.remove(0),
+            other => other,
+        };
+
+        self.add_subplan::<C>(address, args, return_type)
     }
 
     pub fn add_subplan<C: SolCall>(
@@ -313,26 +447,64 @@ impl<'a> Planner<'a> {
         args: Vec<Value<'a>>,
         return_type: DynSolType,
     ) -> Result<ReturnValue, WeirollError> {
+        let args = self.expand_args(args)?;
         let dynamic = return_type.is_dynamic();
 
-        let mut has_subplan = false;
-        let mut has_state = false;
+        let command = self.next_context(address, C::SELECTOR);
 
-        if args.len() != 2 {
-            return Err(WeirollError::ArgumentCountMismatch);
+        // The subplan and the state, plus any other parameters the function declares
+        let params = match <C::Parameters<'_> as SolType>::SOL_NAME.parse()? {
+            DynSolType::Tuple(types) => types.len(),
+            _ => 1,
+        };
+        if args.len() != params.max(2) {
+            return Err(WeirollError::ArgumentCountMismatch {
+                command,
+                expected: params.max(2),
+                actual: args.len(),
+            });
         }
+        Self::check_subplan_args(command, &args)?;
+
+        let command = self.push_command(Command {
+            call: FunctionCall {
+                address,
+                flags: CommandFlags::DELEGATECALL,
+                value: None,
+                selector: C::SELECTOR,
+                args,
+                return_type,
+            },
+            kind: CommandType::SubPlan,
+            label: None,
+        });
+
+        Ok(ReturnValue {
+            dynamic,
+            command,
+            planner: self.id,
+        })
+    }
+
+    /// Checks that the arguments of a subplan command include exactly one subplan and one state.
+    pub(crate) fn check_subplan_args(
+        command: CommandContext,
+        args: &[Value<'a>],
+    ) -> Result<(), WeirollError> {
+        let mut has_subplan = false;
+        let mut has_state = false;
 
         for arg in args.iter() {
             match arg {
-                Value::Subplan(_planner) => {
+                Value::Subplan(_) | Value::OwnedSubplan(_) => {
                     if has_subplan {
-                        return Err(WeirollError::MultipleSubplans);
+                        return Err(WeirollError::MultipleSubplans { command });
                     }
                     has_subplan = true;
                 }
                 Value::State(_state) => {
                     if has_state {
-                        return Err(WeirollError::MultipleState);
+                        return Err(WeirollError::MultipleState { command });
                     }
                     has_state = true;
                 }
@@ -341,25 +513,18 @@ impl<'a> Planner<'a> {
         }
 
         if !has_subplan || !has_state {
-            return Err(WeirollError::MissingStateOrSubplan);
+            return Err(WeirollError::MissingStateOrSubplan { command });
         }
 
-        let command = self.commands.insert(Command {
-            call: FunctionCall {
-                address,
-                flags: CommandFlags::DELEGATECALL,
-                value: None,
-                selector: C::SELECTOR,
-                args,
-                return_type,
-            },
-            kind: CommandType::SubPlan,
-        });
-
-        Ok(ReturnValue { dynamic, command })
+        Ok(())
     }
 
-    pub fn replace_state<C: SolCall>(&mut self, address: Address, args: Vec<Value<'a>>) {
+    pub fn replace_state<C: SolCall>(
+        &mut self,
+        address: Address,
+        args: Vec<Value<'a>>,
+    ) -> Result<(), WeirollError> {
+        let args = self.expand_args(args)?;
         let call = FunctionCall {
             address,
             flags: CommandFlags::DELEGATECALL,
@@ -368,179 +533,206 @@ impl<'a> Planner<'a> {
             args,
             return_type: DynSolType::Array(Box::new(DynSolType::Bytes)),
         };
-        self.commands.insert(Command {
+        self.push_command(Command {
             call,
             kind: CommandType::RawCall,
+            label: None,
         });
+        Ok(())
     }
 
     fn build_command_args(
         &self,
+        context: CommandContext,
         command: &Command,
         return_slot_map: &BTreeMap<CommandKey, u8>,
         literal_slot_map: &BTreeMap<Literal, u8>,
         state: &Vec<Bytes>,
-    ) -> Result<Vec<u8>, WeirollError> {
+    ) -> Result<Vec<Slot>, WeirollError> {
         let in_args = Vec::from_iter(command.call.args.iter());
         let mut extra_args: Vec<Value> = vec![];
         if command.call.flags & CommandFlags::CALLTYPE_MASK == CommandFlags::CALL_WITH_VALUE {
             if let Some(value) = command.call.value {
                 extra_args.push(Value::Literal(value.into()));
             } else {
-                return Err(WeirollError::MissingValue);
+                return Err(WeirollError::MissingValue { command: context });
             }
         }
 
         let mut args = vec![];
         // NOTE: for CALL_WITH_VALUE, the value is treated as the first argument.
-        for arg in extra_args.iter().chain(in_args.into_iter()) {
-            let mut slot = match arg {
+        for (argument, arg) in extra_args.iter().chain(in_args).enumerate() {
+            // Errors number the caller's arguments, not the injected value
+            let argument = argument.saturating_sub(extra_args.len());
+            let index = match arg {
                 Value::Return(val) => {
                     if let Some(slot) = return_slot_map.get(&val.command) {
                         *slot
                     } else {
-                        return Err(WeirollError::MissingReturnSlot);
+                        return Err(WeirollError::MissingReturnSlot {
+                            command: context,
+                            argument,
+                        });
                     }
                 }
                 Value::Literal(val) => {
                     if let Some(slot) = literal_slot_map.get(val) {
                         *slot
                     } else {
-                        return Err(WeirollError::MissingLiteralValue);
+                        return Err(WeirollError::MissingLiteralValue {
+                            command: context,
+                            argument,
+                        });
                     }
                 }
                 Value::State(_) => {
-                    tracing::debug!("added state value, using 0xfe return slot");
-                    0xFE
+                    tracing::debug!("added state value, using the whole state");
+                    args.push(Slot::State);
+                    continue;
                 }
-                Value::Subplan(_) => {
+                Value::Composite(_) => return Err(WeirollError::MissingBuilder),
+                Value::Subplan(_) | Value::OwnedSubplan(_) => {
                     tracing::debug!("added state value {state:?}");
                     // buildCommands has already built the subplan and put it in the last state slot
                     (state.len() - 1).try_into()?
                 }
             };
-            // todo- correct??
-            if arg.is_dynamic_type() {
-                slot |= 0x80;
-            }
 
-            args.push(slot);
+            args.push(if arg.is_dynamic_type() {
+                Slot::Dynamic(index)
+            } else {
+                Slot::Static(index)
+            });
         }
 
         Ok(args)
     }
 
-    fn build_commands(&self, ps: &mut PlannerState) -> Result<Vec<FixedBytes<32>>, WeirollError> {
+    fn output_slot(command: &Command, index: u8) -> Slot {
+        if command.call.return_type.is_dynamic() {
+            Slot::Dynamic(index)
+        } else {
+            Slot::Static(index)
+        }
+    }
+
+    fn build_commands(
+        &self,
+        ps: &mut PlannerState,
+        dialect: &dyn VmDialect,
+    ) -> Result<Vec<(CommandKey, FixedBytes<32>)>, WeirollError> {
         let mut encoded_commands = vec![];
 
         // Build commands, and add state entries as needed
-        for (cmd_key, command) in &self.commands {
+        for (index, (cmd_key, command)) in self.command_entries().enumerate() {
+            let context = Self::context(index, command);
+
             if command.kind == CommandType::SubPlan {
                 // Find the subplan
                 let subplanner = command
                     .call
                     .args
                     .iter()
-                    .find_map(|arg| match arg {
-                        Value::Subplan(planner) => Some(planner),
-                        _ => None,
-                    })
-                    .ok_or(WeirollError::MissingSubplan)?;
+                    .find_map(Value::subplan)
+                    .ok_or(WeirollError::MissingSubplan { command: context })?;
 
                 // Build a list of commands
-                let subcommands = subplanner.build_commands(ps)?;
-
-                // Push the commands onto the state
-                ps.state.push(subcommands[0].clone().to_vec().into());
+                ps.subplan.push(index);
+                let subcommands = subplanner.build_commands(ps, dialect);
+                ps.subplan.pop();
+                let subcommands = subcommands.map_err(|source| WeirollError::Subplan {
+                    command: context,
+                    source: Box::new(source),
+                })?;
+
+                // Push the commands onto the state, as the tail of a `bytes32[]` argument
+                let words = subcommands
+                    .into_iter()
+                    .map(|(_, word)| DynSolValue::FixedBytes(word, 32))
+                    .collect();
+                let mut encoded = DynSolValue::Array(words).abi_encode();
+                encoded.drain(..32);
+                ps.state.push(encoded.into());
 
                 // The slot is no longer needed after this command
                 ps.free_slots.push((ps.state.len() - 1).try_into()?);
             }
 
-            let mut flags = command.call.flags;
-
-            let mut args = self.build_command_args(
+            let args = self.build_command_args(
+                context,
                 command,
                 &ps.return_slot_map,
                 &ps.literal_slot_map,
                 &ps.state,
             )?;
 
-            if args.len() > 6 {
-                flags |= CommandFlags::EXTENDED_COMMAND;
-            }
-
             // Add any expired state entries to free slots
             if let Some(expr) = ps.state_expirations.get(&cmd_key) {
                 ps.free_slots.extend(expr.iter().copied())
             };
 
             // Figure out where to put the return value
-            let mut ret = 0xff;
-            if let Some(expiry) = ps.command_visibility.get(&cmd_key) {
+            let mut output = Slot::Unused;
+            if let Some((_, target)) = self.merged.get(&cmd_key) {
+                // The other branch's output is still live, so share its slot
+                if let Some(ret) = ps.return_slot_map.get(target).copied() {
+                    ps.return_slot_map.insert(cmd_key, ret);
+                    output = Self::output_slot(command, ret);
+                }
+            } else if let Some(expiry) = ps.command_visibility.get(&cmd_key) {
                 if let CommandType::RawCall | CommandType::SubPlan = command.kind {
-                    return Err(WeirollError::InvalidReturnSlot);
+                    return Err(WeirollError::InvalidReturnSlot { command: context });
                 }
 
-                ret = ps.state.len().try_into()?;
+                let mut ret = ps.state.len().try_into()?;
                 if let Some(slot) = ps.free_slots.pop() {
                     ret = slot;
                 }
 
                 ps.return_slot_map.insert(cmd_key, ret);
 
-                ps.state_expirations.entry(*expiry).or_default().push(ret);
+                if !ps.retain_returns {
+                    ps.state_expirations.entry(*expiry).or_default().push(ret);
+                }
 
                 if ret == u8::try_from(ps.state.len())? {
                     ps.state.push(Bytes::default());
                 }
 
-                // todo: what's this?
-                if command.call.return_type.is_dynamic() {
-                    tracing::debug!("ret type is dynamic, set ret to 0x80");
-                    ret |= 0x80;
-                }
+                output = Self::output_slot(command, ret);
             } else if let CommandType::RawCall | CommandType::SubPlan = command.kind {
-                // todo: what's this?
-                // if command.call.fragment.outputs.len() == 1 {}
-                tracing::debug!("call is raw or subplan, set ret to 0xfe");
-                ret = 0xfe;
-            }
-
-            if (flags & CommandFlags::EXTENDED_COMMAND) == CommandFlags::EXTENDED_COMMAND {
-                // Extended command
-                let mut cmd = BytesMut::with_capacity(32);
-
-                cmd.put(&command.call.selector[..]);
-                cmd.put(&flags.bits().to_le_bytes()[..]);
-                cmd.put(&[0u8; 6][..]);
-                cmd.put_u8(ret);
-                cmd.put(&command.call.address.0.0[..]);
-
-                // push first command, indicating extended cmd
-                let word: [u8; 32] = cmd.to_vec().try_into().unwrap();
-                encoded_commands.push(word.into());
-
-                // use the next command for the actual args
-                args.resize(32, 0xff);
-                let word: [u8; 32] = args.try_into().unwrap();
-                encoded_commands.push(word.into());
-            } else {
-                // Standard command
-                let mut cmd = BytesMut::with_capacity(32);
-
-                cmd.put(&command.call.selector[..]);
-                cmd.put(&flags.bits().to_le_bytes()[..]);
+                tracing::debug!("call is raw or subplan, writing to the whole state");
+                output = Slot::State;
+            } else if ps.retain_returns && Self::has_single_output(&command.call) {
+                let ret = ps.state.len().try_into()?;
+                ps.return_slot_map.insert(cmd_key, ret);
+                ps.state.push(Bytes::default());
 
-                // pad args to 6
-                args.resize(6, 0xff);
+                output = Self::output_slot(command, ret);
+            }
 
-                cmd.put(&args[..]);
-                cmd.put_u8(ret.to_le());
-                cmd.put(&command.call.address.0.0[..]);
+            let flags = command.call.flags;
+            let parts = CommandParts {
+                context,
+                selector: command.call.selector.into(),
+                call_type: CallType::from_flags(flags),
+                tuple_return: flags.contains(CommandFlags::TUPLE_RETURN),
+                extra_flags: flags.bits()
+                    & !(CommandFlags::CALLTYPE_MASK
+                        | CommandFlags::EXTENDED_COMMAND
+                        | CommandFlags::TUPLE_RETURN)
+                        .bits(),
+                args,
+                output,
+                target: command.call.address,
+            };
 
-                let word: [u8; 32] = cmd.to_vec().try_into().unwrap();
-                encoded_commands.push(word.into());
+            dialect.validate(&parts)?;
+            let path = [ps.subplan.as_slice(), &[index]].concat();
+            ps.command_slots
+                .insert(path, (parts.args.clone(), parts.output));
+            for word in dialect.encode(&parts)? {
+                encoded_commands.push((cmd_key, word));
             }
         }
 
@@ -553,7 +745,8 @@ impl<'a> Planner<'a> {
         command_visibility: &mut BTreeMap<CommandKey, CommandKey>,
         seen: &mut BTreeSet<CommandKey>,
     ) -> Result<(), WeirollError> {
-        for (cmd_key, command) in &self.commands {
+        for (index, (cmd_key, command)) in self.command_entries().enumerate() {
+            let context = Self::context(index, command);
             let in_args = &command.call.args;
             let mut extra_args = vec![];
 
@@ -561,16 +754,21 @@ impl<'a> Planner<'a> {
                 if let Some(value) = command.call.value {
                     extra_args.push(value.into());
                 } else {
-                    return Err(WeirollError::MissingValue);
+                    return Err(WeirollError::MissingValue { command: context });
                 }
             }
 
             // NOTE: for CALL_WITH_VALUE, the value is treated as the first argument.
-            for arg in extra_args.iter().chain(in_args.iter()) {
+            for (argument, arg) in extra_args.iter().chain(in_args.iter()).enumerate() {
+                // Errors number the caller's arguments, not the injected value
+                let argument = argument.saturating_sub(extra_args.len());
                 match arg {
                     Value::Return(val) => {
                         if !seen.contains(&val.command) {
-                            return Err(WeirollError::CommandNotVisible);
+                            return Err(WeirollError::CommandNotVisible {
+                                command: context,
+                                argument,
+                            });
                         }
                         command_visibility.insert(val.command, cmd_key);
                     }
@@ -580,15 +778,44 @@ impl<'a> Planner<'a> {
                         literal_visibility.push((val.clone(), cmd_key));
                     }
                     Value::State(_) => {}
-                    Value::Subplan(subplan) => {
+                    Value::Composite(_) => return Err(WeirollError::MissingBuilder),
+                    Value::Subplan(_) | Value::OwnedSubplan(_) => {
                         // let mut subplan_seen = Default::default();
-                        if command.call.return_type.is_dynamic() {
-                            subplan.preplan(literal_visibility, command_visibility, seen)?;
+                        if let Some(subplan) = arg.subplan()
+                            && command.call.return_type.is_dynamic()
+                        {
+                            let outside = seen.clone();
+                            subplan
+                                .preplan(literal_visibility, command_visibility, seen)
+                                .map_err(|source| WeirollError::Subplan {
+                                    command: context,
+                                    source: Box::new(source),
+                                })?;
+
+                            // A subplan may run repeatedly, so values from outside it must
+                            // outlive it rather than expire part way through
+                            let inside =
+                                |key: &CommandKey| seen.contains(key) && !outside.contains(key);
+                            for (_, last) in literal_visibility.iter_mut() {
+                                if inside(last) {
+                                    *last = cmd_key;
+                                }
+                            }
+                            for (producer, last) in command_visibility.iter_mut() {
+                                if outside.contains(producer) && inside(last) {
+                                    *last = cmd_key;
+                                }
+                            }
                         }
                     }
                 }
             }
 
+            // Keep the other branch's output live until this command has overwritten it
+            if let Some((_, target)) = self.merged.get(&cmd_key) {
+                command_visibility.insert(*target, cmd_key);
+            }
+
             seen.insert(cmd_key);
         }
 
@@ -598,6 +825,25 @@ impl<'a> Planner<'a> {
     }
 
     pub fn plan(&self) -> Result<(Vec<FixedBytes<32>>, Vec<Bytes>), WeirollError> {
+        let (encoded_commands, ps) = self.plan_with_state(false)?;
+        let commands = encoded_commands.into_iter().map(|(_, word)| word).collect();
+
+        Ok((commands, ps.state))
+    }
+
+    /// Plans the program and ABI-encodes it as a call to the VM's `execute(bytes32[],bytes[])`.
+    pub fn execute_calldata(&self) -> Result<Bytes, WeirollError> {
+        let (commands, state) = self.plan()?;
+
+        Ok(executeCall { commands, state }.abi_encode().into())
+    }
+
+    /// Plans the program, also returning the command each encoded word belongs to and the
+    /// final planner state.
+    pub(crate) fn plan_with_state(
+        &self,
+        retain_returns: bool,
+    ) -> Result<(Vec<(CommandKey, FixedBytes<32>)>, PlannerState), WeirollError> {
         // Tracks the last time a literal is used in the program
         let mut literal_visibility = Default::default();
 
@@ -637,19 +883,74 @@ impl<'a> Planner<'a> {
             free_slots: Default::default(),
             state_expirations,
             command_visibility,
+            retain_returns,
             state,
+            command_slots: Default::default(),
+            subplan: Default::default(),
         };
 
-        let encoded_commands = self.build_commands(&mut ps)?;
+        let encoded_commands = self.build_commands(&mut ps, self.dialect())?;
+
+        Ok((encoded_commands, ps))
+    }
+
+    /// Whether the VM can write this call's output into a single state slot.
+    fn has_single_output(call: &FunctionCall) -> bool {
+        !matches!(call.return_type, DynSolType::Tuple(_))
+    }
 
-        Ok((encoded_commands, ps.state))
+    /// Returns the position of `key` in the order commands were planned.
+    pub(crate) fn command_position(&self, key: CommandKey) -> Option<usize> {
+        self.order.iter().position(|k| *k == key)
+    }
+
+    pub(crate) fn return_type(&self, key: CommandKey) -> Option<&DynSolType> {
+        self.commands.get(key).map(|c| &c.call.return_type)
+    }
+
+    pub(crate) fn command_entries(&self) -> impl Iterator<Item = (CommandKey, &Command<'a>)> {
+        self.order.iter().map(|key| (*key, &self.commands[*key]))
+    }
+
+    pub(crate) fn push_command(&mut self, command: Command<'a>) -> CommandKey {
+        let key = self.commands.insert(command);
+        self.order.push(key);
+        key
+    }
+
+    pub(crate) fn remove_command(&mut self, key: CommandKey) -> Option<Command<'a>> {
+        self.order.retain(|k| *k != key);
+        self.commands.remove(key)
+    }
+
+    pub(crate) fn context(index: usize, command: &Command) -> CommandContext {
+        CommandContext {
+            index,
+            selector: command.call.selector.into(),
+            target: command.call.address,
+        }
+    }
+
+    /// Describes the command at `key` for error reporting.
+    pub(crate) fn command_context(&self, key: CommandKey) -> Option<CommandContext> {
+        let index = self.command_position(key)?;
+        Some(Self::context(index, &self.commands[key]))
+    }
+
+    /// Describes the command which would be planned next, for errors raised before insertion.
+    pub(crate) fn next_context(&self, address: Address, selector: [u8; 4]) -> CommandContext {
+        CommandContext {
+            index: self.order.len(),
+            selector: selector.into(),
+            target: address,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::bindings::{math::Math, strings::Strings};
+    use crate::bindings::{erc20::ERC20, math::Math, strings::Strings};
     use alloy::dyn_abi::DynSolValue;
     use alloy::{
         dyn_abi::DynSolType,
@@ -967,6 +1268,66 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_planner_reports_command_not_visible() {
+        let mut other = Planner::default();
+        other
+            .call_address::<Math::addCall>(addr(), vec![U256::from(1).into(), U256::from(2).into()])
+            .unwrap();
+        let foreign = other
+            .call_address::<Math::addCall>(addr(), vec![U256::from(3).into(), U256::from(4).into()])
+            .unwrap();
+
+        let mut planner = Planner::default();
+        planner
+            .call_address::<Math::addCall>(
+                addr(),
+                vec![U256::from(1).into(), foreign.clone().into()],
+            )
+            .unwrap();
+
+        let err = planner.plan().unwrap_err();
+        assert_eq!(
+            err,
+            WeirollError::CommandNotVisible {
+                command: CommandContext {
+                    index: 0,
+                    selector: Math::addCall::SELECTOR.into(),
+                    target: addr(),
+                },
+                argument: 1,
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "command 0 (selector 0x771602f7 on {}): argument 1 uses a return value which is not visible here",
+                addr()
+            )
+        );
+
+        // The value of a CALL_WITH_VALUE is not counted as an argument
+        let mut planner = Planner::default();
+        planner
+            .call_address_with_value::<ERC20::transferCall>(
+                addr(),
+                U256::from(1),
+                vec![addr().into(), foreign.into()],
+            )
+            .unwrap();
+        assert_eq!(
+            planner.plan().unwrap_err(),
+            WeirollError::CommandNotVisible {
+                command: CommandContext {
+                    index: 0,
+                    selector: ERC20::transferCall::SELECTOR.into(),
+                    target: addr(),
+                },
+                argument: 1,
+            }
+        );
+    }
+
     #[test]
     fn test_planner_argument_count_mismatch() {
         let mut planner = Planner::default();
@@ -975,16 +1336,29 @@ mod tests {
             vec![U256::from(1).into()],
             DynSolType::Uint(256),
         );
-        assert_eq!(ret.err(), Some(WeirollError::ArgumentCountMismatch));
+        assert_eq!(
+            ret.err(),
+            Some(WeirollError::ArgumentCountMismatch {
+                command: CommandContext {
+                    index: 0,
+                    selector: Math::addCall::SELECTOR.into(),
+                    target: addr(),
+                },
+                expected: 2,
+                actual: 1,
+            })
+        );
     }
 
     #[test]
     fn test_planner_replace_state() {
         let mut planner = Planner::default();
-        planner.replace_state::<SampleContract::useStateCall>(
-            addr(),
-            vec![Value::State(Default::default())],
-        );
+        planner
+            .replace_state::<SampleContract::useStateCall>(
+                addr(),
+                vec![Value::State(Default::default())],
+            )
+            .expect("replace state");
         let (commands, state) = planner.plan().expect("plan");
         assert_eq!(commands.len(), 1);
         assert_eq!(
@@ -1030,7 +1404,9 @@ mod tests {
         assert_eq!(state[1], DynSolValue::from(U256::from(2)).abi_encode());
         assert_eq!(
             state[2],
-            "0x771602f7010001ffffffffffeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee"
+            // The subplan's commands, as a `bytes32[]` without its offset
+            "0x0000000000000000000000000000000000000000000000000000000000000001\
+             771602f7010001ffffffffffeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee"
                 .parse::<Bytes>()
                 .unwrap()
         );
@@ -0,0 +1,340 @@
+use crate::Planner;
+use crate::bindings::testable_vm::TestableVM;
+use crate::cmds::ReturnValue;
+use crate::error::{CommandContext, ProviderError, WeirollError};
+use crate::planner::CommandKey;
+
+use alloy::dyn_abi::{DynSolType, DynSolValue};
+use alloy::network::{Ethereum, Network, TransactionBuilder};
+use alloy::primitives::{Address, Bytes, FixedBytes, U256};
+use alloy::providers::{PendingTransactionBuilder, Provider};
+use alloy::rpc::types::state::{StateOverride, StateOverridesBuilder};
+use alloy::sol_types::SolCall;
+use alloy::transports::TransportError;
+use std::collections::BTreeMap;
+use std::future::Future;
+
+/// Returns a state override which places the [`TestableVM`] runtime code at `address`.
+///
+/// This allows simulating plans against chains where no VM has been deployed.
+pub fn testable_vm_override(address: Address) -> StateOverride {
+    StateOverridesBuilder::default()
+        .with_code(address, TestableVM::DEPLOYED_BYTECODE.clone())
+        .build()
+}
+
+/// The outcome of [`WeirollProviderExt::simulate_plan`].
+#[derive(Debug)]
+pub struct PlanSimulation {
+    pub(crate) state: Vec<Bytes>,
+    pub(crate) returns: BTreeMap<CommandKey, (u8, DynSolType, CommandContext)>,
+}
+
+impl PlanSimulation {
+    /// The VM state after execution.
+    pub fn state(&self) -> &[Bytes] {
+        &self.state
+    }
+
+    /// Decodes the value produced by the command behind `ret`.
+    pub fn get(&self, ret: &ReturnValue) -> Result<DynSolValue, WeirollError> {
+        let (slot, ty, command) = self
+            .returns
+            .get(&ret.command)
+            .ok_or(WeirollError::UnknownReturnValue)?;
+        let data = self
+            .state
+            .get(usize::from(*slot))
+            .ok_or(WeirollError::InvalidReturnSlot { command: *command })?;
+
+        let decoded = if ty.is_dynamic() {
+            // The VM strips the leading offset word from dynamic return data
+            let mut encoded = Vec::with_capacity(32 + data.len());
+            encoded.extend_from_slice(&U256::from(32).to_be_bytes::<32>());
+            encoded.extend_from_slice(data);
+            ty.abi_decode(&encoded)
+        } else {
+            ty.abi_decode(data)
+        };
+
+        decoded.map_err(|source| WeirollError::ReturnDecode {
+            command: *command,
+            source,
+        })
+    }
+}
+
+/// Extension trait for simulating and sending plans with an alloy [`Provider`].
+pub trait WeirollProviderExt<N: Network = Ethereum>: Provider<N> {
+    /// Executes `planner` on the VM at `vm` with `eth_call`, optionally applying `overrides`.
+    ///
+    /// The plan is built so that every call output keeps its own state slot, which makes all
+    /// [`ReturnValue`]s readable from the result but means the state layout differs from
+    /// [`Planner::plan`].
+    fn simulate_plan(
+        &self,
+        vm: Address,
+        planner: &Planner<'_>,
+        overrides: Option<StateOverride>,
+    ) -> impl Future<Output = Result<PlanSimulation, ProviderError>> + Send;
+
+    /// Sends `planner` to the VM at `vm` as a transaction.
+    fn send_plan(
+        &self,
+        vm: Address,
+        planner: &Planner<'_>,
+    ) -> impl Future<Output = Result<PendingTransactionBuilder<N>, ProviderError>> + Send;
+}
+
+impl<N, P> WeirollProviderExt<N> for P
+where
+    N: Network,
+    P: Provider<N>,
+{
+    fn simulate_plan(
+        &self,
+        vm: Address,
+        planner: &Planner<'_>,
+        overrides: Option<StateOverride>,
+    ) -> impl Future<Output = Result<PlanSimulation, ProviderError>> + Send {
+        let prepared = PreparedPlan::new(planner, true);
+
+        async move {
+            let (prepared, returns) = prepared?;
+            let tx = N::TransactionRequest::default()
+                .with_to(vm)
+                .with_input(prepared.calldata.clone());
+
+            let output = self
+                .call(tx)
+                .overrides_opt(overrides)
+                .await
+                .map_err(|err| prepared.map_error(err))?;
+            let state = TestableVM::executeCall::abi_decode_returns(&output)?;
+
+            Ok(PlanSimulation { state, returns })
+        }
+    }
+
+    fn send_plan(
+        &self,
+        vm: Address,
+        planner: &Planner<'_>,
+    ) -> impl Future<Output = Result<PendingTransactionBuilder<N>, ProviderError>> + Send {
+        let prepared = PreparedPlan::new(planner, false);
+
+        async move {
+            let (prepared, _) = prepared?;
+            let tx = N::TransactionRequest::default()
+                .with_to(vm)
+                .with_input(prepared.calldata.clone());
+
+            self.send_transaction(tx)
+                .await
+                .map_err(|err| prepared.map_error(err))
+        }
+    }
+}
+
+/// `execute` calldata, along with the planner command each command word came from and its
+/// target.
+pub(crate) struct PreparedPlan {
+    pub(crate) calldata: Bytes,
+    command_positions: Vec<(Option<usize>, Address)>,
+}
+
+impl PreparedPlan {
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn new(
+        planner: &Planner<'_>,
+        retain_returns: bool,
+    ) -> Result<(Self, BTreeMap<CommandKey, (u8, DynSolType, CommandContext)>), WeirollError> {
+        let (encoded, ps) = planner.plan_with_state(retain_returns)?;
+
+        let command_positions = encoded
+            .iter()
+            .map(|(key, word)| {
+                (
+                    planner.command_position(*key),
+                    Address::from_slice(&word[12..]),
+                )
+            })
+            .collect();
+        let commands: Vec<FixedBytes<32>> = encoded.into_iter().map(|(_, word)| word).collect();
+
+        let returns = ps
+            .return_slot_map
+            .iter()
+            .filter_map(|(key, slot)| {
+                let ty = planner.return_type(*key)?;
+                let context = planner.command_context(*key)?;
+                Some((*key, (*slot, ty.clone(), context)))
+            })
+            .collect();
+
+        let calldata = TestableVM::executeCall {
+            commands,
+            state: ps.state,
+        }
+        .abi_encode()
+        .into();
+
+        Ok((
+            Self {
+                calldata,
+                command_positions,
+            },
+            returns,
+        ))
+    }
+
+    /// Maps an `ExecutionFailed` revert back to the planner command that caused it.
+    fn map_error(&self, err: TransportError) -> ProviderError {
+        let failure = err
+            .as_error_resp()
+            .and_then(|resp| resp.as_decoded_error::<TestableVM::ExecutionFailed>());
+
+        match failure {
+            Some(failure) => self.execution_failed(failure),
+            None => ProviderError::Transport(err),
+        }
+    }
+
+    /// Maps an `ExecutionFailed` revert back to the planner command that caused it.
+    ///
+    /// The VM reports `command_index` 0 for every failure, so the command is found by its target
+    /// instead: the only command word calling `target`, or else the reported one if it does.
+    pub(crate) fn execution_failed(&self, failure: TestableVM::ExecutionFailed) -> ProviderError {
+        let command_index = failure.command_index.saturating_to::<usize>();
+        let mut calling = self
+            .command_positions
+            .iter()
+            .filter(|(_, target)| *target == failure.target);
+        let command = match (calling.next(), calling.next()) {
+            (Some((position, _)), None) => *position,
+            _ => self
+                .command_positions
+                .get(command_index)
+                .filter(|(_, target)| *target == failure.target)
+                .and_then(|(position, _)| *position),
+        };
+
+        ProviderError::ExecutionFailed {
+            command,
+            command_index,
+            target: failure.target,
+            message: failure.message,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::{math::Math, strings::Strings};
+    use alloy::primitives::address;
+
+    fn addr() -> Address {
+        address!("0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee")
+    }
+
+    #[test]
+    fn prepared_plan_retains_unused_returns() {
+        let mut planner = Planner::default();
+        let sum = planner
+            .call_address::<Math::addCall>(addr(), vec![U256::from(1).into(), U256::from(2).into()])
+            .unwrap();
+        let joined = planner
+            .call_address::<Strings::strcatCall>(
+                addr(),
+                vec![String::from("a").into(), String::from("b").into()],
+            )
+            .unwrap();
+
+        let (_, returns) = PreparedPlan::new(&planner, true).unwrap();
+        assert!(returns.contains_key(&sum.command));
+        assert!(returns.contains_key(&joined.command));
+
+        let (_, returns) = PreparedPlan::new(&planner, false).unwrap();
+        assert!(returns.is_empty());
+    }
+
+    #[test]
+    fn simulation_decodes_static_and_dynamic_returns() {
+        let mut planner = Planner::default();
+        let sum = planner
+            .call_address::<Math::addCall>(addr(), vec![U256::from(1).into(), U256::from(2).into()])
+            .unwrap();
+        let joined = planner
+            .call_address::<Strings::strcatCall>(
+                addr(),
+                vec![String::from("a").into(), String::from("b").into()],
+            )
+            .unwrap();
+
+        let (_, returns) = PreparedPlan::new(&planner, true).unwrap();
+        let mut state = vec![Bytes::default(); 6];
+        state[usize::from(returns[&sum.command].0)] =
+            DynSolValue::from(U256::from(3)).abi_encode().into();
+        state[usize::from(returns[&joined.command].0)] = DynSolValue::from(String::from("ab"))
+            .abi_encode()[32..]
+            .to_vec()
+            .into();
+
+        let simulation = PlanSimulation { state, returns };
+        assert_eq!(
+            simulation.get(&sum).unwrap(),
+            DynSolValue::from(U256::from(3))
+        );
+        assert_eq!(
+            simulation.get(&joined).unwrap(),
+            DynSolValue::from(String::from("ab"))
+        );
+    }
+
+    #[test]
+    fn execution_failure_is_found_by_target() {
+        let other = address!("0x5555555555555555555555555555555555555555");
+        let mut planner = Planner::default();
+        for target in [addr(), other, addr()] {
+            planner
+                .call_address::<Math::addCall>(
+                    target,
+                    vec![U256::from(1).into(), U256::from(2).into()],
+                )
+                .unwrap();
+        }
+        let (prepared, _) = PreparedPlan::new(&planner, false).unwrap();
+
+        let failure = |target| TestableVM::ExecutionFailed {
+            command_index: U256::ZERO,
+            target,
+            message: String::from("failed"),
+        };
+        assert!(matches!(
+            prepared.execution_failed(failure(other)),
+            ProviderError::ExecutionFailed {
+                command: Some(1),
+                command_index: 0,
+                ..
+            }
+        ));
+        assert!(matches!(
+            prepared.execution_failed(failure(addr())),
+            ProviderError::ExecutionFailed {
+                command: Some(0),
+                ..
+            }
+        ));
+
+        let unknown = address!("0x6666666666666666666666666666666666666666");
+        assert_eq!(
+            prepared.execution_failed(failure(other)).to_string(),
+            format!("command 1 calling {other} failed: failed")
+        );
+        assert_eq!(
+            prepared.execution_failed(failure(unknown)).to_string(),
+            format!("command unknown calling {unknown} failed: failed")
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! Calls which are not described by a `SolCall`, such as plain ETH transfers and calls to a
+//! contract's `fallback` function.
+//!
+//! The VM always builds calldata from a 4-byte selector followed by the encoded arguments, so
+//! raw calldata is planned as its selector plus one `bytes32` literal per remaining word:
+//!
+//! ```ignore
+//! planner.transfer_eth(recipient, U256::from(1e18))?;
+//! planner.call_raw_with_value(weth, amount, [0u8; 4], vec![], DynSolType::Tuple(vec![]))?;
+//! ```
+
+use crate::Planner;
+use crate::calls::FunctionCall;
+use crate::cmds::{Command, CommandFlags, CommandType, ReturnValue, Value};
+use crate::error::WeirollError;
+
+use alloy::dyn_abi::DynSolType;
+use alloy::primitives::{Address, B256, U256};
+
+impl<'a> Planner<'a> {
+    /// Sends `amount` wei to `to` with CALL_WITH_VALUE.
+    ///
+    /// The call carries a zero selector and no arguments, so EOAs and contracts with a payable
+    /// `fallback` accept it. Contracts which only implement `receive` will reject it, as the VM
+    /// cannot make a call with empty calldata.
+    pub fn transfer_eth(&mut self, to: Address, amount: U256) -> Result<ReturnValue, WeirollError> {
+        self.insert_raw_call(
+            to,
+            [0; 4],
+            CommandFlags::CALL_WITH_VALUE,
+            Some(amount),
+            vec![],
+            DynSolType::Tuple(vec![]),
+        )
+    }
+
+    /// Plans a CALL with calldata given as raw bytes rather than a `SolCall`.
+    ///
+    /// `calldata` is either a bare selector or a selector followed by whole 32-byte words, which
+    /// are sent ahead of `args`. The output is decoded as `return_type`.
+    pub fn call_raw(
+        &mut self,
+        to: Address,
+        calldata: impl AsRef<[u8]>,
+        args: Vec<Value<'a>>,
+        return_type: DynSolType,
+    ) -> Result<ReturnValue, WeirollError> {
+        let (selector, words) = self.split_calldata(to, calldata.as_ref())?;
+        self.insert_raw_call(
+            to,
+            selector,
+            CommandFlags::CALL,
+            None,
+            words.chain(args).collect(),
+            return_type,
+        )
+    }
+
+    /// Like [`call_raw`](Self::call_raw), but sends `value` wei with CALL_WITH_VALUE.
+    pub fn call_raw_with_value(
+        &mut self,
+        to: Address,
+        value: U256,
+        calldata: impl AsRef<[u8]>,
+        args: Vec<Value<'a>>,
+        return_type: DynSolType,
+    ) -> Result<ReturnValue, WeirollError> {
+        let (selector, words) = self.split_calldata(to, calldata.as_ref())?;
+        self.insert_raw_call(
+            to,
+            selector,
+            CommandFlags::CALL_WITH_VALUE,
+            Some(value),
+            words.chain(args).collect(),
+            return_type,
+        )
+    }
+
+    /// Splits `calldata` into its selector and `bytes32` literals for the remaining words.
+    fn split_calldata<'c>(
+        &self,
+        to: Address,
+        calldata: &'c [u8],
+    ) -> Result<([u8; 4], impl Iterator<Item = Value<'a>> + 'c), WeirollError> {
+        let mut selector = [0; 4];
+        let len = calldata.len().min(4);
+        selector[..len].copy_from_slice(&calldata[..len]);
+
+        if calldata.len() < 4 || !(calldata.len() - 4).is_multiple_of(32) {
+            return Err(WeirollError::UnencodableCalldata {
+                command: self.next_context(to, selector),
+                length: calldata.len(),
+            });
+        }
+
+        let words = calldata[4..]
+            .chunks_exact(32)
+            .map(|word| B256::from_slice(word).into());
+        Ok((selector, words))
+    }
+
+    /// Plans a call to `selector` without checking `args` against a function signature.
+    pub(crate) fn insert_raw_call(
+        &mut self,
+        address: Address,
+        selector: [u8; 4],
+        flags: CommandFlags,
+        value: Option<U256>,
+        args: Vec<Value<'a>>,
+        return_type: DynSolType,
+    ) -> Result<ReturnValue, WeirollError> {
+        let args = self.expand_args(args)?;
+        let dynamic = return_type.is_dynamic();
+
+        let command = self.push_command(Command {
+            call: FunctionCall {
+                address,
+                selector,
+                flags,
+                value,
+                args,
+                return_type,
+            },
+            kind: CommandType::Call,
+            label: None,
+        });
+
+        Ok(ReturnValue {
+            dynamic,
+            command,
+            planner: self.id,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::math::Math;
+    use crate::view::{ArgView, CallType};
+    use alloy::primitives::{Bytes, address};
+    use alloy::sol_types::SolCall;
+
+    fn recipient() -> Address {
+        address!("0x4d5F47FA6A74757f35C14fD3a6Ef8E3C9BC514E8")
+    }
+
+    #[test]
+    fn transfer_eth_sends_value_as_first_argument() -> Result<(), WeirollError> {
+        let mut planner = Planner::default();
+        planner.transfer_eth(recipient(), U256::from(5))?;
+
+        let (commands, state) = planner.plan()?;
+        let word = commands[0].0;
+        assert_eq!(word[..4], [0; 4]);
+        assert_eq!(word[4], CommandFlags::CALL_WITH_VALUE.bits());
+        assert_eq!(word[5..12], [0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(word[12..], recipient().0.0);
+        assert_eq!(state, vec![Bytes::from(U256::from(5).to_be_bytes::<32>())]);
+
+        let view = &planner.commands()[0];
+        assert_eq!(view.call_type, CallType::CallWithValue);
+        assert_eq!(view.value, Some(U256::from(5)));
+        assert!(!view.has_output());
+        Ok(())
+    }
+
+    #[test]
+    fn call_raw_matches_typed_call() -> Result<(), WeirollError> {
+        let a = U256::from(1);
+        let b = U256::from(2);
+        let calldata = Math::addCall { a, b }.abi_encode();
+
+        let mut planner = Planner::default();
+        planner.call_raw(recipient(), &calldata, vec![], DynSolType::Uint(256))?;
+        planner.call_raw(
+            recipient(),
+            Math::addCall::SELECTOR,
+            vec![a.into()],
+            DynSolType::Uint(256),
+        )?;
+        let commands = planner.commands();
+        assert_eq!(commands[0].selector, Math::addCall::SELECTOR);
+        assert!(matches!(commands[1].args[..], [ArgView::Literal(_)]));
+
+        let mut expected = Planner::default();
+        expected.call_address::<Math::addCall>(
+            recipient(),
+            vec![B256::from(a).into(), B256::from(b).into()],
+        )?;
+        expected.call_address::<Math::addCall>(recipient(), vec![a.into()])?;
+        assert_eq!(planner.plan()?, expected.plan()?);
+
+        assert!(matches!(
+            planner.call_raw(
+                recipient(),
+                [0xd0, 0xe3, 0x0d, 0xb0, 1],
+                vec![],
+                DynSolType::Tuple(vec![])
+            ),
+            Err(WeirollError::UnencodableCalldata { length: 5, .. })
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+//! Encoding of weiroll plans as Gnosis Safe transactions.
+//!
+//! A Safe runs a plan by DELEGATECALLing into the VM's `execute`, so the plan operates on the
+//! Safe's own balances and storage. [`SafeTransaction::execute_plan`] builds that transaction and
+//! [`SafeTransaction::signing_hash`] computes the EIP-712 `SafeTx` hash owners sign.
+//! [`MultiSend`] batches a plan with plain calls through the MultiSend library.
+
+use crate::Planner;
+use crate::error::WeirollError;
+
+use alloy::primitives::{Address, B256, Bytes, U256};
+use alloy::sol;
+use alloy::sol_types::{Eip712Domain, SolCall, SolStruct, eip712_domain};
+
+sol! {
+    /// The EIP-712 struct signed by Safe owners.
+    #[derive(Debug, Default, PartialEq)]
+    struct SafeTx {
+        address to;
+        uint256 value;
+        bytes data;
+        uint8 operation;
+        uint256 safeTxGas;
+        uint256 baseGas;
+        uint256 gasPrice;
+        address gasToken;
+        address refundReceiver;
+        uint256 nonce;
+    }
+
+    interface IMultiSend {
+        function multiSend(bytes transactions) external payable;
+    }
+}
+
+/// How a Safe invokes the target of a transaction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Operation {
+    #[default]
+    Call = 0,
+    DelegateCall = 1,
+}
+
+/// A transaction to be executed by a Safe through `execTransaction`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SafeTransaction {
+    pub to: Address,
+    pub value: U256,
+    pub data: Bytes,
+    pub operation: Operation,
+    pub safe_tx_gas: U256,
+    pub base_gas: U256,
+    pub gas_price: U256,
+    pub gas_token: Address,
+    pub refund_receiver: Address,
+    pub nonce: U256,
+}
+
+impl SafeTransaction {
+    /// DELEGATECALLs `execute` on the VM at `vm` with the planned commands and state.
+    pub fn execute_plan(
+        vm: Address,
+        planner: &Planner<'_>,
+        nonce: U256,
+    ) -> Result<Self, WeirollError> {
+        Ok(Self {
+            to: vm,
+            data: planner.execute_calldata()?,
+            operation: Operation::DelegateCall,
+            nonce,
+            ..Default::default()
+        })
+    }
+
+    /// The EIP-712 domain of the Safe at `safe` on `chain_id`.
+    pub fn domain(safe: Address, chain_id: u64) -> Eip712Domain {
+        eip712_domain! {
+            chain_id: chain_id,
+            verifying_contract: safe,
+        }
+    }
+
+    /// Returns the `SafeTx` struct for this transaction.
+    pub fn to_sol(&self) -> SafeTx {
+        SafeTx {
+            to: self.to,
+            value: self.value,
+            data: self.data.clone(),
+            operation: self.operation as u8,
+            safeTxGas: self.safe_tx_gas,
+            baseGas: self.base_gas,
+            gasPrice: self.gas_price,
+            gasToken: self.gas_token,
+            refundReceiver: self.refund_receiver,
+            nonce: self.nonce,
+        }
+    }
+
+    /// The hash Safe owners sign to approve this transaction.
+    pub fn signing_hash(&self, safe: Address, chain_id: u64) -> B256 {
+        self.to_sol()
+            .eip712_signing_hash(&Self::domain(safe, chain_id))
+    }
+}
+
+/// Batches several operations into one Safe transaction using the MultiSend library.
+#[derive(Clone, Debug, Default)]
+pub struct MultiSend {
+    transactions: Vec<u8>,
+}
+
+impl MultiSend {
+    /// Appends a plain CALL to `to`.
+    pub fn call(mut self, to: Address, value: U256, data: impl Into<Bytes>) -> Self {
+        self.push(Operation::Call, to, value, &data.into());
+        self
+    }
+
+    /// Appends a DELEGATECALL into the VM at `vm` executing `planner`.
+    pub fn execute_plan(
+        mut self,
+        vm: Address,
+        planner: &Planner<'_>,
+    ) -> Result<Self, WeirollError> {
+        let data = planner.execute_calldata()?;
+        self.push(Operation::DelegateCall, vm, U256::ZERO, &data);
+        Ok(self)
+    }
+
+    fn push(&mut self, operation: Operation, to: Address, value: U256, data: &Bytes) {
+        self.transactions.push(operation as u8);
+        self.transactions.extend_from_slice(to.as_slice());
+        self.transactions
+            .extend_from_slice(&value.to_be_bytes::<32>());
+        self.transactions
+            .extend_from_slice(&U256::from(data.len()).to_be_bytes::<32>());
+        self.transactions.extend_from_slice(data);
+    }
+
+    /// The packed `transactions` argument to `multiSend`.
+    pub fn packed(&self) -> Bytes {
+        self.transactions.clone().into()
+    }
+
+    /// Builds a Safe transaction which DELEGATECALLs the MultiSend library at `multisend`.
+    pub fn build(&self, multisend: Address, nonce: U256) -> SafeTransaction {
+        let data = IMultiSend::multiSendCall {
+            transactions: self.packed(),
+        }
+        .abi_encode();
+
+        SafeTransaction {
+            to: multisend,
+            data: data.into(),
+            operation: Operation::DelegateCall,
+            nonce,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::math::Math;
+    use alloy::primitives::{address, b256, keccak256};
+    use alloy::sol_types::SolValue;
+
+    fn vm() -> Address {
+        address!("0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee")
+    }
+
+    fn planner() -> Planner<'static> {
+        let mut planner = Planner::default();
+        planner
+            .call_address::<Math::addCall>(vm(), vec![U256::from(1).into(), U256::from(2).into()])
+            .unwrap();
+        planner
+    }
+
+    #[test]
+    fn safe_tx_typehash_matches_safe_contracts() {
+        assert_eq!(
+            SafeTx::default().eip712_type_hash(),
+            b256!("0xbb8310d486368db6bd6f849402fdd73ad53d316b5a4b2644ad6efe0f941286d8")
+        );
+    }
+
+    #[test]
+    fn domain_separator_matches_safe_contracts() {
+        let safe = address!("0x1111111111111111111111111111111111111111");
+        let domain_typehash =
+            b256!("0x47e79534a245952e8b16893a336b85a3d9ea9fa8c573f3d803afb92a79469218");
+        let expected = keccak256((domain_typehash, U256::from(1), safe).abi_encode());
+
+        assert_eq!(SafeTransaction::domain(safe, 1).separator(), expected);
+    }
+
+    #[test]
+    fn execute_plan_delegatecalls_vm() {
+        let planner = planner();
+        let tx = SafeTransaction::execute_plan(vm(), &planner, U256::from(7)).unwrap();
+
+        assert_eq!(tx.to, vm());
+        assert_eq!(tx.operation, Operation::DelegateCall);
+        assert_eq!(tx.data, planner.execute_calldata().unwrap());
+        assert_eq!(tx.to_sol().nonce, U256::from(7));
+    }
+
+    #[test]
+    fn multisend_packs_transactions() {
+        let planner = planner();
+        let to = address!("0x2222222222222222222222222222222222222222");
+        let batch = MultiSend::default()
+            .call(to, U256::from(5), vec![0xab, 0xcd])
+            .execute_plan(vm(), &planner)
+            .unwrap();
+
+        let packed = batch.packed();
+        assert_eq!(packed[0], Operation::Call as u8);
+        assert_eq!(&packed[1..21], to.as_slice());
+        assert_eq!(U256::from_be_slice(&packed[21..53]), U256::from(5));
+        assert_eq!(U256::from_be_slice(&packed[53..85]), U256::from(2));
+        assert_eq!(&packed[85..87], &[0xab, 0xcd]);
+        assert_eq!(packed[87], Operation::DelegateCall as u8);
+        assert_eq!(&packed[88..108], vm().as_slice());
+
+        let multisend = address!("0x3333333333333333333333333333333333333333");
+        let tx = batch.build(multisend, U256::ZERO);
+        assert_eq!(tx.to, multisend);
+        assert_eq!(tx.operation, Operation::DelegateCall);
+        assert_eq!(&tx.data[..4], IMultiSend::multiSendCall::SELECTOR);
+    }
+}
@@ -0,0 +1,225 @@
+/// Write a sequence of planner calls with Solidity-like syntax.
+///
+/// ```ignore
+/// let mut rewards = Planner::default();
+/// let mut planner = Planner::default();
+/// weiroll::weiroll_script!(planner => {
+///     let bal = staticcall weth.balanceOf(me);
+///     call events.logUint(bal);
+///     let shares = call vault.deposit{value: amount}(me);
+///     delegatecall math.add(bal, shares);
+///     subplan vm.execute(rewards) {
+///         call gauge.claim(me);
+///     };
+/// });
+/// ```
+///
+/// Every contract must implement [`PlanContract`](crate::PlanContract), see
+/// [`contract_planner!`](crate::contract_planner). Each statement is one of `call`,
+/// `staticcall`, `delegatecall` or `subplan`, optionally bound with `let`. A `subplan` block is
+/// planned into the named planner, which must be declared before the enclosing one, and passed
+/// to the call along with the current state.
+///
+/// The script expands to statements in the enclosing function, so `let` bindings remain in scope
+/// afterwards and failures are returned with `?` as [`WeirollError::Script`](crate::WeirollError),
+/// naming the statement which failed.
+#[macro_export]
+macro_rules! weiroll_script {
+    ($planner:expr => { $($body:tt)* }) => {
+        $crate::weiroll_script!(@block $planner; $($body)*);
+    };
+
+    (@block $planner:expr; $($body:tt)*) => {
+        let __planner: &mut $crate::Planner = &mut $planner;
+        $crate::weiroll_script!(@stmts __planner; $($body)*);
+    };
+
+    (@stmts $p:ident;) => {};
+
+    // subplan blocks
+    (@stmts $p:ident;
+        subplan $contract:ident . $method:ident ( $sub:ident ) { $($inner:tt)* };
+        $($rest:tt)*
+    ) => {
+        {
+            $crate::weiroll_script!(@block $sub; $($inner)*);
+        }
+        $crate::weiroll_script!(@wrap
+            [subplan $contract.$method($sub) { .. }]
+            $crate::PlanContract::plan(&$contract, &mut *$p)
+                .$method($crate::Value::Subplan(&$sub), $crate::Value::State(::std::vec::Vec::new()))
+                .subplan()
+        )?;
+        $crate::weiroll_script!(@stmts $p; $($rest)*);
+    };
+
+    // value calls
+    (@stmts $p:ident;
+        let $name:ident = call $contract:ident . $method:ident { value: $value:expr } ( $($arg:expr),* $(,)? );
+        $($rest:tt)*
+    ) => {
+        let $name = $crate::weiroll_script!(@wrap
+            [let $name = call $contract.$method{value: $value}($($arg),*)]
+            $crate::PlanContract::plan(&$contract, &mut *$p)
+                .$method($($arg),*)
+                .call_with_value(($value).into())
+        )?;
+        $crate::weiroll_script!(@stmts $p; $($rest)*);
+    };
+
+    (@stmts $p:ident;
+        call $contract:ident . $method:ident { value: $value:expr } ( $($arg:expr),* $(,)? );
+        $($rest:tt)*
+    ) => {
+        $crate::weiroll_script!(@wrap
+            [call $contract.$method{value: $value}($($arg),*)]
+            $crate::PlanContract::plan(&$contract, &mut *$p)
+                .$method($($arg),*)
+                .call_with_value(($value).into())
+        )?;
+        $crate::weiroll_script!(@stmts $p; $($rest)*);
+    };
+
+    // call, staticcall and delegatecall
+    (@stmts $p:ident;
+        let $name:ident = $kind:ident $contract:ident . $method:ident ( $($arg:expr),* $(,)? );
+        $($rest:tt)*
+    ) => {
+        let $name = $crate::weiroll_script!(@wrap
+            [let $name = $kind $contract.$method($($arg),*)]
+            $crate::PlanContract::plan(&$contract, &mut *$p)
+                .$method($($arg),*)
+                .$kind()
+        )?;
+        $crate::weiroll_script!(@stmts $p; $($rest)*);
+    };
+
+    (@stmts $p:ident;
+        $kind:ident $contract:ident . $method:ident ( $($arg:expr),* $(,)? );
+        $($rest:tt)*
+    ) => {
+        $crate::weiroll_script!(@wrap
+            [$kind $contract.$method($($arg),*)]
+            $crate::PlanContract::plan(&$contract, &mut *$p)
+                .$method($($arg),*)
+                .$kind()
+        )?;
+        $crate::weiroll_script!(@stmts $p; $($rest)*);
+    };
+
+    (@wrap [$($statement:tt)*] $expr:expr) => {
+        ($expr).map_err(|source| $crate::WeirollError::Script {
+            statement: stringify!($($statement)*),
+            source: ::std::boxed::Box::new(source),
+        })
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bindings::{
+        erc20::ERC20, events::Events, math::Math, strings::Strings, testable_vm::TestableVM,
+    };
+    use crate::{Planner, WeirollError};
+    use alloy::primitives::{U256, address};
+    use alloy::providers::{ProviderBuilder, mock::Asserter};
+
+    #[test]
+    fn script_matches_call_contract() -> Result<(), WeirollError> {
+        let provider = ProviderBuilder::new().connect_mocked_client(Asserter::new());
+        let weth = ERC20::new(
+            address!("0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2"),
+            &provider,
+        );
+        let events = Events::new(
+            address!("0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee"),
+            &provider,
+        );
+        let me = address!("0x4d5F47FA6A74757f35C14fD3a6Ef8E3C9BC514E8");
+
+        let mut planner = Planner::default();
+        crate::weiroll_script!(planner => {
+            let bal = staticcall weth.balanceOf(me);
+            call events.logUint(bal.clone());
+            call weth.transfer{value: U256::from(1)}(me, bal);
+        });
+
+        let mut expected = Planner::default();
+        let bal =
+            crate::call_contract!(staticcall, &mut expected, &weth, ERC20::balanceOfCall[me])?;
+        crate::call_contract!(&mut expected, &events, Events::logUintCall[bal.clone()])?;
+        crate::call_contract!(
+            value(U256::from(1)),
+            &mut expected,
+            &weth,
+            ERC20::transferCall[me, bal]
+        )?;
+
+        assert_eq!(planner.plan()?, expected.plan()?);
+        Ok(())
+    }
+
+    #[test]
+    fn script_plans_subplan_blocks() -> Result<(), WeirollError> {
+        let provider = ProviderBuilder::new().connect_mocked_client(Asserter::new());
+        let math = Math::new(
+            address!("0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee"),
+            &provider,
+        );
+        let vm = TestableVM::new(
+            address!("0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee"),
+            &provider,
+        );
+
+        let mut sub = Planner::default();
+        let mut planner = Planner::default();
+        crate::weiroll_script!(planner => {
+            subplan vm.execute(sub) {
+                call math.add(U256::from(1), U256::from(2));
+            };
+        });
+
+        let (commands, state) = planner.plan()?;
+        assert_eq!(commands.len(), 1);
+        assert_eq!(
+            commands[0],
+            "0xde792d5f0082fefffffffffeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee"
+                .parse::<alloy::primitives::Bytes>()
+                .unwrap()[..]
+        );
+        assert_eq!(state.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn script_reports_failing_statement() {
+        let provider = ProviderBuilder::new().connect_mocked_client(Asserter::new());
+        let math = Math::new(
+            address!("0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee"),
+            &provider,
+        );
+        let strings = Strings::new(
+            address!("0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee"),
+            &provider,
+        );
+        let script = || -> Result<(), WeirollError> {
+            let mut planner = Planner::default();
+            crate::weiroll_script!(planner => {
+                let one = call strings.strcat(String::from("o"), String::from("ne"));
+                call math.add(U256::from(1), one);
+            });
+            Ok(())
+        };
+
+        match script() {
+            Err(WeirollError::Script { statement, source }) => {
+                assert!(statement.starts_with("call math.add"));
+                assert!(matches!(
+                    *source,
+                    WeirollError::ArgumentTypeMismatch { argument: 1, .. }
+                ));
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+}
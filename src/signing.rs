@@ -0,0 +1,222 @@
+//! EIP-712 signatures over planned `(commands, state)` pairs.
+//!
+//! [`Planner::plan_hash`] is the canonical hash of a plan: the EIP-712 `hashStruct` of its
+//! [`Plan`]. A [`SignedPlan`] binds that plan to a VM, a nonce and a deadline, and is what
+//! signers approve for the bundled
+//! [`PlanEntrypoint`], which verifies the signature
+//! and forwards the plan to the VM with the signer appended to the calldata (ERC-2771 style).
+//!
+//! ```ignore
+//! let signed = SignedPlan::new(vm, &planner, nonce, deadline)?;
+//! let signature = signed.sign(&signer, entrypoint, chain_id).await?;
+//! let calldata = signed.execute_calldata(&signature);
+//! ```
+
+use crate::Planner;
+use crate::error::WeirollError;
+use crate::helpers::entrypoint::PlanEntrypoint;
+
+use alloy::primitives::{Address, B256, Bytes, Signature, SignatureError, U256};
+use alloy::signers::Signer;
+use alloy::sol;
+use alloy::sol_types::{Eip712Domain, SolCall, SolStruct, eip712_domain};
+
+sol! {
+    /// A planned `(commands, state)` pair.
+    #[derive(Debug, Default, PartialEq)]
+    struct Plan {
+        bytes32[] commands;
+        bytes[] state;
+    }
+
+    /// The EIP-712 struct signed to authorize running a [`Plan`] on `vm`.
+    #[derive(Debug, Default, PartialEq)]
+    struct SignedPlan {
+        address vm;
+        Plan plan;
+        uint256 nonce;
+        uint256 deadline;
+    }
+}
+
+impl Plan {
+    /// The commands and state planned by `planner`.
+    pub fn new(planner: &Planner<'_>) -> Result<Self, WeirollError> {
+        let (commands, state) = planner.plan()?;
+        Ok(Self { commands, state })
+    }
+}
+
+impl Planner<'_> {
+    /// The canonical hash of the planned commands and state.
+    ///
+    /// This is the EIP-712 `hashStruct` of the [`Plan`], so it is also the `plan` member of a
+    /// [`SignedPlan`]'s encoding.
+    pub fn plan_hash(&self) -> Result<B256, WeirollError> {
+        Ok(Plan::new(self)?.eip712_hash_struct())
+    }
+}
+
+impl SignedPlan {
+    /// Authorizes running `planner` on the VM at `vm` with `nonce`, until `deadline` (a unix
+    /// timestamp, inclusive).
+    pub fn new(
+        vm: Address,
+        planner: &Planner<'_>,
+        nonce: U256,
+        deadline: U256,
+    ) -> Result<Self, WeirollError> {
+        Ok(Self {
+            vm,
+            plan: Plan::new(planner)?,
+            nonce,
+            deadline,
+        })
+    }
+
+    /// The EIP-712 domain of the entrypoint at `entrypoint` on `chain_id`.
+    pub fn domain(entrypoint: Address, chain_id: u64) -> Eip712Domain {
+        eip712_domain! {
+            name: "WeirollEntrypoint",
+            version: "1",
+            chain_id: chain_id,
+            verifying_contract: entrypoint,
+        }
+    }
+
+    /// The digest signed to authorize this plan at `entrypoint` on `chain_id`.
+    pub fn signing_hash(&self, entrypoint: Address, chain_id: u64) -> B256 {
+        self.eip712_signing_hash(&Self::domain(entrypoint, chain_id))
+    }
+
+    /// Signs [`SignedPlan::signing_hash`] with `signer`.
+    pub async fn sign<S: Signer + Sync + ?Sized>(
+        &self,
+        signer: &S,
+        entrypoint: Address,
+        chain_id: u64,
+    ) -> alloy::signers::Result<Signature> {
+        signer
+            .sign_hash(&self.signing_hash(entrypoint, chain_id))
+            .await
+    }
+
+    /// The address which produced `signature` over this plan.
+    pub fn recover(
+        &self,
+        signature: &Signature,
+        entrypoint: Address,
+        chain_id: u64,
+    ) -> Result<Address, SignatureError> {
+        signature.recover_address_from_prehash(&self.signing_hash(entrypoint, chain_id))
+    }
+
+    /// Calldata for `PlanEntrypoint.executeSigned` with `signature`.
+    pub fn execute_calldata(&self, signature: &Signature) -> Bytes {
+        PlanEntrypoint::executeSignedCall {
+            vm: self.vm,
+            commands: self.plan.commands.clone(),
+            state: self.plan.state.clone(),
+            nonce: self.nonce,
+            deadline: self.deadline,
+            signature: signature.as_bytes().to_vec().into(),
+        }
+        .abi_encode()
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::math::Math;
+    use alloy::primitives::{address, keccak256};
+    use alloy::signers::local::PrivateKeySigner;
+
+    fn vm() -> Address {
+        address!("0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee")
+    }
+
+    fn entrypoint() -> Address {
+        address!("0x1111111111111111111111111111111111111111")
+    }
+
+    fn planner() -> Planner<'static> {
+        let mut planner = Planner::default();
+        planner
+            .call_address::<Math::addCall>(vm(), vec![U256::from(1).into(), U256::from(2).into()])
+            .unwrap();
+        planner
+    }
+
+    #[test]
+    fn typehashes_match_entrypoint() {
+        assert_eq!(
+            Plan::default().eip712_type_hash(),
+            keccak256("Plan(bytes32[] commands,bytes[] state)")
+        );
+        assert_eq!(
+            SignedPlan::default().eip712_type_hash(),
+            keccak256(
+                "SignedPlan(address vm,Plan plan,uint256 nonce,uint256 deadline)\
+                 Plan(bytes32[] commands,bytes[] state)"
+            )
+        );
+    }
+
+    #[test]
+    fn plan_hash_encodes_commands_and_state() {
+        let planner = planner();
+        let (commands, state) = planner.plan().unwrap();
+        let state_hashes = state.iter().flat_map(keccak256).collect::<Vec<_>>();
+        let expected = keccak256(
+            [
+                Plan::default().eip712_type_hash().as_slice(),
+                keccak256(commands.concat()).as_slice(),
+                keccak256(state_hashes).as_slice(),
+            ]
+            .concat(),
+        );
+
+        assert_eq!(planner.plan_hash().unwrap(), expected);
+        assert_ne!(Planner::default().plan_hash().unwrap(), expected);
+    }
+
+    #[test]
+    fn signing_hash_binds_plan_vm_and_domain() {
+        let planner = planner();
+        let signed = SignedPlan::new(vm(), &planner, U256::ZERO, U256::MAX).unwrap();
+        let hash = signed.signing_hash(entrypoint(), 1);
+
+        assert_ne!(signed.signing_hash(entrypoint(), 10), hash);
+        assert_ne!(signed.signing_hash(vm(), 1), hash);
+        let other = SignedPlan {
+            vm: entrypoint(),
+            ..SignedPlan::new(vm(), &planner, U256::ZERO, U256::MAX).unwrap()
+        };
+        assert_ne!(other.signing_hash(entrypoint(), 1), hash);
+    }
+
+    #[tokio::test]
+    async fn sign_recovers_to_signer() {
+        let signer = PrivateKeySigner::random();
+        let signed = SignedPlan::new(vm(), &planner(), U256::from(3), U256::MAX).unwrap();
+        let signature = signed.sign(&signer, entrypoint(), 1).await.unwrap();
+
+        assert_eq!(
+            signed.recover(&signature, entrypoint(), 1).unwrap(),
+            signer.address()
+        );
+        assert_ne!(
+            signed.recover(&signature, entrypoint(), 10).unwrap(),
+            signer.address()
+        );
+
+        let calldata = signed.execute_calldata(&signature);
+        let call = PlanEntrypoint::executeSignedCall::abi_decode(&calldata).unwrap();
+        assert_eq!(call.vm, vm());
+        assert_eq!(call.commands, signed.plan.commands);
+        assert_eq!(call.nonce, U256::from(3));
+        assert_eq!(call.signature.len(), 65);
+    }
+}
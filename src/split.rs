@@ -0,0 +1,463 @@
+//! Splitting plans which are too large for a single transaction.
+//!
+//! [`Planner::split`] cuts a plan into parts which run one after another, each as its own
+//! `execute` call with its own state, so that every part stays within a [`Budget`]:
+//!
+//! ```ignore
+//! let budget = Budget { calldata: Some(64 * 1024), ..Default::default() };
+//! for part in planner.split(budget)? {
+//!     provider.send_plan(vm, &part).await?.get_receipt().await?;
+//! }
+//! ```
+//!
+//! A cut normally only falls where no output of an earlier part is consumed by a later one.
+//! With a [`Carry`] contract set, such outputs are stored after the command producing them and
+//! loaded back at the start of each part which consumes them, so any cut is possible.
+
+use crate::Planner;
+use crate::calls::FunctionCall;
+use crate::cmds::{Command, CommandFlags, CommandType, ReturnValue, Value};
+use crate::error::WeirollError;
+use crate::helpers::carry::Carry;
+use crate::planner::{CommandKey, PlannerId};
+
+use alloy::dyn_abi::DynSolType;
+use alloy::primitives::{Address, U256};
+use alloy::sol_types::SolCall;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Limits each part of [`Planner::split`] must stay within. Unset limits are not checked.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Budget {
+    /// Most bytes of `execute` calldata
+    pub calldata: Option<usize>,
+    /// Most calldata gas, at EIP-2028 prices of 4 per zero byte and 16 per other byte
+    pub calldata_gas: Option<u64>,
+    /// Most commands, counting those which carry values between parts
+    pub commands: Option<usize>,
+}
+
+impl Budget {
+    fn fits(&self, part: &Planner<'_>) -> Result<bool, WeirollError> {
+        if self.commands.is_some_and(|max| part.order.len() > max) {
+            return Ok(false);
+        }
+        if self.calldata.is_none() && self.calldata_gas.is_none() {
+            return Ok(true);
+        }
+
+        let cost = part.cost()?;
+        Ok(self.calldata.is_none_or(|max| cost.calldata_size <= max)
+            && self.calldata_gas.is_none_or(|max| cost.calldata_gas <= max))
+    }
+}
+
+/// An output, by the planner which produced it and its key there. Subplans built separately
+/// may reuse the keys of this planner, so keys alone do not identify an output.
+type Output = (PlannerId, CommandKey);
+
+/// Where the outputs of a plan are produced and consumed, by position in the plan.
+#[derive(Default)]
+struct Liveness {
+    /// The command producing each output; outputs of subplans belong to the subplan's command
+    producers: BTreeMap<Output, usize>,
+    /// The later commands consuming each output
+    consumers: BTreeMap<Output, BTreeSet<usize>>,
+}
+
+impl Liveness {
+    fn new(planner: &Planner<'_>) -> Self {
+        let mut liveness = Self::default();
+        for (index, (key, command)) in planner.command_entries().enumerate() {
+            liveness.produced(index, planner.id, key, command);
+        }
+
+        for (index, (key, command)) in planner.command_entries().enumerate() {
+            let mut used = BTreeSet::new();
+            command.consumed_outputs(&mut used);
+            Self::overwritten(&planner.merged, key, command, &mut used);
+            for producer in used {
+                if liveness
+                    .producers
+                    .get(&producer)
+                    .is_some_and(|p| *p < index)
+                {
+                    liveness
+                        .consumers
+                        .entry(producer)
+                        .or_default()
+                        .insert(index);
+                }
+            }
+        }
+        liveness
+    }
+
+    fn produced(
+        &mut self,
+        index: usize,
+        planner: PlannerId,
+        key: CommandKey,
+        command: &Command<'_>,
+    ) {
+        self.producers.insert((planner, key), index);
+        for subplan in command.call.args.iter().filter_map(Value::subplan) {
+            for (key, command) in subplan.command_entries() {
+                self.produced(index, subplan.id, key, command);
+            }
+        }
+    }
+
+    /// Collects the outputs whose slots `command` or its subplans write to, which must stay
+    /// live until then.
+    fn overwritten(
+        merged: &BTreeMap<CommandKey, Output>,
+        key: CommandKey,
+        command: &Command<'_>,
+        used: &mut BTreeSet<Output>,
+    ) {
+        used.extend(merged.get(&key));
+        for subplan in command.call.args.iter().filter_map(Value::subplan) {
+            for (key, command) in subplan.command_entries() {
+                Self::overwritten(&subplan.merged, key, command, used);
+            }
+        }
+    }
+
+    /// An output consumed across a cut before position `cut`, and the first command consuming it.
+    fn crossing(&self, cut: usize) -> Option<(usize, usize)> {
+        self.producers.iter().find_map(|(output, producer)| {
+            let consumer = self.consumers.get(output)?.range(cut..).next()?;
+            (*producer < cut).then_some((*producer, *consumer))
+        })
+    }
+
+    /// The positions up to `len` a plan can be cut before without any output being consumed
+    /// across the cut.
+    fn cuts(&self, len: usize) -> Vec<usize> {
+        // An output blocks the cuts after its producer, up to and including its last consumer
+        let mut blocking = vec![0isize; len + 2];
+        for (output, consumers) in &self.consumers {
+            if let Some(last) = consumers.last() {
+                blocking[self.producers[output] + 1] += 1;
+                blocking[last + 1] -= 1;
+            }
+        }
+
+        let mut live = 0;
+        (1..=len)
+            .filter(|cut| {
+                live += blocking[*cut];
+                live == 0
+            })
+            .collect()
+    }
+}
+
+impl<'a> Planner<'a> {
+    /// Sets the address of the [`Carry`] contract, which lets [`split`](Self::split) cut a plan
+    /// where outputs are still to be consumed.
+    pub fn set_carry(&mut self, address: Address) {
+        self.carry = Some(address);
+    }
+
+    /// Cuts the plan into parts, to be run in order, which each stay within `budget`.
+    ///
+    /// Parts are as large as the budget allows. Unless a [`Carry`] contract is set, a cut can
+    /// only fall where no output is consumed across it. Carried values are stored by index, so
+    /// the parts of one split must run before those of another on the same VM.
+    pub fn split(&self, budget: Budget) -> Result<Vec<Planner<'a>>, WeirollError> {
+        let liveness = Liveness::new(self);
+        let carried = liveness
+            .consumers
+            .keys()
+            .enumerate()
+            .map(|(index, output)| (*output, index))
+            .collect::<BTreeMap<_, _>>();
+        let cuts = match self.carry {
+            Some(_) => (1..=self.order.len()).collect(),
+            None => liveness.cuts(self.order.len()),
+        };
+        let context = |index: usize| {
+            let key = self.order[index];
+            Self::context(index, &self.commands[key])
+        };
+
+        let mut parts = vec![];
+        let mut start = 0;
+        while start < self.order.len() {
+            let ends = &cuts[cuts.partition_point(|cut| *cut <= start)..];
+
+            // A longer part never fits where a shorter one does not, so probe ends at doubling
+            // distances until one does not fit, then bisect between the last two probes
+            let mut best = None;
+            let (mut low, mut high) = (0, ends.len());
+            let mut step = Some(1);
+            while low < high {
+                let probe = match step {
+                    Some(step) => (low + step - 1).min(high - 1),
+                    None => low + (high - low) / 2,
+                };
+                let part = self.part(start, ends[probe], &liveness, &carried)?;
+                if budget.fits(&part)? {
+                    best = Some((ends[probe], part));
+                    low = probe + 1;
+                    step = step.map(|step| step * 2);
+                } else {
+                    high = probe;
+                    step = None;
+                }
+            }
+
+            let Some((end, part)) = best else {
+                let next = ends.first().copied().unwrap_or(self.order.len() + 1);
+                return Err(
+                    match (start + 1..next).find_map(|cut| liveness.crossing(cut)) {
+                        Some((producer, consumer)) => WeirollError::LiveAcrossSplit {
+                            producer: context(producer),
+                            consumer: context(consumer),
+                        },
+                        None => WeirollError::ExceedsBudget {
+                            command: context(start),
+                        },
+                    },
+                );
+            };
+            parts.push(part);
+            start = end;
+        }
+
+        Ok(parts)
+    }
+
+    /// Plans the commands from `start` to `end`, loading the outputs they consume from earlier
+    /// parts and storing those later parts consume.
+    fn part(
+        &self,
+        start: usize,
+        end: usize,
+        liveness: &Liveness,
+        carried: &BTreeMap<Output, usize>,
+    ) -> Result<Planner<'a>, WeirollError> {
+        let mut part = self.nested();
+        // Parts hold this planner's commands, so their outputs are this planner's too
+        part.id = self.id;
+        part.merged = self.merged.clone();
+
+        let mut stores = vec![];
+        for (output, index) in carried {
+            let (planner, key) = output;
+            let producer = liveness.producers[output];
+            let mut consumers = liveness.consumers[output].range(start..);
+            let load = producer < start && consumers.next().is_some_and(|c| *c < end);
+            let store = (start..end).contains(&producer) && consumers.any(|c| *c >= end);
+            if !load && !store {
+                continue;
+            }
+
+            let carry = self.carry.ok_or(WeirollError::MissingCarry)?;
+            let command = self
+                .commands
+                .get(*key)
+                .ok_or(WeirollError::UnknownReturnValue)?;
+            let tuple = command.call.flags.contains(CommandFlags::TUPLE_RETURN);
+            if load {
+                // The load takes the producer's key, so its consumers need no remapping
+                part.commands[*key] = Command {
+                    call: FunctionCall {
+                        address: carry,
+                        selector: Carry::loadCall::SELECTOR,
+                        flags: CommandFlags::STATICCALL,
+                        value: None,
+                        args: vec![U256::from(*index).into()],
+                        return_type: if tuple {
+                            DynSolType::Bytes
+                        } else {
+                            command.call.return_type.clone()
+                        },
+                    },
+                    kind: CommandType::Call,
+                    label: None,
+                };
+                part.order.push(*key);
+                part.merged.remove(key);
+            } else {
+                let value = ReturnValue {
+                    dynamic: tuple || command.call.return_type.is_dynamic(),
+                    command: *key,
+                    planner: *planner,
+                };
+                stores.push((carry, *index, value));
+            }
+        }
+
+        part.order.extend_from_slice(&self.order[start..end]);
+        for (carry, index, value) in stores {
+            let selector = if value.dynamic {
+                Carry::storeDynamicCall::SELECTOR
+            } else {
+                Carry::storeCall::SELECTOR
+            };
+            part.insert_raw_call(
+                carry,
+                selector,
+                CommandFlags::CALL,
+                None,
+                vec![U256::from(index).into(), value.into()],
+                DynSolType::Tuple(vec![]),
+            )?;
+        }
+
+        Ok(part)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::{math::Math, strings::Strings, testable_vm::TestableVM};
+    use crate::view::CallType;
+    use alloy::primitives::address;
+
+    fn math() -> Address {
+        address!("0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee")
+    }
+
+    fn carry() -> Address {
+        address!("0xcccccccccccccccccccccccccccccccccccccccc")
+    }
+
+    fn add<'a>(
+        planner: &mut Planner<'a>,
+        a: impl Into<Value<'a>>,
+        b: u64,
+    ) -> Result<ReturnValue, WeirollError> {
+        planner.call_address::<Math::addCall>(math(), vec![a.into(), U256::from(b).into()])
+    }
+
+    fn commands(max: usize) -> Budget {
+        Budget {
+            commands: Some(max),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn cuts_between_independent_commands() -> Result<(), WeirollError> {
+        let mut planner = Planner::default();
+        let a = add(&mut planner, U256::from(1), 2)?;
+        add(&mut planner, a, 3)?;
+        add(&mut planner, U256::from(4), 5)?;
+        let b = add(&mut planner, U256::from(6), 7)?;
+        add(&mut planner, b, 8)?;
+
+        let parts = planner.split(commands(3))?;
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].commands().len(), 3);
+        assert_eq!(parts[1].commands().len(), 2);
+        for part in &parts {
+            part.plan()?;
+        }
+
+        assert_eq!(planner.split(Budget::default())?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn reports_live_values_without_carry() -> Result<(), WeirollError> {
+        let mut planner = Planner::default();
+        let a = add(&mut planner, U256::from(1), 2)?;
+        add(&mut planner, U256::from(3), 4)?;
+        add(&mut planner, a, 5)?;
+
+        assert!(matches!(
+            planner.split(commands(2)),
+            Err(WeirollError::LiveAcrossSplit { producer, consumer })
+                if producer.index == 0 && consumer.index == 2
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn separately_built_subplans_do_not_hide_live_values() -> Result<(), WeirollError> {
+        let mut subplan = Planner::default();
+        let own = add(&mut subplan, U256::from(5), 6)?;
+        add(&mut subplan, own.clone(), 7)?;
+
+        let mut planner = Planner::default();
+        let a = add(&mut planner, U256::from(1), 2)?;
+        assert_eq!(own.command, a.command);
+        planner.add_subplan_address::<TestableVM::executeCall>(
+            math(),
+            vec![Value::Subplan(&subplan), Value::State(vec![])],
+        )?;
+        add(&mut planner, U256::from(3), 4)?;
+        add(&mut planner, a, 5)?;
+
+        assert!(matches!(
+            planner.split(commands(3)),
+            Err(WeirollError::LiveAcrossSplit { producer, consumer })
+                if producer.index == 0 && consumer.index == 3
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn reports_commands_over_budget() -> Result<(), WeirollError> {
+        let mut planner = Planner::default();
+        add(&mut planner, U256::from(1), 2)?;
+
+        let budget = Budget {
+            calldata: Some(100),
+            ..Default::default()
+        };
+        assert!(matches!(
+            planner.split(budget),
+            Err(WeirollError::ExceedsBudget { command }) if command.index == 0
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn carries_live_values() -> Result<(), WeirollError> {
+        let mut planner = Planner::default();
+        planner.set_carry(carry());
+        let sum = add(&mut planner, U256::from(1), 2)?;
+        let joined = planner
+            .strings(math())
+            .strcat(String::from("a"), String::from("b"))?;
+        add(&mut planner, sum.clone(), 3)?;
+        planner.strings(math()).strlen(joined)?;
+        add(&mut planner, sum.clone(), 4)?;
+
+        let parts = planner.split(commands(4))?;
+        assert_eq!(parts.len(), 3);
+
+        // The first part stores both outputs once they are produced
+        let first = parts[0].commands();
+        assert_eq!(first.len(), 4);
+        assert_eq!(first[2].selector, Carry::storeCall::SELECTOR);
+        assert_eq!(first[3].selector, Carry::storeDynamicCall::SELECTOR);
+        assert_eq!(first[2].call_type, CallType::Call);
+
+        // Later parts load them back in place of the commands which produced them
+        let second = parts[1].commands();
+        assert_eq!(second.len(), 4);
+        assert_eq!(second[0].selector, Carry::loadCall::SELECTOR);
+        assert_eq!(second[0].call_type, CallType::StaticCall);
+        assert_eq!(second[0].output, sum);
+        assert_eq!(second[0].dependents, vec![2]);
+        assert_eq!(second[1].selector, Carry::loadCall::SELECTOR);
+        assert_eq!(second[1].dependents, vec![3]);
+        assert_eq!(second[3].selector, Strings::strlenCall::SELECTOR);
+
+        let third = parts[2].commands();
+        assert_eq!(third.len(), 2);
+        assert_eq!(third[0].selector, Carry::loadCall::SELECTOR);
+        assert_eq!(third[0].output, sum);
+        for part in &parts {
+            part.plan()?;
+        }
+        Ok(())
+    }
+}
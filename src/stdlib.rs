@@ -0,0 +1,366 @@
+//! Typed helpers for weiroll's standard libraries.
+//!
+//! Configure the deployed library addresses once with [`Libraries`], then plan calls with
+//! snake_case methods which accept any mix of literals and [`ReturnValue`]s:
+//!
+//! ```ignore
+//! let libs = Libraries { math, strings, events, tupler, assertions, codec };
+//! let total = libs.math(&mut planner).add(U256::from(1), balance)?;
+//! libs.events(&mut planner).log_uint(total)?;
+//! libs.assertions(&mut planner).assert_gte(total, min_out, String::from("slippage"))?;
+//! let payload = libs.codec(&mut planner).encode(vec![total.into(), recipient.into()])?;
+//! ```
+//!
+//! Libraries are DELEGATECALLed by default, as they are stateless; use `call` or `staticcall`
+//! on a helper to invoke a library deployed as a regular contract. The [`Codec`] helpers always
+//! DELEGATECALL.
+
+use crate::Planner;
+use crate::bindings::{events::Events, lib_tupler::LibTupler, math::Math, strings::Strings};
+use crate::cmds::{CommandFlags, ReturnValue, Value};
+use crate::error::WeirollError;
+use crate::helpers::assertions::Assertions;
+use crate::helpers::codec::Codec;
+
+use alloy::dyn_abi::DynSolType;
+use alloy::primitives::Address;
+use alloy::sol_types::SolCall;
+
+/// Addresses of the deployed standard libraries.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Libraries {
+    pub math: Address,
+    pub strings: Address,
+    pub events: Address,
+    pub tupler: Address,
+    /// The bundled [`Assertions`] contract
+    pub assertions: Address,
+    /// The bundled [`Codec`] contract
+    pub codec: Address,
+}
+
+impl Libraries {
+    pub fn math<'p, 'a>(&self, planner: &'p mut Planner<'a>) -> MathLib<'p, 'a> {
+        MathLib::new(planner, self.math)
+    }
+
+    pub fn strings<'p, 'a>(&self, planner: &'p mut Planner<'a>) -> StringsLib<'p, 'a> {
+        StringsLib::new(planner, self.strings)
+    }
+
+    pub fn events<'p, 'a>(&self, planner: &'p mut Planner<'a>) -> EventsLib<'p, 'a> {
+        EventsLib::new(planner, self.events)
+    }
+
+    pub fn tupler<'p, 'a>(&self, planner: &'p mut Planner<'a>) -> TuplerLib<'p, 'a> {
+        TuplerLib::new(planner, self.tupler)
+    }
+
+    pub fn assertions<'p, 'a>(&self, planner: &'p mut Planner<'a>) -> AssertionsLib<'p, 'a> {
+        AssertionsLib::new(planner, self.assertions)
+    }
+
+    pub fn codec<'p, 'a>(&self, planner: &'p mut Planner<'a>) -> CodecPlanner<'p, 'a> {
+        CodecPlanner::new(planner, self.codec)
+    }
+}
+
+impl<'a> Planner<'a> {
+    /// Plans calls to the [`Math`] library at `address`.
+    pub fn math(&mut self, address: Address) -> MathLib<'_, 'a> {
+        MathLib::new(self, address)
+    }
+
+    /// Plans calls to the [`Strings`] library at `address`.
+    pub fn strings(&mut self, address: Address) -> StringsLib<'_, 'a> {
+        StringsLib::new(self, address)
+    }
+
+    /// Plans calls to the [`Events`] library at `address`.
+    pub fn events(&mut self, address: Address) -> EventsLib<'_, 'a> {
+        EventsLib::new(self, address)
+    }
+
+    /// Plans calls to the [`LibTupler`] library at `address`.
+    pub fn tupler(&mut self, address: Address) -> TuplerLib<'_, 'a> {
+        TuplerLib::new(self, address)
+    }
+
+    /// Plans assertions with the [`Assertions`] contract at `address`.
+    ///
+    /// A failed assertion reverts the plan, and the message is reported in
+    /// [`ProviderError::ExecutionFailed`](crate::ProviderError::ExecutionFailed).
+    pub fn assertions(&mut self, address: Address) -> AssertionsLib<'_, 'a> {
+        AssertionsLib::new(self, address)
+    }
+
+    /// Plans encoding with the [`Codec`] contract at `address`.
+    pub fn codec(&mut self, address: Address) -> CodecPlanner<'_, 'a> {
+        CodecPlanner::new(self, address)
+    }
+}
+
+crate::contract_planner! {
+    /// Plans calls to the [`Math`] library.
+    pub library MathLib {
+        add(a, b) => Math::addCall;
+        sub(a, b) => Math::subCall;
+        mul(a, b) => Math::mulCall;
+        sum(values) => Math::sumCall;
+    }
+}
+
+crate::contract_planner! {
+    /// Plans calls to the [`Strings`] library.
+    pub library StringsLib {
+        strcat(a, b) => Strings::strcatCall;
+        strlen(x) => Strings::strlenCall;
+    }
+}
+
+crate::contract_planner! {
+    /// Plans calls to the [`Events`] library.
+    pub library EventsLib {
+        log_address(message) => Events::logAddressCall;
+        log_bytes(message) => Events::logBytesCall;
+        log_bytes32(message) => Events::logBytes32Call;
+        log_string(message) => Events::logStringCall;
+        log_uint(message) => Events::logUintCall;
+    }
+}
+
+crate::contract_planner! {
+    /// Plans calls to the [`LibTupler`] library.
+    pub library TuplerLib {
+        extract_element(tuple, index) => LibTupler::extractElementCall;
+    }
+}
+
+crate::contract_planner! {
+    /// Plans checks with the [`Assertions`] contract, each reverting with `message` on failure.
+    pub library AssertionsLib {
+        assert_true(condition, message) => Assertions::assertTrueCall;
+        assert_eq(a, b, message) => Assertions::assertEqCall;
+        assert_gte(a, b, message) => Assertions::assertGteCall;
+    }
+}
+
+crate::contract_planner! {
+    /// Plans encoding with the [`Codec`] contract. Each helper takes any number of values,
+    /// DELEGATECALLs the contract and returns a `bytes` value.
+    pub struct CodecPlanner for Codec::CodecInstance {}
+}
+
+impl<'a> CodecPlanner<'_, 'a> {
+    /// `abi.encode(values...)`.
+    pub fn encode(self, values: Vec<Value<'a>>) -> Result<ReturnValue, WeirollError> {
+        self.plan(Codec::encodeCall::SELECTOR, values)
+    }
+
+    /// `abi.encodePacked(values...)`.
+    ///
+    /// The type of every value must be known, and must be a static value type, `bytes` or
+    /// `string`; arrays and tuples are not supported.
+    pub fn encode_packed(self, values: Vec<Value<'a>>) -> Result<ReturnValue, WeirollError> {
+        let selector = Codec::encodePackedCall::SELECTOR;
+        let layout = values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| {
+                self.planner
+                    .value_type(value)
+                    .as_ref()
+                    .and_then(packed_layout)
+                    .ok_or(WeirollError::UnpackableArgument {
+                        command: self.planner.next_context(self.address, selector),
+                        argument: index + 1,
+                    })
+            })
+            .collect::<Result<Vec<u8>, _>>()?;
+
+        let args = std::iter::once(layout.into()).chain(values).collect();
+        self.plan(selector, args)
+    }
+
+    /// Concatenates the contents of `bytes` or `string` values.
+    pub fn concat(self, values: Vec<Value<'a>>) -> Result<ReturnValue, WeirollError> {
+        let selector = Codec::concatCall::SELECTOR;
+        for (argument, value) in values.iter().enumerate() {
+            if let Some(actual) = self.planner.value_type(value)
+                && !matches!(actual, DynSolType::Bytes | DynSolType::String)
+            {
+                return Err(WeirollError::ArgumentTypeMismatch {
+                    command: self.planner.next_context(self.address, selector),
+                    argument,
+                    expected: DynSolType::Bytes,
+                    actual,
+                });
+            }
+        }
+
+        self.plan(selector, values)
+    }
+
+    fn plan(self, selector: [u8; 4], args: Vec<Value<'a>>) -> Result<ReturnValue, WeirollError> {
+        self.planner.insert_raw_call(
+            self.address,
+            selector,
+            CommandFlags::DELEGATECALL,
+            None,
+            args,
+            DynSolType::Bytes,
+        )
+    }
+}
+
+/// Describes how [`Codec`] packs a value of type `ty`, see `contracts/Codec.sol`.
+fn packed_layout(ty: &DynSolType) -> Option<u8> {
+    match ty {
+        DynSolType::Bool => Some(1),
+        DynSolType::Int(bits) | DynSolType::Uint(bits) => Some((bits / 8) as u8),
+        DynSolType::Address => Some(20),
+        DynSolType::FixedBytes(len) => Some(0x80 | *len as u8),
+        DynSolType::Function => Some(0x80 | 24),
+        DynSolType::Bytes | DynSolType::String => Some(0),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmds::Literal;
+    use crate::view::{ArgView, CallType};
+    use alloy::primitives::{U256, address};
+
+    fn libraries() -> Libraries {
+        Libraries {
+            math: address!("0x1111111111111111111111111111111111111111"),
+            strings: address!("0x2222222222222222222222222222222222222222"),
+            events: address!("0x3333333333333333333333333333333333333333"),
+            tupler: address!("0x4444444444444444444444444444444444444444"),
+            assertions: address!("0x5555555555555555555555555555555555555555"),
+            codec: address!("0x6666666666666666666666666666666666666666"),
+        }
+    }
+
+    #[test]
+    fn helpers_match_direct_calls() -> Result<(), WeirollError> {
+        let libs = libraries();
+
+        let mut planner = Planner::default();
+        let sum = libs.math(&mut planner).add(U256::from(1), U256::from(2))?;
+        let product = planner.math(libs.math).mul(sum, U256::from(3))?;
+        let joined = libs
+            .strings(&mut planner)
+            .strcat(String::from("a"), String::from("b"))?;
+        libs.events(&mut planner).log_uint(product)?;
+        libs.events(&mut planner).log_string(joined)?;
+
+        let mut expected = Planner::default();
+        let sum = expected.delegatecall_address::<Math::addCall>(
+            libs.math,
+            vec![U256::from(1).into(), U256::from(2).into()],
+        )?;
+        let product = expected.delegatecall_address::<Math::mulCall>(
+            libs.math,
+            vec![sum.into(), U256::from(3).into()],
+        )?;
+        let joined = expected.delegatecall_address::<Strings::strcatCall>(
+            libs.strings,
+            vec![String::from("a").into(), String::from("b").into()],
+        )?;
+        expected.delegatecall_address::<Events::logUintCall>(libs.events, vec![product.into()])?;
+        expected.delegatecall_address::<Events::logStringCall>(libs.events, vec![joined.into()])?;
+
+        assert_eq!(planner.plan()?, expected.plan()?);
+        Ok(())
+    }
+
+    #[test]
+    fn helpers_switch_call_type() -> Result<(), WeirollError> {
+        let libs = libraries();
+
+        let mut planner = Planner::default();
+        let len = libs
+            .strings(&mut planner)
+            .staticcall()
+            .strlen(String::from("abc"))?;
+        libs.events(&mut planner).call().log_uint(len)?;
+
+        let commands = planner.commands();
+        assert_eq!(commands[0].call_type, CallType::StaticCall);
+        assert_eq!(commands[1].call_type, CallType::Call);
+        Ok(())
+    }
+
+    #[test]
+    fn assertions_take_message_last() -> Result<(), WeirollError> {
+        let libs = libraries();
+
+        let mut planner = Planner::default();
+        let received = libs.math(&mut planner).add(U256::from(1), U256::from(2))?;
+        libs.assertions(&mut planner).assert_gte(
+            received,
+            U256::from(3),
+            String::from("slippage"),
+        )?;
+
+        let commands = planner.commands();
+        assert_eq!(commands[1].selector, Assertions::assertGteCall::SELECTOR);
+        assert_eq!(commands[1].call_type, CallType::DelegateCall);
+        assert_eq!(commands[1].target, libs.assertions);
+        assert!(
+            matches!(commands[1].args[0], ArgView::Return(ref ret) if *ret == commands[0].output)
+        );
+        assert!(!commands[1].has_output());
+
+        let message = String::from("slippage");
+        assert!(matches!(&commands[1].args[2], ArgView::Literal(bytes)
+            if *bytes == Literal::from(message).bytes()));
+        Ok(())
+    }
+
+    #[test]
+    fn codec_helpers_describe_values() -> Result<(), WeirollError> {
+        let libs = libraries();
+
+        let mut planner = Planner::default();
+        let sum = libs.math(&mut planner).add(U256::from(1), U256::from(2))?;
+        let joined = libs
+            .strings(&mut planner)
+            .strcat(String::from("a"), String::from("b"))?;
+        let encoded = libs
+            .codec(&mut planner)
+            .encode(vec![sum.clone().into(), joined.clone().into()])?;
+        libs.codec(&mut planner).encode_packed(vec![
+            libs.math.into(),
+            sum.clone().into(),
+            joined.clone().into(),
+        ])?;
+        libs.codec(&mut planner)
+            .concat(vec![encoded.clone().into(), joined.into()])?;
+
+        let commands = planner.commands();
+        assert_eq!(commands[2].selector, Codec::encodeCall::SELECTOR);
+        assert_eq!(commands[2].call_type, CallType::DelegateCall);
+        assert_eq!(*commands[2].return_type, DynSolType::Bytes);
+        assert!(encoded.dynamic);
+
+        assert_eq!(commands[3].args.len(), 4);
+        assert!(matches!(&commands[3].args[0], ArgView::Literal(layout)
+            if *layout == Literal::from(vec![20u8, 32, 0]).bytes()));
+        assert_eq!(commands[4].selector, Codec::concatCall::SELECTOR);
+
+        assert!(matches!(
+            libs.codec(&mut planner).concat(vec![sum.clone().into()]),
+            Err(WeirollError::ArgumentTypeMismatch { argument: 0, .. })
+        ));
+        assert!(matches!(
+            libs.codec(&mut planner)
+                .encode_packed(vec![Value::array([U256::from(1).into()])]),
+            Err(WeirollError::UnpackableArgument { argument: 1, .. })
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,484 @@
+//! Inspecting and editing planned commands.
+//!
+//! [`Planner::commands`] returns a read-only [`CommandView`] of each command. Commands are
+//! identified by their output [`ReturnValue`] when labelling or editing them, so edits can never
+//! leave a consumer pointing at a command which no longer exists.
+
+use crate::Planner;
+use crate::cmds::{CommandFlags, CommandType, ReturnValue, Value};
+use crate::error::{CommandContext, WeirollError};
+use crate::planner::CommandKey;
+
+use alloy::dyn_abi::DynSolType;
+use alloy::primitives::{Address, Bytes, FixedBytes, U256};
+use std::collections::BTreeSet;
+
+/// The opcode the VM uses to invoke a command's target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallType {
+    Call,
+    DelegateCall,
+    StaticCall,
+    CallWithValue,
+}
+
+impl CallType {
+    pub(crate) fn from_flags(flags: CommandFlags) -> Self {
+        match flags & CommandFlags::CALLTYPE_MASK {
+            CommandFlags::CALL => CallType::Call,
+            CommandFlags::STATICCALL => CallType::StaticCall,
+            CommandFlags::CALL_WITH_VALUE => CallType::CallWithValue,
+            _ => CallType::DelegateCall,
+        }
+    }
+
+    pub(crate) fn flags(self) -> CommandFlags {
+        match self {
+            CallType::Call => CommandFlags::CALL,
+            CallType::DelegateCall => CommandFlags::DELEGATECALL,
+            CallType::StaticCall => CommandFlags::STATICCALL,
+            CallType::CallWithValue => CommandFlags::CALL_WITH_VALUE,
+        }
+    }
+}
+
+/// What a command does with the VM state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommandKind {
+    /// A call whose output may be written to a state slot.
+    Call,
+    /// A call whose `bytes[]` output replaces the whole state, see [`Planner::replace_state`].
+    ReplaceState,
+    /// A call which executes a nested plan, see [`Planner::add_subplan`].
+    Subplan,
+}
+
+/// An argument of a planned command.
+#[derive(Clone, Debug)]
+pub enum ArgView<'p> {
+    /// A literal, ABI-encoded as it is stored in the state.
+    Literal(Bytes),
+    /// The output of an earlier command.
+    Return(ReturnValue),
+    /// The current VM state.
+    State,
+    /// A nested plan.
+    Subplan(&'p Planner<'p>),
+    /// An array or tuple which could not be expanded, see [`Planner::set_builder`].
+    Composite(Vec<ArgView<'p>>),
+}
+
+fn arg_view<'p>(arg: &'p Value<'p>) -> ArgView<'p> {
+    match arg {
+        Value::Literal(literal) => ArgView::Literal(literal.bytes()),
+        Value::Return(ret) => ArgView::Return(ret.clone()),
+        Value::State(_) => ArgView::State,
+        Value::Subplan(planner) => ArgView::Subplan(planner),
+        Value::OwnedSubplan(planner) => ArgView::Subplan(planner),
+        Value::Composite(composite) => {
+            ArgView::Composite(composite.elements().iter().map(arg_view).collect())
+        }
+    }
+}
+
+/// A planned command.
+#[derive(Clone, Debug)]
+pub struct CommandView<'p> {
+    /// Position of the command in its planner
+    pub index: usize,
+    pub target: Address,
+    pub selector: FixedBytes<4>,
+    pub call_type: CallType,
+    pub kind: CommandKind,
+    /// The value sent with a [`CallType::CallWithValue`] command
+    pub value: Option<U256>,
+    pub args: Vec<ArgView<'p>>,
+    pub return_type: &'p DynSolType,
+    /// The handle later commands use to consume this command's output
+    pub output: ReturnValue,
+    /// Positions of the commands consuming this command's output, directly or through a subplan
+    pub dependents: Vec<usize>,
+    pub label: Option<&'p str>,
+}
+
+impl CommandView<'_> {
+    /// Identifies this command in errors and diagnostics.
+    pub fn context(&self) -> CommandContext {
+        CommandContext {
+            index: self.index,
+            selector: self.selector,
+            target: self.target,
+        }
+    }
+
+    /// Whether the command produces a value later commands could consume.
+    pub fn has_output(&self) -> bool {
+        self.kind == CommandKind::Call && *self.return_type != DynSolType::Tuple(vec![])
+    }
+}
+
+impl<'a> Planner<'a> {
+    /// Returns a view of every command, in the order they were planned.
+    pub fn commands(&self) -> Vec<CommandView<'_>> {
+        let consumed: Vec<BTreeSet<CommandKey>> = self
+            .command_entries()
+            .map(|(_, command)| {
+                let mut used = BTreeSet::new();
+                command.consumed(self.id, &mut used);
+                used
+            })
+            .collect();
+
+        self.command_entries()
+            .enumerate()
+            .map(|(index, (key, command))| CommandView {
+                index,
+                target: command.call.address,
+                selector: command.call.selector.into(),
+                call_type: CallType::from_flags(command.call.flags),
+                kind: match command.kind {
+                    CommandType::Call => CommandKind::Call,
+                    CommandType::RawCall => CommandKind::ReplaceState,
+                    CommandType::SubPlan => CommandKind::Subplan,
+                },
+                value: command.call.value,
+                args: command.call.args.iter().map(arg_view).collect(),
+                return_type: &command.call.return_type,
+                output: ReturnValue {
+                    dynamic: command.call.return_type.is_dynamic(),
+                    command: key,
+                    planner: self.id,
+                },
+                dependents: consumed
+                    .iter()
+                    .enumerate()
+                    .skip(index + 1)
+                    .filter(|(_, used)| used.contains(&key))
+                    .map(|(dependent, _)| dependent)
+                    .collect(),
+                label: command.label.as_deref(),
+            })
+            .collect()
+    }
+
+    fn key_of(&self, ret: &ReturnValue) -> Result<CommandKey, WeirollError> {
+        // Subplans may share their parent's keys, so the command must also be planned here
+        if ret.planner == self.id && self.order.contains(&ret.command) {
+            Ok(ret.command)
+        } else {
+            Err(WeirollError::UnknownReturnValue)
+        }
+    }
+
+    /// Attaches a human-readable label to the command producing `ret`.
+    pub fn set_label(
+        &mut self,
+        ret: &ReturnValue,
+        label: impl Into<String>,
+    ) -> Result<(), WeirollError> {
+        let key = self.key_of(ret)?;
+        self.commands[key].label = Some(label.into());
+        Ok(())
+    }
+
+    /// Removes the command producing `ret`, which must not have any dependents.
+    pub fn remove(&mut self, ret: &ReturnValue) -> Result<(), WeirollError> {
+        let key = self.key_of(ret)?;
+        let commands = self.commands();
+        let Some(view) = commands.iter().find(|c| c.output.command == key) else {
+            return Err(WeirollError::UnknownReturnValue);
+        };
+
+        if !view.dependents.is_empty() {
+            return Err(WeirollError::HasDependents {
+                command: view.context(),
+                dependents: view.dependents.clone(),
+            });
+        }
+
+        self.remove_command(key);
+        Ok(())
+    }
+
+    /// Changes the contract the command producing `ret` calls.
+    pub fn set_target(&mut self, ret: &ReturnValue, target: Address) -> Result<(), WeirollError> {
+        let key = self.key_of(ret)?;
+        self.commands[key].call.address = target;
+        Ok(())
+    }
+
+    /// Sets flag bits on the command producing `ret` for a [`VmDialect`](crate::VmDialect) which
+    /// defines them.
+    ///
+    /// Bits the planner sets itself, the call type and the extended and tuple return flags, are
+    /// ignored. The default dialect rejects commands with any other flags.
+    pub fn set_extra_flags(&mut self, ret: &ReturnValue, flags: u8) -> Result<(), WeirollError> {
+        let key = self.key_of(ret)?;
+        let reserved = CommandFlags::CALLTYPE_MASK
+            | CommandFlags::EXTENDED_COMMAND
+            | CommandFlags::TUPLE_RETURN;
+        let call = &mut self.commands[key].call;
+        call.flags |= CommandFlags::from_bits_retain(flags & !reserved.bits());
+        Ok(())
+    }
+
+    /// Replaces the arguments of the command producing `ret`.
+    ///
+    /// The number of arguments must not change, and [`ReturnValue`] arguments must be outputs of
+    /// commands planned before this one. A subplan command must still take one subplan and the
+    /// state.
+    pub fn replace_args(
+        &mut self,
+        ret: &ReturnValue,
+        args: Vec<Value<'a>>,
+    ) -> Result<(), WeirollError> {
+        let key = self.key_of(ret)?;
+        let index = self.command_position(key).unwrap_or_default();
+        let command = Self::context(index, &self.commands[key]);
+
+        // Builder calls for composite arguments are planned last, then moved before the command
+        let planned = self.order.len();
+        let args = self.expand_args(args)?;
+        let builders = self.order.split_off(planned);
+
+        if let Err(err) = self.check_replacement(key, command, &args, &builders) {
+            for builder in builders {
+                self.commands.remove(builder);
+            }
+            return Err(err);
+        }
+
+        self.order.splice(index..index, builders);
+        self.commands[key].call.args = args;
+        Ok(())
+    }
+
+    fn check_replacement(
+        &self,
+        key: CommandKey,
+        command: CommandContext,
+        args: &[Value<'a>],
+        builders: &[CommandKey],
+    ) -> Result<(), WeirollError> {
+        let expected = self.commands[key].call.args.len();
+        if args.len() != expected {
+            return Err(WeirollError::ArgumentCountMismatch {
+                command,
+                expected,
+                actual: args.len(),
+            });
+        }
+        if self.commands[key].kind == CommandType::SubPlan {
+            Self::check_subplan_args(command, args)?;
+        }
+
+        let visible = |ret: &ReturnValue| {
+            ret.planner == self.id
+                && (self.order[..command.index].contains(&ret.command)
+                    || builders.contains(&ret.command))
+        };
+
+        for (argument, arg) in args.iter().enumerate() {
+            if let Value::Return(val) = arg
+                && !visible(val)
+            {
+                return Err(WeirollError::CommandNotVisible { command, argument });
+            }
+        }
+
+        for builder in builders {
+            for (argument, arg) in self.commands[*builder].call.args.iter().enumerate() {
+                if let Value::Return(val) = arg
+                    && !visible(val)
+                {
+                    return Err(WeirollError::CommandNotVisible {
+                        command: Self::context(command.index, &self.commands[*builder]),
+                        argument,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::{events::Events, math::Math, testable_vm::TestableVM};
+    use alloy::primitives::address;
+    use alloy::sol_types::SolCall;
+
+    fn addr() -> Address {
+        address!("0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee")
+    }
+
+    #[test]
+    fn commands_report_dependents_and_labels() {
+        let mut planner = Planner::default();
+        let sum = planner
+            .call_address::<Math::addCall>(addr(), vec![U256::from(1).into(), U256::from(2).into()])
+            .unwrap();
+        planner.set_label(&sum, "sum").unwrap();
+        let log = planner
+            .call_address::<Events::logUintCall>(addr(), vec![sum.clone().into()])
+            .unwrap();
+
+        let commands = planner.commands();
+        assert_eq!(commands.len(), 2);
+        assert_eq!(commands[0].label, Some("sum"));
+        assert_eq!(commands[0].call_type, CallType::Call);
+        assert_eq!(commands[0].selector, Math::addCall::SELECTOR);
+        assert_eq!(commands[0].dependents, vec![1]);
+        assert!(matches!(commands[1].args[0], ArgView::Return(ref ret) if *ret == sum));
+        assert_eq!(commands[1].output, log);
+        assert!(commands[1].dependents.is_empty());
+    }
+
+    #[test]
+    fn edits_respect_dependents() {
+        let other = address!("0x1111111111111111111111111111111111111111");
+        let mut planner = Planner::default();
+        let sum = planner
+            .call_address::<Math::addCall>(addr(), vec![U256::from(1).into(), U256::from(2).into()])
+            .unwrap();
+        let log = planner
+            .call_address::<Events::logUintCall>(addr(), vec![sum.clone().into()])
+            .unwrap();
+
+        assert!(matches!(
+            planner.remove(&sum),
+            Err(WeirollError::HasDependents { ref dependents, .. }) if *dependents == vec![1]
+        ));
+        assert!(matches!(
+            planner.replace_args(&sum, vec![U256::from(1).into(), log.clone().into()]),
+            Err(WeirollError::CommandNotVisible { argument: 1, .. })
+        ));
+
+        planner.set_target(&log, other).unwrap();
+        planner
+            .replace_args(&log, vec![U256::from(5).into()])
+            .unwrap();
+        planner.remove(&sum).unwrap();
+        assert_eq!(planner.remove(&sum), Err(WeirollError::UnknownReturnValue));
+
+        let commands = planner.commands();
+        assert_eq!(commands.len(), 1);
+        assert_eq!(commands[0].target, other);
+        assert!(matches!(commands[0].args[0], ArgView::Literal(_)));
+    }
+
+    #[test]
+    fn replace_args_plans_builders_before_command() {
+        let mut planner = Planner::default();
+        planner.set_builder(address!("0x2222222222222222222222222222222222222222"));
+        let a = planner
+            .call_address::<Math::addCall>(addr(), vec![U256::from(1).into(), U256::from(2).into()])
+            .unwrap();
+        let total = planner
+            .call_address::<Math::sumCall>(addr(), vec![Value::array([])])
+            .unwrap();
+        let late = planner
+            .call_address::<Math::addCall>(addr(), vec![U256::from(3).into(), U256::from(4).into()])
+            .unwrap();
+
+        assert!(matches!(
+            planner.replace_args(&total, vec![Value::array([a.clone().into(), late.into()])]),
+            Err(WeirollError::CommandNotVisible { argument: 2, .. })
+        ));
+        assert_eq!(planner.commands().len(), 3);
+
+        planner
+            .replace_args(&total, vec![Value::array([a.clone().into(), a.into()])])
+            .unwrap();
+        let commands = planner.commands();
+        assert_eq!(commands.len(), 4);
+        assert_eq!(commands[2].output, total);
+        assert!(
+            matches!(&commands[2].args[..], [ArgView::Return(ret)] if *ret == commands[1].output)
+        );
+    }
+
+    #[test]
+    fn replace_args_rejects_outputs_of_other_planners() {
+        // `foreign` has the same key as `sum`, which is visible to `log`
+        let mut other = Planner::default();
+        let foreign = other
+            .call_address::<Math::addCall>(addr(), vec![U256::from(1).into(), U256::from(2).into()])
+            .unwrap();
+
+        let mut planner = Planner::default();
+        let sum = planner
+            .call_address::<Math::addCall>(addr(), vec![U256::from(3).into(), U256::from(4).into()])
+            .unwrap();
+        let log = planner
+            .call_address::<Events::logUintCall>(addr(), vec![sum.clone().into()])
+            .unwrap();
+        assert_eq!(foreign.command, sum.command);
+
+        assert!(matches!(
+            planner.replace_args(&log, vec![foreign.into()]),
+            Err(WeirollError::CommandNotVisible { argument: 0, .. })
+        ));
+        planner.replace_args(&log, vec![sum.into()]).unwrap();
+    }
+
+    #[test]
+    fn separately_built_subplans_do_not_add_dependents() {
+        // The subplan's first command has the same key as `a`
+        let mut subplanner = Planner::default();
+        let own = subplanner
+            .call_address::<Math::addCall>(addr(), vec![U256::from(1).into(), U256::from(2).into()])
+            .unwrap();
+        subplanner
+            .call_address::<Events::logUintCall>(addr(), vec![own.clone().into()])
+            .unwrap();
+
+        let mut planner = Planner::default();
+        let a = planner
+            .call_address::<Math::addCall>(addr(), vec![U256::from(3).into(), U256::from(4).into()])
+            .unwrap();
+        planner
+            .add_subplan_address::<TestableVM::executeCall>(
+                addr(),
+                vec![Value::Subplan(&subplanner), Value::State(vec![])],
+            )
+            .unwrap();
+        assert_eq!(own.command, a.command);
+
+        assert!(planner.commands()[0].dependents.is_empty());
+        planner.remove(&a).unwrap();
+        assert_eq!(planner.commands().len(), 1);
+    }
+
+    #[test]
+    fn replace_args_keeps_subplan_and_state() {
+        let subplanner = Planner::default();
+        let mut planner = Planner::default();
+        let run = planner
+            .add_subplan_address::<TestableVM::executeCall>(
+                addr(),
+                vec![Value::Subplan(&subplanner), Value::State(vec![])],
+            )
+            .unwrap();
+
+        assert!(matches!(
+            planner.replace_args(
+                &run,
+                vec![Value::Subplan(&subplanner), Value::Subplan(&subplanner)]
+            ),
+            Err(WeirollError::MultipleSubplans { .. })
+        ));
+        assert!(matches!(
+            planner.replace_args(&run, vec![U256::from(1).into(), Value::State(vec![])]),
+            Err(WeirollError::MissingStateOrSubplan { .. })
+        ));
+        planner
+            .replace_args(
+                &run,
+                vec![Value::State(vec![]), Value::Subplan(&subplanner)],
+            )
+            .unwrap();
+    }
+}